@@ -1,6 +1,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 mod workspaces;
 
@@ -10,7 +10,12 @@ fn main() {
             let app_data_dir = app.path().app_data_dir()?;
             std::fs::create_dir_all(&app_data_dir)?;
             let workspace_state = workspaces::WorkspaceState::new(app_data_dir)?;
+            let startup_notices = workspace_state.pending_startup_notices();
             app.manage(workspace_state);
+            workspaces::pr_watch::spawn_pull_request_watcher(app.handle().clone());
+            for notice in startup_notices {
+                app.emit(workspaces::STARTUP_NOTICE_EVENT, notice)?;
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -18,6 +23,7 @@ fn main() {
             workspaces::commands::workspace_get_active_id,
             workspaces::commands::workspace_set_active,
             workspaces::commands::workspace_create_local,
+            workspaces::commands::workspace_fork,
             workspaces::commands::workspace_create_github,
             workspaces::commands::workspace_create_new_github,
             workspaces::commands::workspace_list_github_repos,
@@ -26,9 +32,46 @@ fn main() {
             workspaces::commands::workspace_list_repo_branches,
             workspaces::commands::workspace_list_repo_issues,
             workspaces::commands::workspace_create_from_source,
+            workspaces::commands::workspace_retry_create,
+            workspaces::commands::workspace_cancel_create,
+            workspaces::commands::workspace_watch_progress,
+            workspaces::commands::workspace_list_importable_worktrees,
+            workspaces::commands::workspace_import_existing,
             workspaces::commands::workspace_pick_directory,
             workspaces::commands::workspace_archive,
-            workspaces::commands::workspace_delete
+            workspaces::commands::workspace_rename,
+            workspaces::commands::workspace_set_sparse_paths,
+            workspaces::commands::workspace_delete,
+            workspaces::commands::workspace_list_registry_backups,
+            workspaces::commands::workspace_restore_registry_backup,
+            workspaces::commands::workspace_take_startup_notices,
+            workspaces::commands::workspace_doctor,
+            workspaces::commands::workspace_doctor_repair,
+            workspaces::commands::workspace_status,
+            workspaces::commands::workspace_diff,
+            workspaces::commands::workspace_stage,
+            workspaces::commands::workspace_unstage,
+            workspaces::commands::workspace_discard,
+            workspaces::commands::workspace_commit,
+            workspaces::commands::workspace_sync,
+            workspaces::commands::workspace_sync_continue,
+            workspaces::commands::workspace_sync_abort,
+            workspaces::commands::workspace_open_pull_request,
+            workspaces::commands::workspace_refresh_pull_requests,
+            workspaces::commands::workspace_get_auto_archive_policy,
+            workspaces::commands::workspace_set_auto_archive_policy,
+            workspaces::commands::workspace_get_clone_strategy,
+            workspaces::commands::workspace_set_clone_strategy,
+            workspaces::commands::workspace_cache_inventory,
+            workspaces::commands::workspace_cache_maintain,
+            workspaces::commands::workspace_cache_evict,
+            workspaces::commands::workspace_get_cache_budget,
+            workspaces::commands::workspace_set_cache_budget,
+            workspaces::commands::workspace_checks,
+            workspaces::commands::workspace_checkpoint_create,
+            workspaces::commands::workspace_checkpoint_list,
+            workspaces::commands::workspace_checkpoint_diff,
+            workspaces::commands::workspace_checkpoint_restore
         ])
         .run(tauri::generate_context!())
         .unwrap_or_else(|err| {
@@ -0,0 +1,421 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use super::git_diff::{read_diff, DiffOptions, WorkspaceDiff};
+use super::git_local::run_git;
+use super::process::{git_timeout, run_command, RunLimits};
+use super::WorkspaceError;
+
+/// Namespace holding checkpoint refs, one sub-namespace per workspace id.
+pub const CHECKPOINT_REF_PREFIX: &str = "refs/kata/checkpoints";
+
+/// Identity used for checkpoint commits, which never leave the repository.
+const CHECKPOINT_AUTHOR: (&str, &str) = ("Kata Checkpoints", "checkpoints@kata.local");
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// Position in `refs/kata/checkpoints/<workspace>/<index>`.
+    pub index: u32,
+    pub name: String,
+    /// Commit whose tree is the working tree, including untracked files.
+    pub sha: String,
+    /// `HEAD` when the checkpoint was taken.
+    pub head: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateCheckpointInput {
+    pub workspace_id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointDiffInput {
+    pub workspace_id: String,
+    pub checkpoint: u32,
+    /// Checkpoint to compare with; the current state of the worktree if unset.
+    pub against: Option<u32>,
+    #[serde(default)]
+    pub options: DiffOptions,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreCheckpointInput {
+    pub workspace_id: String,
+    pub checkpoint: u32,
+    /// Also reset the branch to the `HEAD` recorded by the checkpoint.
+    #[serde(default)]
+    pub reset_head: bool,
+}
+
+fn checkpoint_ref(workspace_id: &str, index: u32) -> String {
+    format!("{CHECKPOINT_REF_PREFIX}/{workspace_id}/{index}")
+}
+
+/// Records the index, working tree and untracked files of the worktree as
+/// a checkpoint. Like `git stash`, the checkpoint commit has `HEAD` and a
+/// commit of the index as parents; unlike it, nothing in the worktree changes.
+pub fn create_checkpoint(
+    worktree_path: &Path,
+    workspace_id: &str,
+    name: &str,
+) -> Result<Checkpoint, WorkspaceError> {
+    let name = name.trim();
+    let name = if name.is_empty() { "Checkpoint" } else { name };
+    let head = run_git(worktree_path, &["rev-parse", "--verify", "HEAD"])?;
+    let index_tree = run_git(worktree_path, &["write-tree"])?;
+    let worktree_tree = snapshot_tree(worktree_path)?;
+
+    let index_commit = commit_tree(
+        worktree_path,
+        &index_tree,
+        &[&head],
+        &format!("index of {name}"),
+    )?;
+    let sha = commit_tree(worktree_path, &worktree_tree, &[&head, &index_commit], name)?;
+
+    let index = list_checkpoints(worktree_path, workspace_id)?
+        .last()
+        .map_or(1, |checkpoint| checkpoint.index + 1);
+    let reference = checkpoint_ref(workspace_id, index);
+    // The empty old value makes the update fail if the ref already exists.
+    run_git(worktree_path, &["update-ref", &reference, &sha, ""])?;
+
+    list_checkpoints(worktree_path, workspace_id)?
+        .into_iter()
+        .find(|checkpoint| checkpoint.index == index)
+        .ok_or_else(|| {
+            WorkspaceError::GitFailed(format!("Checkpoint was not recorded: {reference}"))
+        })
+}
+
+/// Checkpoints of a workspace, oldest first.
+pub fn list_checkpoints(
+    repo_path: &Path,
+    workspace_id: &str,
+) -> Result<Vec<Checkpoint>, WorkspaceError> {
+    let prefix = format!("{CHECKPOINT_REF_PREFIX}/{workspace_id}/");
+    let output = run_git(
+        repo_path,
+        &[
+            "for-each-ref",
+            "--format=%(refname)%00%(objectname)%00%(parent)%00%(creatordate:iso-strict)%00%(contents:subject)",
+            prefix.trim_end_matches('/'),
+        ],
+    )?;
+    let mut checkpoints = output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\0');
+            let index = fields.next()?.strip_prefix(&prefix)?.parse::<u32>().ok()?;
+            let sha = fields.next()?.to_string();
+            let head = fields.next()?.split(' ').next()?.to_string();
+            let created_at = fields.next()?.to_string();
+            let name = fields.next().unwrap_or_default().to_string();
+            Some(Checkpoint {
+                index,
+                name,
+                sha,
+                head,
+                created_at,
+            })
+        })
+        .collect::<Vec<_>>();
+    checkpoints.sort_by_key(|checkpoint| checkpoint.index);
+    Ok(checkpoints)
+}
+
+/// Diffs a checkpoint against another one, or against the current state of
+/// the worktree (untracked files included).
+pub fn diff_checkpoint(
+    worktree_path: &Path,
+    workspace_id: &str,
+    checkpoint: u32,
+    against: Option<u32>,
+    options: &DiffOptions,
+) -> Result<WorkspaceDiff, WorkspaceError> {
+    let from = find_checkpoint(worktree_path, workspace_id, checkpoint)?;
+    let (to_tree, to_commit) = match against {
+        Some(index) => {
+            let to = find_checkpoint(worktree_path, workspace_id, index)?;
+            (format!("{}^{{tree}}", to.sha), Some(to.sha))
+        }
+        None => (snapshot_tree(worktree_path)?, None),
+    };
+    let range_args = vec![format!("{}^{{tree}}", from.sha), to_tree];
+    let mut diff = read_diff(worktree_path, &range_args, options)?;
+    diff.from_commit = Some(from.sha);
+    diff.to_commit = to_commit;
+    Ok(diff)
+}
+
+/// Brings the index, working tree and untracked files back to a checkpoint.
+/// The current state is checkpointed first, and that checkpoint is returned
+/// so the restore can itself be undone.
+pub fn restore_checkpoint(
+    worktree_path: &Path,
+    workspace_id: &str,
+    checkpoint: u32,
+    reset_head: bool,
+) -> Result<Checkpoint, WorkspaceError> {
+    let target = find_checkpoint(worktree_path, workspace_id, checkpoint)?;
+    let backup = create_checkpoint(
+        worktree_path,
+        workspace_id,
+        &format!("Before restoring \"{}\"", target.name),
+    )?;
+
+    if reset_head {
+        run_git(worktree_path, &["reset", "--soft", &target.head])?;
+    }
+    // Check the full snapshot out (formerly untracked files become tracked),
+    // drop files that did not exist then, then put the recorded index back
+    // without touching the files.
+    run_git(
+        worktree_path,
+        &[
+            "read-tree",
+            "--reset",
+            "-u",
+            &format!("{}^{{tree}}", target.sha),
+        ],
+    )?;
+    run_git(worktree_path, &["clean", "-fd", "--quiet"])?;
+    run_git(
+        worktree_path,
+        &["read-tree", &format!("{}^2^{{tree}}", target.sha)],
+    )?;
+    Ok(backup)
+}
+
+/// Removes every checkpoint ref of a workspace. The commits are left for
+/// `git gc` to collect.
+pub fn delete_checkpoints(repo_path: &Path, workspace_id: &str) -> Result<(), WorkspaceError> {
+    for checkpoint in list_checkpoints(repo_path, workspace_id)? {
+        run_git(
+            repo_path,
+            &[
+                "update-ref",
+                "-d",
+                &checkpoint_ref(workspace_id, checkpoint.index),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn find_checkpoint(
+    repo_path: &Path,
+    workspace_id: &str,
+    index: u32,
+) -> Result<Checkpoint, WorkspaceError> {
+    list_checkpoints(repo_path, workspace_id)?
+        .into_iter()
+        .find(|checkpoint| checkpoint.index == index)
+        .ok_or_else(|| WorkspaceError::NotFound(format!("Checkpoint not found: {index}")))
+}
+
+/// Writes a tree of the working tree including untracked (but not ignored)
+/// files, using a scratch index so the real one is left alone.
+fn snapshot_tree(worktree_path: &Path) -> Result<String, WorkspaceError> {
+    let index_path = PathBuf::from(run_git(
+        worktree_path,
+        &[
+            "rev-parse",
+            "--path-format=absolute",
+            "--git-path",
+            "kata-checkpoint-index",
+        ],
+    )?);
+    let result = run_git(worktree_path, &["write-tree"])
+        .and_then(|index_tree| {
+            run_git_with_index(worktree_path, &index_path, &["read-tree", &index_tree])
+        })
+        .and_then(|_| run_git_with_index(worktree_path, &index_path, &["add", "--all"]))
+        .and_then(|_| run_git_with_index(worktree_path, &index_path, &["write-tree"]));
+    let _ = fs::remove_file(&index_path);
+    result
+}
+
+fn commit_tree(
+    repo_path: &Path,
+    tree: &str,
+    parents: &[&str],
+    message: &str,
+) -> Result<String, WorkspaceError> {
+    let mut command = Command::new("git");
+    command
+        .current_dir(repo_path)
+        .env("GIT_AUTHOR_NAME", CHECKPOINT_AUTHOR.0)
+        .env("GIT_AUTHOR_EMAIL", CHECKPOINT_AUTHOR.1)
+        .env("GIT_COMMITTER_NAME", CHECKPOINT_AUTHOR.0)
+        .env("GIT_COMMITTER_EMAIL", CHECKPOINT_AUTHOR.1)
+        .args(["commit-tree", tree, "-m", message]);
+    for parent in parents {
+        command.args(["-p", parent]);
+    }
+    git_output(command, &["commit-tree"])
+}
+
+fn run_git_with_index(
+    repo_path: &Path,
+    index_path: &Path,
+    args: &[&str],
+) -> Result<String, WorkspaceError> {
+    let mut command = Command::new("git");
+    command
+        .current_dir(repo_path)
+        .env("GIT_INDEX_FILE", index_path)
+        .args(args);
+    git_output(command, args)
+}
+
+fn git_output(command: Command, args: &[&str]) -> Result<String, WorkspaceError> {
+    let description = format!("git {}", args.join(" "));
+    let output = run_command(command, &description, RunLimits::timeout(git_timeout(args)))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(WorkspaceError::GitFailed(format!(
+            "{description} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::{
+        create_checkpoint, delete_checkpoints, diff_checkpoint, list_checkpoints,
+        restore_checkpoint,
+    };
+    use crate::workspaces::git_diff::{DiffFileStatus, DiffOptions};
+    use crate::workspaces::test_support::git;
+
+    fn repo(root: &Path) -> std::path::PathBuf {
+        let repo = root.join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-b", "main"]);
+        fs::write(repo.join(".gitignore"), "target/\n").unwrap();
+        fs::write(repo.join("a.txt"), "a\n").unwrap();
+        fs::write(repo.join("b.txt"), "b\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-m", "init"]);
+        repo
+    }
+
+    #[test]
+    fn checkpoints_without_touching_index_or_worktree() {
+        let dir = tempdir().unwrap();
+        let repo = repo(dir.path());
+        fs::write(repo.join("a.txt"), "a staged\n").unwrap();
+        git(&repo, &["add", "a.txt"]);
+        fs::write(repo.join("b.txt"), "b unstaged\n").unwrap();
+        fs::write(repo.join("new.txt"), "untracked\n").unwrap();
+        let status_before = git(&repo, &["status", "--porcelain"]);
+        let head = git(&repo, &["rev-parse", "HEAD"]);
+
+        let first = create_checkpoint(&repo, "ws_1", "first").unwrap();
+        let second = create_checkpoint(&repo, "ws_1", "").unwrap();
+
+        assert_eq!(git(&repo, &["status", "--porcelain"]), status_before);
+        assert_eq!(git(&repo, &["rev-parse", "HEAD"]), head);
+        assert_eq!((first.index, second.index), (1, 2));
+        assert_eq!(first.name, "first");
+        assert_eq!(second.name, "Checkpoint");
+        assert_eq!(first.head, head);
+        assert_eq!(
+            git(&repo, &["show", &format!("{}:new.txt", first.sha)]),
+            "untracked"
+        );
+        assert_eq!(list_checkpoints(&repo, "ws_1").unwrap().len(), 2);
+        assert!(list_checkpoints(&repo, "ws_2").unwrap().is_empty());
+    }
+
+    #[test]
+    fn diffs_checkpoint_against_current_state() {
+        let dir = tempdir().unwrap();
+        let repo = repo(dir.path());
+        create_checkpoint(&repo, "ws_1", "clean").unwrap();
+        fs::write(repo.join("a.txt"), "a changed\n").unwrap();
+        fs::write(repo.join("new.txt"), "untracked\n").unwrap();
+
+        let diff = diff_checkpoint(&repo, "ws_1", 1, None, &DiffOptions::default()).unwrap();
+
+        let mut files = diff
+            .files
+            .iter()
+            .map(|file| (file.path.as_str(), file.status))
+            .collect::<Vec<_>>();
+        files.sort_by_key(|(path, _)| *path);
+        assert_eq!(
+            files,
+            vec![
+                ("a.txt", DiffFileStatus::Modified),
+                ("new.txt", DiffFileStatus::Added)
+            ]
+        );
+    }
+
+    #[test]
+    fn restores_index_worktree_and_untracked_files() {
+        let dir = tempdir().unwrap();
+        let repo = repo(dir.path());
+        fs::write(repo.join("a.txt"), "a staged\n").unwrap();
+        git(&repo, &["add", "a.txt"]);
+        fs::write(repo.join("b.txt"), "b unstaged\n").unwrap();
+        fs::write(repo.join("new.txt"), "untracked\n").unwrap();
+        let status_at_checkpoint = git(&repo, &["status", "--porcelain"]);
+        create_checkpoint(&repo, "ws_1", "good").unwrap();
+
+        // An agent trashes the worktree and commits on top.
+        fs::remove_file(repo.join("new.txt")).unwrap();
+        fs::write(repo.join("a.txt"), "broken\n").unwrap();
+        fs::write(repo.join("junk.txt"), "junk\n").unwrap();
+        fs::create_dir_all(repo.join("target")).unwrap();
+        fs::write(repo.join("target").join("cache"), "ignored\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-m", "oops"]);
+
+        let backup = restore_checkpoint(&repo, "ws_1", 1, true).unwrap();
+
+        assert_eq!(git(&repo, &["status", "--porcelain"]), status_at_checkpoint);
+        assert_eq!(
+            fs::read_to_string(repo.join("b.txt")).unwrap(),
+            "b unstaged\n"
+        );
+        assert!(!repo.join("junk.txt").exists());
+        assert!(repo.join("target").join("cache").exists());
+        assert_eq!(backup.index, 2);
+        assert_eq!(
+            git(&repo, &["log", "-1", "--format=%s", &backup.head]),
+            "oops"
+        );
+    }
+
+    #[test]
+    fn deletes_only_the_workspace_checkpoints() {
+        let dir = tempdir().unwrap();
+        let repo = repo(dir.path());
+        create_checkpoint(&repo, "ws_1", "one").unwrap();
+        create_checkpoint(&repo, "ws_2", "two").unwrap();
+
+        delete_checkpoints(&repo, "ws_1").unwrap();
+
+        assert!(list_checkpoints(&repo, "ws_1").unwrap().is_empty());
+        assert_eq!(list_checkpoints(&repo, "ws_2").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,554 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::git_github::{run_gh, workspace_repo_id};
+use super::git_local::run_git;
+use super::model::{CheckRollup, Workspace};
+use super::WorkspaceError;
+
+/// Size of the tail of a failed job log returned inline. The full log is
+/// kept on disk.
+pub const CI_LOG_EXCERPT_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckRunStatus {
+    Queued,
+    InProgress,
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckConclusion {
+    Success,
+    Failure,
+    Neutral,
+    Cancelled,
+    Skipped,
+    TimedOut,
+    ActionRequired,
+    Stale,
+    StartupFailure,
+}
+
+impl CheckConclusion {
+    /// Parses a check run conclusion or commit status state, in the upper
+    /// case GraphQL or lower case REST spelling.
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_uppercase().as_str() {
+            "SUCCESS" => Some(Self::Success),
+            "FAILURE" | "ERROR" => Some(Self::Failure),
+            "NEUTRAL" => Some(Self::Neutral),
+            "CANCELLED" => Some(Self::Cancelled),
+            "SKIPPED" => Some(Self::Skipped),
+            "TIMED_OUT" => Some(Self::TimedOut),
+            "ACTION_REQUIRED" => Some(Self::ActionRequired),
+            "STALE" => Some(Self::Stale),
+            "STARTUP_FAILURE" => Some(Self::StartupFailure),
+            _ => None,
+        }
+    }
+
+    pub fn is_failure(self) -> bool {
+        matches!(
+            self,
+            Self::Failure
+                | Self::Cancelled
+                | Self::TimedOut
+                | Self::ActionRequired
+                | Self::StartupFailure
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckRun {
+    pub name: String,
+    /// Workflow of a GitHub Actions check. Only known for pull request checks.
+    pub workflow_name: Option<String>,
+    pub status: CheckRunStatus,
+    /// Set once the check has completed.
+    pub conclusion: Option<CheckConclusion>,
+    pub details_url: Option<String>,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+    /// GitHub Actions run and job, parsed from `details_url`.
+    pub run_id: Option<u64>,
+    pub job_id: Option<u64>,
+}
+
+impl CheckRun {
+    pub fn has_failed(&self) -> bool {
+        self.conclusion.is_some_and(CheckConclusion::is_failure)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedJobLog {
+    pub check_name: String,
+    pub run_id: u64,
+    pub job_id: Option<u64>,
+    /// Full `gh run view --log-failed` output on disk.
+    pub path: String,
+    /// The last `CI_LOG_EXCERPT_BYTES` of the log, starting on a line boundary.
+    pub excerpt: String,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceChecks {
+    pub head_sha: String,
+    /// Set when the checks were read from the workspace pull request rather
+    /// than the local `HEAD` commit.
+    pub pull_request_number: Option<u32>,
+    pub rollup: CheckRollup,
+    pub checks: Vec<CheckRun>,
+    pub failed_logs: Vec<FailedJobLog>,
+    /// Failed checks whose log could not be fetched, with the reason.
+    pub log_errors: Vec<String>,
+}
+
+/// One entry of a pull request `statusCheckRollup`, or of the REST
+/// `check-runs` list: either a check run (`status`/`conclusion`) or a
+/// commit status (`context`/`state`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusCheckRollupItem {
+    name: Option<String>,
+    context: Option<String>,
+    workflow_name: Option<String>,
+    status: Option<String>,
+    conclusion: Option<String>,
+    state: Option<String>,
+    #[serde(alias = "details_url")]
+    details_url: Option<String>,
+    target_url: Option<String>,
+    #[serde(alias = "started_at")]
+    started_at: Option<String>,
+    #[serde(alias = "completed_at")]
+    completed_at: Option<String>,
+}
+
+impl StatusCheckRollupItem {
+    pub fn into_check_run(self) -> CheckRun {
+        let (status, conclusion) = match self.state.as_deref() {
+            Some(state) => match CheckConclusion::parse(state) {
+                Some(conclusion) => (CheckRunStatus::Completed, Some(conclusion)),
+                None => (CheckRunStatus::InProgress, None),
+            },
+            None => match self
+                .status
+                .as_deref()
+                .map(str::to_ascii_uppercase)
+                .as_deref()
+            {
+                Some("COMPLETED") => (
+                    CheckRunStatus::Completed,
+                    self.conclusion.as_deref().and_then(CheckConclusion::parse),
+                ),
+                Some("IN_PROGRESS") => (CheckRunStatus::InProgress, None),
+                _ => (CheckRunStatus::Queued, None),
+            },
+        };
+        let details_url = self
+            .details_url
+            .or(self.target_url)
+            .filter(|url| !url.is_empty());
+        let (run_id, job_id) = details_url
+            .as_deref()
+            .map_or((None, None), actions_ids_from_url);
+        CheckRun {
+            name: self.name.or(self.context).unwrap_or_default(),
+            workflow_name: self.workflow_name.filter(|name| !name.is_empty()),
+            status,
+            conclusion,
+            details_url,
+            started_at: self.started_at.and_then(non_zero_timestamp),
+            completed_at: self.completed_at.and_then(non_zero_timestamp),
+            run_id,
+            job_id,
+        }
+    }
+}
+
+/// Combines check runs into one state: any failure wins over anything still
+/// running, which wins over success.
+pub fn check_rollup(checks: &[CheckRun]) -> CheckRollup {
+    if checks.is_empty() {
+        CheckRollup::None
+    } else if checks.iter().any(CheckRun::has_failed) {
+        CheckRollup::Failing
+    } else if checks
+        .iter()
+        .any(|check| check.status != CheckRunStatus::Completed)
+    {
+        CheckRollup::Pending
+    } else {
+        CheckRollup::Passing
+    }
+}
+
+pub fn ci_logs_dir(app_data_dir: &Path, workspace_id: &str) -> PathBuf {
+    app_data_dir
+        .join("workspace-data")
+        .join(workspace_id)
+        .join("ci-logs")
+}
+
+/// Removes the job logs saved for a workspace, along with its data directory
+/// once nothing else is left in it.
+pub fn delete_ci_logs(app_data_dir: &Path, workspace_id: &str) -> Result<(), WorkspaceError> {
+    let logs_dir = ci_logs_dir(app_data_dir, workspace_id);
+    if logs_dir.exists() {
+        fs::remove_dir_all(&logs_dir)?;
+    }
+    if let Some(data_dir) = logs_dir.parent() {
+        let _ = fs::remove_dir(data_dir);
+    }
+    Ok(())
+}
+
+/// Lists the checks on the workspace pull request, or on the workspace `HEAD`
+/// commit when it has none, and saves the failed job log of every failed
+/// GitHub Actions check under `logs_dir`.
+pub fn read_workspace_checks(
+    workspace: &Workspace,
+    logs_dir: &Path,
+) -> Result<WorkspaceChecks, WorkspaceError> {
+    let repo_id = workspace_repo_id(workspace)?;
+    let (head_sha, pull_request_number, checks) = match workspace.pull_request.as_ref() {
+        Some(pull_request) => {
+            let output = run_gh(
+                Path::new("."),
+                &[
+                    "pr",
+                    "view",
+                    "--repo",
+                    &repo_id,
+                    &pull_request.number.to_string(),
+                    "--json",
+                    "headRefOid,statusCheckRollup",
+                ],
+            )?;
+            let (head_sha, checks) = parse_pull_request_checks(&output)?;
+            (head_sha, Some(pull_request.number), checks)
+        }
+        None => {
+            let head_sha = run_git(Path::new(&workspace.worktree_path), &["rev-parse", "HEAD"])?;
+            let output = run_gh(
+                Path::new("."),
+                &[
+                    "api",
+                    &format!("repos/{repo_id}/commits/{head_sha}/check-runs?per_page=100"),
+                ],
+            )?;
+            let checks = parse_commit_check_runs(&output)?;
+            (head_sha, None, checks)
+        }
+    };
+
+    let mut failed_logs = Vec::new();
+    let mut log_errors = Vec::new();
+    let mut fetched = HashSet::new();
+    for check in checks
+        .iter()
+        .filter(|check| check.status == CheckRunStatus::Completed && check.has_failed())
+    {
+        let Some(run_id) = check.run_id else {
+            continue;
+        };
+        if !fetched.insert((run_id, check.job_id)) {
+            continue;
+        }
+        match fetch_failed_log(&repo_id, check, run_id, logs_dir) {
+            Ok(log) => failed_logs.push(log),
+            Err(err) => log_errors.push(format!("{}: {err}", check.name)),
+        }
+    }
+
+    Ok(WorkspaceChecks {
+        head_sha,
+        pull_request_number,
+        rollup: check_rollup(&checks),
+        checks,
+        failed_logs,
+        log_errors,
+    })
+}
+
+fn parse_pull_request_checks(output: &str) -> Result<(String, Vec<CheckRun>), WorkspaceError> {
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ChecksView {
+        head_ref_oid: String,
+        #[serde(default)]
+        status_check_rollup: Option<Vec<StatusCheckRollupItem>>,
+    }
+
+    let view = serde_json::from_str::<ChecksView>(output).map_err(|err| {
+        WorkspaceError::GitFailed(format!("Failed to parse pull request checks: {err}"))
+    })?;
+    let checks = view
+        .status_check_rollup
+        .unwrap_or_default()
+        .into_iter()
+        .map(StatusCheckRollupItem::into_check_run)
+        .collect();
+    Ok((view.head_ref_oid, checks))
+}
+
+fn parse_commit_check_runs(output: &str) -> Result<Vec<CheckRun>, WorkspaceError> {
+    #[derive(Debug, Deserialize)]
+    struct CheckRunsResponse {
+        check_runs: Vec<StatusCheckRollupItem>,
+    }
+
+    let response = serde_json::from_str::<CheckRunsResponse>(output)
+        .map_err(|err| WorkspaceError::GitFailed(format!("Failed to parse check runs: {err}")))?;
+    Ok(response
+        .check_runs
+        .into_iter()
+        .map(StatusCheckRollupItem::into_check_run)
+        .collect())
+}
+
+/// Reuses a log saved by an earlier call: logs of completed jobs don't change.
+/// An empty log is not saved, so a log that wasn't available yet is fetched
+/// again on the next call.
+fn fetch_failed_log(
+    repo_id: &str,
+    check: &CheckRun,
+    run_id: u64,
+    logs_dir: &Path,
+) -> Result<FailedJobLog, WorkspaceError> {
+    let run_arg = run_id.to_string();
+    let job_arg = check.job_id.map(|job_id| job_id.to_string());
+    let path = match job_arg.as_deref() {
+        Some(job_id) => logs_dir.join(format!("{run_id}-{job_id}.log")),
+        None => logs_dir.join(format!("{run_id}.log")),
+    };
+
+    let content = match read_saved_log(&path) {
+        Some(saved) => saved,
+        None => {
+            let mut args = vec!["run", "view", "--repo", repo_id];
+            match job_arg.as_deref() {
+                Some(job_id) => args.extend(["--job", job_id]),
+                None => args.push(&run_arg),
+            }
+            args.push("--log-failed");
+            let log = run_gh(Path::new("."), &args)?;
+            if !log.trim().is_empty() {
+                fs::create_dir_all(logs_dir)?;
+                fs::write(&path, &log)?;
+            }
+            log
+        }
+    };
+    let (excerpt, truncated) = log_excerpt(&content, CI_LOG_EXCERPT_BYTES);
+    Ok(FailedJobLog {
+        check_name: check.name.clone(),
+        run_id,
+        job_id: check.job_id,
+        path: path.to_string_lossy().to_string(),
+        excerpt,
+        truncated,
+    })
+}
+
+/// The log saved at `path`, unless it is missing or empty.
+fn read_saved_log(path: &Path) -> Option<String> {
+    let saved = fs::read(path).ok()?;
+    let saved = String::from_utf8_lossy(&saved).into_owned();
+    (!saved.trim().is_empty()).then_some(saved)
+}
+
+/// Keeps the end of `log` (where the failure usually is), dropping the first
+/// partial line.
+fn log_excerpt(log: &str, max_bytes: usize) -> (String, bool) {
+    if log.len() <= max_bytes {
+        return (log.to_string(), false);
+    }
+    let mut start = log.len() - max_bytes;
+    while !log.is_char_boundary(start) {
+        start += 1;
+    }
+    let start = log[start..]
+        .find('\n')
+        .map_or(start, |offset| start + offset + 1);
+    (log[start..].to_string(), true)
+}
+
+/// Extracts `(run_id, job_id)` from an Actions URL such as
+/// `https://github.com/<owner>/<repo>/actions/runs/<run>/job/<job>`.
+fn actions_ids_from_url(url: &str) -> (Option<u64>, Option<u64>) {
+    let segments = url.split(['/', '?', '#']).collect::<Vec<_>>();
+    let id_after = |marker: &str| {
+        segments
+            .windows(2)
+            .find(|pair| pair[0] == marker)
+            .and_then(|pair| pair[1].parse::<u64>().ok())
+    };
+    if !url.contains("/actions/runs/") {
+        return (None, None);
+    }
+    (id_after("runs"), id_after("job"))
+}
+
+/// GitHub reports unset times as `0001-01-01T00:00:00Z`.
+fn non_zero_timestamp(value: String) -> Option<String> {
+    (!value.is_empty() && !value.starts_with("0001-")).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{
+        actions_ids_from_url, check_rollup, ci_logs_dir, delete_ci_logs, log_excerpt,
+        parse_commit_check_runs, parse_pull_request_checks, read_saved_log, CheckConclusion,
+        CheckRunStatus,
+    };
+    use crate::workspaces::model::CheckRollup;
+
+    #[test]
+    fn parses_check_runs_and_commit_statuses_from_pull_request_rollup() {
+        let output = r#"{
+            "headRefOid": "abc123",
+            "statusCheckRollup": [
+                {
+                    "__typename": "CheckRun",
+                    "name": "test (ubuntu-latest)",
+                    "workflowName": "CI",
+                    "status": "COMPLETED",
+                    "conclusion": "FAILURE",
+                    "detailsUrl": "https://github.com/acme/widgets/actions/runs/101/job/202",
+                    "startedAt": "2026-10-01T10:00:00Z",
+                    "completedAt": "2026-10-01T10:05:00Z"
+                },
+                {
+                    "__typename": "CheckRun",
+                    "name": "lint",
+                    "workflowName": "CI",
+                    "status": "IN_PROGRESS",
+                    "conclusion": "",
+                    "detailsUrl": "https://github.com/acme/widgets/actions/runs/101/job/203",
+                    "startedAt": "2026-10-01T10:00:00Z",
+                    "completedAt": "0001-01-01T00:00:00Z"
+                },
+                {
+                    "__typename": "StatusContext",
+                    "context": "ci/legacy",
+                    "state": "SUCCESS",
+                    "targetUrl": "https://ci.example.com/build/9",
+                    "startedAt": "2026-10-01T10:00:00Z"
+                }
+            ]
+        }"#;
+
+        let (head_sha, checks) = parse_pull_request_checks(output).unwrap();
+
+        assert_eq!(head_sha, "abc123");
+        assert_eq!(checks.len(), 3);
+        assert_eq!(checks[0].conclusion, Some(CheckConclusion::Failure));
+        assert_eq!(checks[0].workflow_name.as_deref(), Some("CI"));
+        assert_eq!((checks[0].run_id, checks[0].job_id), (Some(101), Some(202)));
+        assert_eq!(checks[1].status, CheckRunStatus::InProgress);
+        assert_eq!(checks[1].completed_at, None);
+        assert_eq!(checks[2].name, "ci/legacy");
+        assert_eq!(checks[2].status, CheckRunStatus::Completed);
+        assert_eq!((checks[2].run_id, checks[2].job_id), (None, None));
+        assert_eq!(check_rollup(&checks), CheckRollup::Failing);
+    }
+
+    #[test]
+    fn parses_rest_check_runs_for_a_commit() {
+        let output = r#"{
+            "total_count": 2,
+            "check_runs": [
+                {
+                    "name": "build",
+                    "status": "completed",
+                    "conclusion": "success",
+                    "details_url": "https://github.com/acme/widgets/actions/runs/7/job/8",
+                    "started_at": "2026-10-01T10:00:00Z",
+                    "completed_at": "2026-10-01T10:02:00Z"
+                },
+                {
+                    "name": "deploy",
+                    "status": "queued",
+                    "conclusion": null,
+                    "details_url": "https://github.com/acme/widgets/actions/runs/7/job/9",
+                    "started_at": null,
+                    "completed_at": null
+                }
+            ]
+        }"#;
+
+        let checks = parse_commit_check_runs(output).unwrap();
+
+        assert_eq!(checks[0].conclusion, Some(CheckConclusion::Success));
+        assert_eq!(
+            checks[0].started_at.as_deref(),
+            Some("2026-10-01T10:00:00Z")
+        );
+        assert_eq!(checks[1].status, CheckRunStatus::Queued);
+        assert_eq!(check_rollup(&checks), CheckRollup::Pending);
+        assert_eq!(check_rollup(&[]), CheckRollup::None);
+    }
+
+    #[test]
+    fn reads_actions_ids_only_from_actions_urls() {
+        assert_eq!(
+            actions_ids_from_url(
+                "https://github.com/acme/widgets/actions/runs/55?check_suite_focus=true"
+            ),
+            (Some(55), None)
+        );
+        assert_eq!(
+            actions_ids_from_url("https://ci.example.com/runs/55/job/3"),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn keeps_the_tail_of_long_logs_from_a_line_start() {
+        let log = "first line\nsecond line\nerror: boom\n";
+
+        assert_eq!(log_excerpt(log, 1024), (log.to_string(), false));
+        assert_eq!(log_excerpt(log, 16), ("error: boom\n".to_string(), true));
+    }
+
+    #[test]
+    fn treats_missing_and_empty_saved_logs_as_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("42-7.log");
+        assert_eq!(read_saved_log(&path), None);
+
+        fs::write(&path, "\n").unwrap();
+        assert_eq!(read_saved_log(&path), None);
+
+        fs::write(&path, "error: boom\n").unwrap();
+        assert_eq!(read_saved_log(&path).as_deref(), Some("error: boom\n"));
+    }
+
+    #[test]
+    fn deletes_saved_logs_with_the_workspace_data_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs_dir = ci_logs_dir(dir.path(), "ws_ab12");
+        fs::create_dir_all(&logs_dir).unwrap();
+        fs::write(logs_dir.join("42-7.log"), "error: boom\n").unwrap();
+
+        delete_ci_logs(dir.path(), "ws_ab12").unwrap();
+
+        assert!(!dir.path().join("workspace-data").join("ws_ab12").exists());
+        delete_ci_logs(dir.path(), "ws_ab12").unwrap();
+    }
+}
@@ -1,23 +1,53 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::process::Command;
 
 use serde::Serialize;
-use tauri::State;
-use uuid::Uuid;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Manager, State};
 
+use super::checkpoints::{
+    create_checkpoint, delete_checkpoints, diff_checkpoint, list_checkpoints, restore_checkpoint,
+    Checkpoint, CheckpointDiffInput, CreateCheckpointInput, RestoreCheckpointInput,
+};
+use super::ci_checks::{ci_logs_dir, delete_ci_logs, read_workspace_checks, WorkspaceChecks};
+use super::create_jobs::{
+    cancel_create_job, pending_workspace, spawn_create_job, watch_create_progress,
+};
+use super::doctor::{diagnose, prune_worktrees, re_add_worktree, DoctorRepair, DoctorReport};
+use super::fork::WorkspaceForkInput;
+use super::git_changes::{
+    commit_changes, discard_changes, ensure_branch_writable, stage_changes, unstage_changes,
+    WorkspaceChangeSelection, WorkspaceCommitInput,
+};
+use super::git_diff::{diff_workspace, WorkspaceDiff, WorkspaceDiffInput};
 use super::git_github::{
-    create_github_workspace, create_new_github_workspace, list_repo_branches,
-    list_repo_issues, list_repo_pull_requests, pull_request_head_branch,
-    repo_default_branch,
-    repo_url_from_id,
+    list_repo_branches, list_repo_issues, list_repo_pull_requests, open_pull_request,
+    repo_id_from_url, repo_url_from_id,
 };
-use super::git_local::create_local_workspace;
+use super::git_local::{normalize_sparse_paths, set_sparse_paths};
+use super::git_progress::{GitMonitor, GitProgressEvent};
+use super::git_status::{
+    read_workspace_statuses, read_worktree_status, CommitSummary, StatusCache,
+    WorkspaceStatusEntry, WorktreeStatus,
+};
+use super::git_sync::{abort_sync, continue_sync, sync_worktree, SyncOutcome, WorkspaceSyncInput};
+use super::import::{import_worktrees, list_importable_worktrees};
 use super::model::{
-    now_iso8601, CreateGitHubWorkspaceInput, CreateLocalWorkspaceInput,
-    CreateNewGitHubWorkspaceInput, CreateWorkspaceFromSourceInput, KnownRepoOption,
-    PreparedWorkspace, Workspace, WorkspaceCreateFromSource, WorkspaceSourceType,
-    WorkspaceStatus,
+    next_job_id, next_workspace_id, AutoArchivePolicy, CloneStrategy, CreateGitHubWorkspaceInput,
+    CreateLocalWorkspaceInput, CreateNewGitHubWorkspaceInput, CreateWorkspaceFromSourceInput,
+    CreateWorkspaceRequest, ImportExistingWorktreesInput, ImportableWorktree, KnownRepoOption,
+    OpenPullRequestInput, RegistryBackup, SetCloneStrategyInput, SetSparsePathsInput, Workspace,
+    WorkspaceStartupNotice, WorkspaceStatus,
+};
+use super::pr_watch::{emit_pull_request_changes, poll_pull_requests, PullRequestStatusChange};
+use super::process::{git_timeout, run_command, RunLimits, GH_TIMEOUT};
+use super::recovery::adopt_worktree;
+use super::rename::{rename_worktree, WorkspaceRenameInput};
+use super::repo_cache::{
+    enforce_cache_budget, evict_cache, maintain_cache, read_cache_inventory, CacheInventory,
+    CachedRepo,
 };
 use super::{WorkspaceError, WorkspaceState};
 
@@ -25,38 +55,46 @@ fn to_command_error(error: WorkspaceError) -> String {
     error.to_string()
 }
 
-fn next_workspace_id() -> String {
-    format!("ws_{}", Uuid::new_v4().simple())
+/// Error of the commands that change a worktree. A failed hook keeps its
+/// name, exit code and output so the UI can show them; anything else is
+/// only a message.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ChangeCommandError {
+    #[serde(rename_all = "camelCase")]
+    HookFailed {
+        message: String,
+        hook: String,
+        exit_code: Option<i32>,
+        output: String,
+    },
+    Failed {
+        message: String,
+    },
 }
 
-fn workspace_suffix(workspace_id: &str) -> &str {
-    workspace_id
-        .strip_prefix("ws_")
-        .map(|value| &value[..4.min(value.len())])
-        .unwrap_or("ws")
+impl From<WorkspaceError> for ChangeCommandError {
+    fn from(error: WorkspaceError) -> Self {
+        let message = error.to_string();
+        match error {
+            WorkspaceError::HookFailed {
+                hook,
+                exit_code,
+                output,
+            } => Self::HookFailed {
+                message,
+                hook,
+                exit_code,
+                output,
+            },
+            _ => Self::Failed { message },
+        }
+    }
 }
 
-fn build_workspace(
-    id: String,
-    name: String,
-    source_type: WorkspaceSourceType,
-    source: String,
-    prepared: PreparedWorkspace,
-) -> Workspace {
-    let timestamp = now_iso8601();
-    Workspace {
-        id,
-        name,
-        source_type,
-        source,
-        repo_root_path: prepared.repo_root_path,
-        worktree_path: prepared.worktree_path,
-        branch: prepared.branch,
-        base_ref: Some(prepared.base_ref),
-        status: WorkspaceStatus::Ready,
-        created_at: timestamp.clone(),
-        updated_at: timestamp,
-        last_opened_at: None,
+impl From<String> for ChangeCommandError {
+    fn from(message: String) -> Self {
+        Self::Failed { message }
     }
 }
 
@@ -66,6 +104,15 @@ fn lock_store(state: &WorkspaceState) -> Result<std::sync::MutexGuard<'_, super:
     })
 }
 
+fn lock_status_cache(
+    state: &WorkspaceState,
+) -> Result<std::sync::MutexGuard<'_, StatusCache>, String> {
+    state
+        .status_cache
+        .lock()
+        .map_err(|_| "Workspace state is unavailable. Please restart the application.".to_string())
+}
+
 fn persist_workspace(state: &WorkspaceState, workspace: Workspace) -> Result<Workspace, String> {
     let mut store = lock_store(state)?;
     store.insert(workspace.clone());
@@ -142,26 +189,27 @@ pub async fn workspace_list_github_repos(
 fn workspace_list_github_repos_blocking(
     query: Option<String>,
 ) -> Result<Vec<GitHubRepoOption>, String> {
-    let output = Command::new("gh")
-        .args([
-            "repo",
-            "list",
-            "--limit",
-            "200",
-            "--source",
-            "--no-archived",
-            "--json",
-            "nameWithOwner,url,isPrivate,updatedAt",
-        ])
-        .output()
-        .map_err(|err| {
-            if err.kind() == std::io::ErrorKind::NotFound {
+    let mut command = Command::new("gh");
+    command.args([
+        "repo",
+        "list",
+        "--limit",
+        "200",
+        "--source",
+        "--no-archived",
+        "--json",
+        "nameWithOwner,url,isPrivate,updatedAt",
+    ]);
+    let output = run_command(command, "gh repo list", RunLimits::timeout(GH_TIMEOUT)).map_err(
+        |err| match err {
+            WorkspaceError::Io(err) if err.kind() == std::io::ErrorKind::NotFound => {
                 "GitHub CLI not found. Install gh and run `gh auth login` to use repository suggestions."
                     .to_string()
-            } else {
-                format!("Failed to run GitHub CLI: {err}")
             }
-        })?;
+            WorkspaceError::Io(err) => format!("Failed to run GitHub CLI: {err}"),
+            err => err.to_string(),
+        },
+    )?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
@@ -273,180 +321,160 @@ pub fn workspace_set_active(id: String, state: State<'_, WorkspaceState>) -> Res
     Ok(())
 }
 
+/// Inserts a `Creating` record for `request` and starts its creation job.
+/// The job reports through `CREATE_PROGRESS_EVENT`.
+fn create_in_background(
+    app: &AppHandle,
+    state: &WorkspaceState,
+    request: CreateWorkspaceRequest,
+) -> Result<Workspace, String> {
+    let workspace = {
+        let store = lock_store(state)?;
+        pending_workspace(&store, next_workspace_id(), request).map_err(to_command_error)?
+    };
+    let workspace = persist_workspace(state, workspace)?;
+    spawn_create_job(app.clone(), workspace.clone()).map_err(to_command_error)?;
+    Ok(workspace)
+}
+
 #[tauri::command]
-pub async fn workspace_create_local(
+pub fn workspace_create_local(
     input: CreateLocalWorkspaceInput,
+    app: AppHandle,
     state: State<'_, WorkspaceState>,
 ) -> Result<Workspace, String> {
-    let workspace_id = next_workspace_id();
-    let suffix_owned = workspace_suffix(&workspace_id).to_string();
-    let workspaces_root = state.app_data_dir.join("workspaces");
-    let ws_name = input.workspace_name.clone();
-    let source = input.repo_path.clone();
-
-    let prepared = tauri::async_runtime::spawn_blocking(move || {
-        create_local_workspace(
-            Path::new(&input.repo_path),
-            &input.workspace_name,
-            input.branch_name,
-            input.base_ref,
-            &suffix_owned,
-            &workspaces_root,
-        )
-    })
-    .await
-    .map_err(|err| format!("Task failed: {err}"))?
-    .map_err(to_command_error)?;
+    create_in_background(&app, &state, CreateWorkspaceRequest::Local(input))
+}
 
-    let workspace = build_workspace(workspace_id, ws_name, WorkspaceSourceType::Local, source, prepared);
-    persist_workspace(&state, workspace)
+/// Creates a workspace starting from another workspace's `HEAD`, optionally
+/// with its uncommitted changes, in the same repository.
+#[tauri::command]
+pub fn workspace_fork(
+    input: WorkspaceForkInput,
+    app: AppHandle,
+    state: State<'_, WorkspaceState>,
+) -> Result<Workspace, String> {
+    create_in_background(&app, &state, CreateWorkspaceRequest::Fork(input))
 }
 
 #[tauri::command]
-pub async fn workspace_create_github(
+pub fn workspace_create_github(
     input: CreateGitHubWorkspaceInput,
+    app: AppHandle,
     state: State<'_, WorkspaceState>,
 ) -> Result<Workspace, String> {
-    let workspace_id = next_workspace_id();
-    let suffix_owned = workspace_suffix(&workspace_id).to_string();
-    let app_data_dir = state.app_data_dir.clone();
-    let ws_name = input.workspace_name.clone();
-    let source = input.repo_url.clone();
-
-    let prepared = tauri::async_runtime::spawn_blocking(move || {
-        create_github_workspace(
-            &input.repo_url,
-            &input.workspace_name,
-            input.clone_root_path,
-            input.branch_name,
-            input.base_ref,
-            &suffix_owned,
-            &app_data_dir,
-        )
-    })
-    .await
-    .map_err(|err| format!("Task failed: {err}"))?
-    .map_err(to_command_error)?;
-
-    let workspace = build_workspace(workspace_id, ws_name, WorkspaceSourceType::Github, source, prepared);
-    persist_workspace(&state, workspace)
+    create_in_background(&app, &state, CreateWorkspaceRequest::Github(input))
 }
 
 #[tauri::command]
-pub async fn workspace_create_new_github(
+pub fn workspace_create_new_github(
     input: CreateNewGitHubWorkspaceInput,
+    app: AppHandle,
     state: State<'_, WorkspaceState>,
 ) -> Result<Workspace, String> {
-    let workspace_id = next_workspace_id();
-    let suffix_owned = workspace_suffix(&workspace_id).to_string();
-    let app_data_dir = state.app_data_dir.clone();
-    let ws_name = input.workspace_name.clone();
-
-    let created = tauri::async_runtime::spawn_blocking(move || {
-        create_new_github_workspace(
-            &input.repository_name,
-            &input.workspace_name,
-            input.clone_root_path,
-            input.branch_name,
-            input.base_ref,
-            &suffix_owned,
-            &app_data_dir,
-        )
-    })
-    .await
-    .map_err(|err| format!("Task failed: {err}"))?
-    .map_err(to_command_error)?;
-
-    let workspace = build_workspace(
-        workspace_id,
-        ws_name,
-        WorkspaceSourceType::Github,
-        created.repo_url,
-        created.prepared,
-    );
-    persist_workspace(&state, workspace)
+    create_in_background(&app, &state, CreateWorkspaceRequest::NewGithub(input))
 }
 
 #[tauri::command]
-pub async fn workspace_create_from_source(
+pub fn workspace_create_from_source(
     input: CreateWorkspaceFromSourceInput,
+    app: AppHandle,
     state: State<'_, WorkspaceState>,
 ) -> Result<Workspace, String> {
-    let workspace_id = next_workspace_id();
-    let suffix_owned = workspace_suffix(&workspace_id).to_string();
-    let app_data_dir = state.app_data_dir.clone();
+    create_in_background(&app, &state, CreateWorkspaceRequest::FromSource(input))
+}
 
-    let repo_id = input.repo_id.trim().to_string();
-    if repo_id.is_empty() {
-        return Err("Repository selection is required".to_string());
-    }
-    let repo_url = repo_url_from_id(&repo_id).map_err(to_command_error)?;
-    let fallback_name = repo_id
-        .split('/')
-        .filter(|segment| !segment.trim().is_empty())
-        .last()
-        .unwrap_or("Workspace")
-        .to_string();
-    let workspace_name = input
-        .workspace_name
-        .as_deref()
-        .map(str::trim)
-        .filter(|value| !value.is_empty())
-        .unwrap_or(fallback_name.as_str())
-        .to_string();
-
-    let clone_root_path = input.clone_root_path.clone();
-    let source = input.source.clone();
-    let source_value = repo_url.clone();
-    let workspace_name_for_create = workspace_name.clone();
-
-    let prepared = tauri::async_runtime::spawn_blocking(move || {
-        let (branch_name, base_ref) = match source {
-            WorkspaceCreateFromSource::Default => (None, None),
-            WorkspaceCreateFromSource::PullRequest { value } => {
-                let head_branch = pull_request_head_branch(&repo_id, value)?;
-                (None, Some(format!("origin/{head_branch}")))
-            }
-            WorkspaceCreateFromSource::Branch { value } => {
-                let normalized = value.trim().trim_start_matches("origin/").to_string();
-                if normalized.is_empty() {
-                    return Err(WorkspaceError::InvalidInput(
-                        "Branch selection is required".to_string(),
-                    ));
+/// Runs the creation job of a workspace whose creation failed again, with
+/// the same input.
+#[tauri::command]
+pub fn workspace_retry_create(
+    workspace_id: String,
+    app: AppHandle,
+    state: State<'_, WorkspaceState>,
+) -> Result<Workspace, String> {
+    let workspace = {
+        let mut store = lock_store(&state)?;
+        let workspace = store.get(&workspace_id).map_err(to_command_error)?;
+        if workspace.status != WorkspaceStatus::Error || workspace.create_job.is_none() {
+            return Err(format!(
+                "Only workspaces whose creation failed can be retried: {}",
+                workspace.name
+            ));
+        }
+        let workspace = store
+            .update(&workspace_id, |workspace| {
+                workspace.status = WorkspaceStatus::Creating;
+                workspace.error_message = None;
+                if let Some(job) = &mut workspace.create_job {
+                    job.id = next_job_id();
                 }
-                (None, Some(format!("origin/{normalized}")))
-            }
-            WorkspaceCreateFromSource::Issue { value } => {
-                let default_branch = repo_default_branch(&repo_id)?;
-                (
-                    Some(format!("feature/issue-{value}")),
-                    Some(format!("origin/{default_branch}")),
-                )
-            }
-        };
-
-        create_github_workspace(
-            &repo_url,
-            &workspace_name_for_create,
-            clone_root_path,
-            branch_name,
-            base_ref,
-            &suffix_owned,
-            &app_data_dir,
+            })
+            .map_err(to_command_error)?;
+        store.save().map_err(to_command_error)?;
+        workspace
+    };
+    spawn_create_job(app, workspace.clone()).map_err(to_command_error)?;
+    Ok(workspace)
+}
+
+/// Cancels a running creation job. The workspace disappears once the job
+/// has stopped and cleaned up, which `CREATE_PROGRESS_EVENT` reports.
+#[tauri::command]
+pub fn workspace_cancel_create(
+    workspace_id: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<(), String> {
+    cancel_create_job(&state, &workspace_id).map_err(to_command_error)
+}
+
+/// Streams the clone, fetch and checkout progress of a workspace that is
+/// being created to `on_progress`, until its creation job ends.
+#[tauri::command]
+pub fn workspace_watch_progress(
+    workspace_id: String,
+    on_progress: Channel<GitProgressEvent>,
+    state: State<'_, WorkspaceState>,
+) -> Result<(), String> {
+    watch_create_progress(&state, &workspace_id, on_progress).map_err(to_command_error)
+}
+
+#[tauri::command]
+pub async fn workspace_list_importable_worktrees(
+    repo_path: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<ImportableWorktree>, String> {
+    let registered = lock_store(&state)?.list();
+    tauri::async_runtime::spawn_blocking(move || {
+        list_importable_worktrees(Path::new(&repo_path), &registered)
+    })
+    .await
+    .map_err(|err| format!("Failed to list worktrees: {err}"))?
+    .map_err(to_command_error)
+}
+
+#[tauri::command]
+pub async fn workspace_import_existing(
+    input: ImportExistingWorktreesInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<Workspace>, String> {
+    let registered = lock_store(&state)?.list();
+    let imported = tauri::async_runtime::spawn_blocking(move || {
+        import_worktrees(
+            Path::new(&input.repo_path),
+            &input.worktree_paths,
+            &registered,
         )
     })
     .await
     .map_err(|err| format!("Task failed: {err}"))?
     .map_err(to_command_error)?;
 
-    let workspace = build_workspace(
-        workspace_id,
-        workspace_name,
-        WorkspaceSourceType::Github,
-        source_value,
-        prepared,
-    );
-    persist_workspace(&state, workspace)
+    let mut store = lock_store(&state)?;
+    for workspace in &imported {
+        store.insert(workspace.clone());
+    }
+    store.save().map_err(to_command_error)?;
+    Ok(imported)
 }
 
 #[tauri::command]
@@ -471,6 +499,687 @@ pub fn workspace_archive(id: String, state: State<'_, WorkspaceState>) -> Result
     Ok(())
 }
 
+/// Renames a workspace, optionally renaming its branch and moving its
+/// worktree to match. Git changes are undone if the registry can't be saved.
+#[tauri::command]
+pub async fn workspace_rename(
+    input: WorkspaceRenameInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<Workspace, String> {
+    let previous = lock_store(&state)?
+        .get(&input.workspace_id)
+        .map_err(to_command_error)?;
+    let workspace = previous.clone();
+    let repo_locks = state.repo_locks.clone();
+    // The lock is held until the rename is saved or rolled back.
+    let (applied, _repo_lock) = tauri::async_runtime::spawn_blocking(move || {
+        let repo_lock =
+            repo_locks.lock(Path::new(&workspace.repo_root_path), GitMonitor::default())?;
+        rename_worktree(&workspace, &input).map(|applied| (applied, repo_lock))
+    })
+    .await
+    .map_err(|err| format!("Rename task failed: {err}"))?
+    .map_err(to_command_error)?;
+
+    let saved = {
+        let mut store = lock_store(&state)?;
+        let result = store
+            .update(&previous.id, |workspace| applied.apply_to(workspace))
+            .and_then(|workspace| store.save().map(|_| workspace));
+        if result.is_err() {
+            let _ = store.update(&previous.id, |workspace| *workspace = previous.clone());
+        }
+        result
+    };
+    lock_status_cache(&state)?.invalidate(&previous.id);
+    saved.map_err(|err| {
+        if let Err(rollback_err) = applied.rollback() {
+            eprintln!("Warning: failed to roll back workspace rename: {rollback_err}");
+        }
+        to_command_error(err)
+    })
+}
+
+/// Widens or narrows the directories checked out in a sparse workspace.
+/// Files outside the new set are removed from the worktree unless they have
+/// local changes, which git leaves in place.
+#[tauri::command]
+pub async fn workspace_set_sparse_paths(
+    input: SetSparsePathsInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<Workspace, String> {
+    let workspace_id = input.workspace_id.clone();
+    let workspace = lock_store(&state)?
+        .get(&workspace_id)
+        .map_err(to_command_error)?;
+    let sparse_paths = tauri::async_runtime::spawn_blocking(move || {
+        let paths = normalize_sparse_paths(&input.paths)?;
+        set_sparse_paths(Path::new(&workspace.worktree_path), &paths)?;
+        Ok::<_, WorkspaceError>(paths)
+    })
+    .await
+    .map_err(|err| format!("Task failed: {err}"))?;
+    lock_status_cache(&state)?.invalidate(&workspace_id);
+    let sparse_paths = sparse_paths.map_err(to_command_error)?;
+
+    let mut store = lock_store(&state)?;
+    let workspace = store
+        .update(&workspace_id, |workspace| {
+            workspace.sparse_paths = sparse_paths
+        })
+        .map_err(to_command_error)?;
+    store.save().map_err(to_command_error)?;
+    Ok(workspace)
+}
+
+#[tauri::command]
+pub fn workspace_list_registry_backups(
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<RegistryBackup>, String> {
+    let store = lock_store(&state)?;
+    store.list_backups().map_err(to_command_error)
+}
+
+#[tauri::command]
+pub fn workspace_restore_registry_backup(
+    index: usize,
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<Workspace>, String> {
+    let mut store = lock_store(&state)?;
+    store.restore_backup(index).map_err(to_command_error)?;
+    Ok(store.list())
+}
+
+/// Returns and clears the notices recorded while loading workspace state.
+/// `STARTUP_NOTICE_EVENT` fires during setup, usually before the webview has
+/// registered a listener, so the frontend also pulls them once on mount.
+#[tauri::command]
+pub fn workspace_take_startup_notices(
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<WorkspaceStartupNotice>, String> {
+    let mut notices = state.startup_notices.lock().map_err(|_| {
+        "Workspace state is unavailable. Please restart the application.".to_string()
+    })?;
+    Ok(std::mem::take(&mut *notices))
+}
+
+#[tauri::command]
+pub async fn workspace_doctor(state: State<'_, WorkspaceState>) -> Result<DoctorReport, String> {
+    let workspaces = lock_store(&state)?.list();
+    let workspaces_root = state.app_data_dir.join("workspaces");
+    tauri::async_runtime::spawn_blocking(move || diagnose(&workspaces, &workspaces_root))
+        .await
+        .map_err(|err| format!("Workspace doctor failed: {err}"))
+}
+
+/// Applies a single repair suggested by `workspace_doctor` and returns a
+/// fresh report.
+#[tauri::command]
+pub async fn workspace_doctor_repair(
+    repair: DoctorRepair,
+    state: State<'_, WorkspaceState>,
+) -> Result<DoctorReport, String> {
+    match repair {
+        DoctorRepair::PruneWorktrees { repo_root_path } => {
+            let repo_locks = state.repo_locks.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let repo_root = Path::new(&repo_root_path);
+                let _repo_lock = repo_locks.lock(repo_root, GitMonitor::default())?;
+                prune_worktrees(repo_root)
+            })
+            .await
+            .map_err(|err| format!("Task failed: {err}"))?
+            .map_err(to_command_error)?;
+        }
+        DoctorRepair::ReAddWorktree { workspace_id } => {
+            let workspace = lock_store(&state)?
+                .get(&workspace_id)
+                .map_err(to_command_error)?;
+            let repo_locks = state.repo_locks.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let _repo_lock =
+                    repo_locks.lock(Path::new(&workspace.repo_root_path), GitMonitor::default())?;
+                re_add_worktree(&workspace)
+            })
+            .await
+            .map_err(|err| format!("Task failed: {err}"))?
+            .map_err(to_command_error)?;
+            let mut store = lock_store(&state)?;
+            store
+                .update(&workspace_id, |workspace| {
+                    if workspace.status == WorkspaceStatus::Error {
+                        workspace.status = WorkspaceStatus::Ready;
+                        workspace.error_message = None;
+                    }
+                })
+                .map_err(to_command_error)?;
+            store.save().map_err(to_command_error)?;
+        }
+        DoctorRepair::AdoptWorktree { worktree_path } => {
+            let adopted = tauri::async_runtime::spawn_blocking(move || {
+                adopt_worktree(Path::new(&worktree_path), None)?.ok_or_else(|| {
+                    WorkspaceError::InvalidInput(format!(
+                        "Not a linked git worktree: {worktree_path}"
+                    ))
+                })
+            })
+            .await
+            .map_err(|err| format!("Task failed: {err}"))?
+            .map_err(to_command_error)?;
+            let mut store = lock_store(&state)?;
+            // The report may be stale, e.g. after a repeated click.
+            if store
+                .list()
+                .iter()
+                .any(|workspace| workspace.worktree_path == adopted.worktree_path)
+            {
+                return Err(format!(
+                    "Worktree is already registered as a workspace: {}",
+                    adopted.worktree_path
+                ));
+            }
+            store.insert(adopted);
+            store.save().map_err(to_command_error)?;
+        }
+        DoctorRepair::MarkError {
+            workspace_id,
+            reason,
+        } => {
+            let mut store = lock_store(&state)?;
+            store
+                .mark_error(&workspace_id, reason)
+                .map_err(to_command_error)?;
+            store.save().map_err(to_command_error)?;
+        }
+    }
+
+    workspace_doctor(state).await
+}
+
+/// Returns git status for the given workspaces, or for every workspace when
+/// `workspace_ids` is omitted. Cached results are reused for a few seconds
+/// unless `refresh` is set.
+#[tauri::command]
+pub async fn workspace_status(
+    workspace_ids: Option<Vec<String>>,
+    refresh: Option<bool>,
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<WorkspaceStatusEntry>, String> {
+    let workspaces = {
+        let store = lock_store(&state)?;
+        match workspace_ids {
+            Some(ids) => ids
+                .iter()
+                .map(|id| store.get(id))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(to_command_error)?,
+            None => store.list(),
+        }
+    };
+
+    let mut cached = Vec::with_capacity(workspaces.len());
+    let mut stale = Vec::new();
+    {
+        let cache = lock_status_cache(&state)?;
+        for workspace in &workspaces {
+            let hit = cache
+                .get(&workspace.id)
+                .filter(|_| !refresh.unwrap_or(false));
+            if hit.is_none() {
+                stale.push(workspace.clone());
+            }
+            cached.push(hit);
+        }
+    }
+
+    let stale_ids = stale
+        .iter()
+        .map(|workspace| workspace.id.clone())
+        .collect::<Vec<_>>();
+    let computed = tauri::async_runtime::spawn_blocking(move || read_workspace_statuses(&stale))
+        .await
+        .map_err(|err| format!("Status task failed: {err}"))?;
+
+    let mut fresh = stale_ids
+        .into_iter()
+        .zip(computed)
+        .collect::<HashMap<_, _>>();
+    let mut cache = lock_status_cache(&state)?;
+    let entries = workspaces
+        .into_iter()
+        .zip(cached)
+        .map(|(workspace, hit)| match hit {
+            Some(status) => WorkspaceStatusEntry::from_result(workspace.id, Ok(status)),
+            None => {
+                let result = fresh.remove(&workspace.id).unwrap_or_else(|| {
+                    Err(WorkspaceError::NotFound(format!(
+                        "Workspace not found: {}",
+                        workspace.id
+                    )))
+                });
+                if let Ok(status) = &result {
+                    cache.insert(workspace.id.clone(), status.clone());
+                }
+                WorkspaceStatusEntry::from_result(workspace.id, result)
+            }
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Returns a structured diff of a workspace against the merge-base with its
+/// `base_ref`, either for the working tree or for a commit range.
+#[tauri::command]
+pub async fn workspace_diff(
+    input: WorkspaceDiffInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<WorkspaceDiff, String> {
+    let workspace = lock_store(&state)?
+        .get(&input.workspace_id)
+        .map_err(to_command_error)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        diff_workspace(&workspace, &input.target, &input.options)
+    })
+    .await
+    .map_err(|err| format!("Diff task failed: {err}"))?
+    .map_err(to_command_error)
+}
+
+/// Lists the CI checks of a workspace's pull request (or `HEAD` commit) and
+/// saves the logs of failed GitHub Actions jobs under its app-data folder.
+#[tauri::command]
+pub async fn workspace_checks(
+    workspace_id: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<WorkspaceChecks, String> {
+    let workspace = lock_store(&state)?
+        .get(&workspace_id)
+        .map_err(to_command_error)?;
+    let logs_dir = ci_logs_dir(&state.app_data_dir, &workspace.id);
+    tauri::async_runtime::spawn_blocking(move || read_workspace_checks(&workspace, &logs_dir))
+        .await
+        .map_err(|err| format!("Checks task failed: {err}"))?
+        .map_err(to_command_error)
+}
+
+/// Snapshots the index, working tree and untracked files of a workspace
+/// into a checkpoint ref without touching its branch.
+#[tauri::command]
+pub async fn workspace_checkpoint_create(
+    input: CreateCheckpointInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<Checkpoint, String> {
+    let workspace = lock_store(&state)?
+        .get(&input.workspace_id)
+        .map_err(to_command_error)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        create_checkpoint(
+            Path::new(&workspace.worktree_path),
+            &workspace.id,
+            &input.name,
+        )
+    })
+    .await
+    .map_err(|err| format!("Checkpoint task failed: {err}"))?
+    .map_err(to_command_error)
+}
+
+#[tauri::command]
+pub async fn workspace_checkpoint_list(
+    workspace_id: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<Checkpoint>, String> {
+    let workspace = lock_store(&state)?
+        .get(&workspace_id)
+        .map_err(to_command_error)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        list_checkpoints(Path::new(&workspace.repo_root_path), &workspace.id)
+    })
+    .await
+    .map_err(|err| format!("Checkpoint task failed: {err}"))?
+    .map_err(to_command_error)
+}
+
+#[tauri::command]
+pub async fn workspace_checkpoint_diff(
+    input: CheckpointDiffInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<WorkspaceDiff, String> {
+    let workspace = lock_store(&state)?
+        .get(&input.workspace_id)
+        .map_err(to_command_error)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        diff_checkpoint(
+            Path::new(&workspace.worktree_path),
+            &workspace.id,
+            input.checkpoint,
+            input.against,
+            &input.options,
+        )
+    })
+    .await
+    .map_err(|err| format!("Diff task failed: {err}"))?
+    .map_err(to_command_error)
+}
+
+/// Restores a checkpoint and returns the checkpoint taken of the state it
+/// replaced.
+#[tauri::command]
+pub async fn workspace_checkpoint_restore(
+    input: RestoreCheckpointInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<Checkpoint, ChangeCommandError> {
+    change_worktree(&input.workspace_id, &state, move |workspace| {
+        restore_checkpoint(
+            Path::new(&workspace.worktree_path),
+            &workspace.id,
+            input.checkpoint,
+            input.reset_head,
+        )
+    })
+    .await
+}
+
+/// Runs a change to a workspace's worktree off the main thread once the
+/// checked-out branch is known to be writable, then drops its cached status.
+/// The repository stays locked in `state.repo_locks` meanwhile, since
+/// fetches and pushes write to the repository all worktrees share.
+async fn change_worktree<T, F>(
+    workspace_id: &str,
+    state: &WorkspaceState,
+    change: F,
+) -> Result<T, ChangeCommandError>
+where
+    T: Send + 'static,
+    F: FnOnce(&Workspace) -> Result<T, WorkspaceError> + Send + 'static,
+{
+    let workspace = lock_store(state)?.get(workspace_id)?;
+    let repo_locks = state.repo_locks.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let _repo_lock =
+            repo_locks.lock(Path::new(&workspace.repo_root_path), GitMonitor::default())?;
+        ensure_branch_writable(&workspace)?;
+        change(&workspace)
+    })
+    .await
+    .map_err(|err| format!("Task failed: {err}"))?;
+    lock_status_cache(state)?.invalidate(workspace_id);
+    Ok(result?)
+}
+
+#[tauri::command]
+pub async fn workspace_stage(
+    input: WorkspaceChangeSelection,
+    state: State<'_, WorkspaceState>,
+) -> Result<WorktreeStatus, ChangeCommandError> {
+    change_worktree(&input.workspace_id, &state, move |workspace| {
+        let worktree_path = Path::new(&workspace.worktree_path);
+        stage_changes(worktree_path, &input.paths, &input.hunk_ids)?;
+        read_worktree_status(worktree_path, workspace.base_ref.as_deref())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn workspace_unstage(
+    input: WorkspaceChangeSelection,
+    state: State<'_, WorkspaceState>,
+) -> Result<WorktreeStatus, ChangeCommandError> {
+    change_worktree(&input.workspace_id, &state, move |workspace| {
+        let worktree_path = Path::new(&workspace.worktree_path);
+        unstage_changes(worktree_path, &input.paths, &input.hunk_ids)?;
+        read_worktree_status(worktree_path, workspace.base_ref.as_deref())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn workspace_discard(
+    input: WorkspaceChangeSelection,
+    state: State<'_, WorkspaceState>,
+) -> Result<WorktreeStatus, ChangeCommandError> {
+    change_worktree(&input.workspace_id, &state, move |workspace| {
+        let worktree_path = Path::new(&workspace.worktree_path);
+        discard_changes(worktree_path, &input.paths, &input.hunk_ids)?;
+        read_worktree_status(worktree_path, workspace.base_ref.as_deref())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn workspace_commit(
+    input: WorkspaceCommitInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<CommitSummary, ChangeCommandError> {
+    change_worktree(&input.workspace_id, &state, move |workspace| {
+        commit_changes(
+            Path::new(&workspace.worktree_path),
+            &input.message,
+            input.amend,
+        )
+    })
+    .await
+}
+
+/// Fetches and brings the workspace branch up to date with its `base_ref`,
+/// stopping with the conflicted files if the rebase or merge cannot finish.
+#[tauri::command]
+pub async fn workspace_sync(
+    input: WorkspaceSyncInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<SyncOutcome, ChangeCommandError> {
+    change_worktree(&input.workspace_id, &state, move |workspace| {
+        sync_worktree(
+            Path::new(&workspace.worktree_path),
+            workspace.base_ref.as_deref(),
+            input.strategy,
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn workspace_sync_continue(
+    workspace_id: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<SyncOutcome, ChangeCommandError> {
+    change_worktree(&workspace_id, &state, |workspace| {
+        continue_sync(Path::new(&workspace.worktree_path))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn workspace_sync_abort(
+    workspace_id: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<(), ChangeCommandError> {
+    change_worktree(&workspace_id, &state, |workspace| {
+        abort_sync(Path::new(&workspace.worktree_path))
+    })
+    .await
+}
+
+/// Pushes the workspace branch and opens (or updates) its pull request,
+/// recording the PR on the workspace.
+#[tauri::command]
+pub async fn workspace_open_pull_request(
+    input: OpenPullRequestInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<Workspace, ChangeCommandError> {
+    let workspace_id = input.workspace_id.clone();
+    let pull_request = change_worktree(&workspace_id, &state, move |workspace| {
+        open_pull_request(workspace, &input)
+    })
+    .await?;
+
+    let mut store = lock_store(&state)?;
+    let workspace = store.update(&workspace_id, |workspace| {
+        workspace.pull_request = Some(pull_request)
+    })?;
+    store.save()?;
+    Ok(workspace)
+}
+
+/// Polls workspace pull requests now instead of waiting for the watcher.
+#[tauri::command]
+pub async fn workspace_refresh_pull_requests(
+    app: AppHandle,
+) -> Result<Vec<PullRequestStatusChange>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let changes = poll_pull_requests(app.state::<WorkspaceState>().inner());
+        emit_pull_request_changes(&app, &changes);
+        changes
+    })
+    .await
+    .map_err(|err| format!("Task failed: {err}"))
+}
+
+#[tauri::command]
+pub fn workspace_get_auto_archive_policy(
+    state: State<'_, WorkspaceState>,
+) -> Result<AutoArchivePolicy, String> {
+    Ok(lock_store(&state)?.auto_archive_policy())
+}
+
+#[tauri::command]
+pub fn workspace_set_auto_archive_policy(
+    policy: AutoArchivePolicy,
+    state: State<'_, WorkspaceState>,
+) -> Result<AutoArchivePolicy, String> {
+    let mut store = lock_store(&state)?;
+    store.set_auto_archive_policy(policy);
+    store.save().map_err(to_command_error)?;
+    Ok(policy)
+}
+
+/// Default clone strategy for new repo caches of `repo_id` (`owner/repo`).
+#[tauri::command]
+pub fn workspace_get_clone_strategy(
+    repo_id: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<CloneStrategy, String> {
+    Ok(lock_store(&state)?.clone_strategy(repo_id.trim()))
+}
+
+#[tauri::command]
+pub fn workspace_set_clone_strategy(
+    input: SetCloneStrategyInput,
+    state: State<'_, WorkspaceState>,
+) -> Result<CloneStrategy, String> {
+    if let CloneStrategy::Shallow { depth: 0 } = input.strategy {
+        return Err("Shallow clone depth must be at least 1".to_string());
+    }
+    let repo_url = repo_url_from_id(&input.repo_id).map_err(to_command_error)?;
+    let repo_id = repo_id_from_url(&repo_url).map_err(to_command_error)?;
+    let mut store = lock_store(&state)?;
+    store.set_clone_strategy(&repo_id, input.strategy);
+    store.save().map_err(to_command_error)?;
+    Ok(input.strategy)
+}
+
+#[tauri::command]
+pub async fn workspace_cache_inventory(
+    state: State<'_, WorkspaceState>,
+) -> Result<CacheInventory, String> {
+    let workspaces = lock_store(&state)?.list();
+    let app_data_dir = state.app_data_dir.clone();
+    tauri::async_runtime::spawn_blocking(move || read_cache_inventory(&app_data_dir, &workspaces))
+        .await
+        .map_err(|err| format!("Task failed: {err}"))
+}
+
+/// Looks up the cache at `path` in a fresh inventory.
+fn find_cache(
+    app_data_dir: &Path,
+    workspaces: &[Workspace],
+    path: &str,
+) -> Result<CachedRepo, WorkspaceError> {
+    read_cache_inventory(app_data_dir, workspaces)
+        .caches
+        .into_iter()
+        .find(|cache| cache.path == path)
+        .ok_or_else(|| WorkspaceError::NotFound(format!("Repo cache not found: {path}")))
+}
+
+/// Runs `git maintenance` on a repo cache and returns its new disk usage.
+#[tauri::command]
+pub async fn workspace_cache_maintain(
+    path: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<CachedRepo, String> {
+    let workspaces = lock_store(&state)?.list();
+    let app_data_dir = state.app_data_dir.clone();
+    let repo_locks = state.repo_locks.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _repo_lock = repo_locks.lock(Path::new(&path), GitMonitor::default())?;
+        let cache = find_cache(&app_data_dir, &workspaces, &path)?;
+        maintain_cache(Path::new(&cache.path))?;
+        find_cache(&app_data_dir, &workspaces, &path)
+    })
+    .await
+    .map_err(|err| format!("Task failed: {err}"))?
+    .map_err(to_command_error)
+}
+
+#[tauri::command]
+pub async fn workspace_cache_evict(
+    path: String,
+    state: State<'_, WorkspaceState>,
+) -> Result<(), String> {
+    let workspaces = lock_store(&state)?.list();
+    let app_data_dir = state.app_data_dir.clone();
+    let repo_locks = state.repo_locks.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let _repo_lock = repo_locks.lock(Path::new(&path), GitMonitor::default())?;
+        evict_cache(&find_cache(&app_data_dir, &workspaces, &path)?)
+    })
+    .await
+    .map_err(|err| format!("Task failed: {err}"))?
+    .map_err(to_command_error)
+}
+
+#[tauri::command]
+pub fn workspace_get_cache_budget(state: State<'_, WorkspaceState>) -> Result<Option<u64>, String> {
+    Ok(lock_store(&state)?.cache_budget_bytes())
+}
+
+/// Saves the repo cache disk budget and evicts unused caches to meet it.
+/// Returns the evicted caches.
+#[tauri::command]
+pub async fn workspace_set_cache_budget(
+    max_bytes: Option<u64>,
+    state: State<'_, WorkspaceState>,
+) -> Result<Vec<CachedRepo>, String> {
+    {
+        let mut store = lock_store(&state)?;
+        store.set_cache_budget_bytes(max_bytes);
+        store.save().map_err(to_command_error)?;
+    }
+    apply_cache_budget(&state).await
+}
+
+async fn apply_cache_budget(state: &WorkspaceState) -> Result<Vec<CachedRepo>, String> {
+    let (workspaces, budget) = {
+        let store = lock_store(state)?;
+        (store.list(), store.cache_budget_bytes())
+    };
+    let Some(max_bytes) = budget else {
+        return Ok(Vec::new());
+    };
+    let app_data_dir = state.app_data_dir.clone();
+    let repo_locks = state.repo_locks.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        enforce_cache_budget(
+            &read_cache_inventory(&app_data_dir, &workspaces),
+            max_bytes,
+            &repo_locks,
+        )
+    })
+    .await
+    .map_err(|err| format!("Task failed: {err}"))
+}
+
 #[tauri::command]
 pub async fn workspace_delete(
     id: String,
@@ -479,14 +1188,35 @@ pub async fn workspace_delete(
 ) -> Result<(), String> {
     let removed = {
         let mut store = lock_store(&state)?;
+        if store.get(&id).map_err(to_command_error)?.status == WorkspaceStatus::Creating {
+            return Err("Workspace is still being created. Cancel its creation first.".to_string());
+        }
         let removed = store.remove(&id).map_err(to_command_error)?;
         store.save().map_err(to_command_error)?;
         removed
     };
+    lock_status_cache(&state)?.invalidate(&id);
+
+    let repo_root_path = removed.repo_root_path.clone();
+    let app_data_dir = state.app_data_dir.clone();
+    let workspace_id = id.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(err) = delete_checkpoints(Path::new(&repo_root_path), &workspace_id) {
+            eprintln!("Warning: failed to delete checkpoints of {workspace_id}: {err}");
+        }
+        if let Err(err) = delete_ci_logs(&app_data_dir, &workspace_id) {
+            eprintln!("Warning: failed to delete CI logs of {workspace_id}: {err}");
+        }
+    })
+    .await
+    .map_err(|err| format!("Cleanup task failed: {err}"))?;
 
     if remove_files {
         let worktree_path = removed.worktree_path;
+        let repo_locks = state.repo_locks.clone();
         tauri::async_runtime::spawn_blocking(move || {
+            let _repo_lock =
+                repo_locks.lock(Path::new(&removed.repo_root_path), GitMonitor::default())?;
             remove_worktree_files(&worktree_path)
         })
         .await
@@ -494,6 +1224,10 @@ pub async fn workspace_delete(
         .map_err(|err| err.to_string())?;
     }
 
+    // The deleted workspace may have been the last one using its cache.
+    if let Err(err) = apply_cache_budget(&state).await {
+        eprintln!("Warning: failed to apply repo cache budget: {err}");
+    }
     Ok(())
 }
 
@@ -506,10 +1240,14 @@ fn remove_worktree_files(worktree_path: &str) -> Result<(), WorkspaceError> {
     // Remove via git first to clean up .git/worktrees metadata, then
     // fall back to filesystem removal if git worktree remove fails
     // (e.g. the directory is not a linked worktree).
-    let git_result = Command::new("git")
-        .args(["worktree", "remove", "--force"])
-        .arg(path)
-        .output();
+    let args = ["worktree", "remove", "--force"];
+    let mut command = Command::new("git");
+    command.args(args).arg(path);
+    let git_result = run_command(
+        command,
+        "git worktree remove",
+        RunLimits::timeout(git_timeout(&args)),
+    );
 
     let git_removed = match git_result {
         Ok(output) => output.status.success(),
@@ -530,3 +1268,34 @@ fn remove_worktree_files(worktree_path: &str) -> Result<(), WorkspaceError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ChangeCommandError;
+    use crate::workspaces::WorkspaceError;
+
+    #[test]
+    fn serializes_hook_failures_with_their_details() {
+        let hook_failed = ChangeCommandError::from(WorkspaceError::HookFailed {
+            hook: "commit-msg".to_string(),
+            exit_code: Some(3),
+            output: "must reference a ticket".to_string(),
+        });
+        let other = ChangeCommandError::from(WorkspaceError::NotFound("ws_1".to_string()));
+
+        assert_eq!(
+            serde_json::to_value(hook_failed).unwrap(),
+            serde_json::json!({
+                "kind": "hookFailed",
+                "message": "The commit-msg hook failed: must reference a ticket",
+                "hook": "commit-msg",
+                "exitCode": 3,
+                "output": "must reference a ticket",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(other).unwrap()["kind"],
+            serde_json::json!("failed")
+        );
+    }
+}
@@ -0,0 +1,781 @@
+use std::path::Path;
+use std::sync::MutexGuard;
+use std::thread;
+
+use serde::Serialize;
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::fork::fork_worktree;
+use super::git_github::{
+    cache_repo_path, create_github_workspace, create_new_github_workspace,
+    create_pull_request_workspace, new_github_repo_url, pull_request_head, repo_default_branch,
+    repo_id_from_url, repo_url_from_id,
+};
+use super::git_local::{create_local_workspace, AddedWorktree};
+use super::git_progress::{GitMonitor, GitProgress, GitProgressEvent};
+use super::model::{
+    build_workspace, next_job_id, workspace_suffix, CloneStrategy, CreateJob,
+    CreateWorkspaceFromSourceInput, CreateWorkspaceRequest, PreparedWorkspace, RepoCacheOptions,
+    Workspace, WorkspaceBranch, WorkspaceCreateFromSource, WorkspacePullRequest,
+    WorkspaceSourceType, WorkspaceStatus,
+};
+use super::process::CancelToken;
+use super::{WorkspaceError, WorkspaceState, WorkspaceStore, CREATE_PROGRESS_EVENT};
+
+/// A running creation job, keyed by workspace ID in `WorkspaceState::create_jobs`.
+#[derive(Debug, Clone)]
+pub struct CreateJobHandle {
+    pub job_id: String,
+    pub cancel: CancelToken,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreateJobStage {
+    Started,
+    Ready,
+    Failed,
+    Cancelled,
+}
+
+/// Payload of `CREATE_PROGRESS_EVENT`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateJobEvent {
+    pub workspace_id: String,
+    pub job_id: String,
+    pub stage: CreateJobStage,
+    /// Failure reason for `Failed`.
+    pub message: Option<String>,
+    /// The workspace record as of this event; `None` once cancelled.
+    pub workspace: Option<Workspace>,
+}
+
+/// What a finished job fills in on the pending workspace record.
+pub struct CreatedWorkspace {
+    pub worktree: AddedWorktree,
+    /// Replaces the pending record's source when only known after creation.
+    pub source: Option<String>,
+    pub pull_request: Option<WorkspacePullRequest>,
+    pub source_pull_request: Option<WorkspacePullRequest>,
+}
+
+impl From<AddedWorktree> for CreatedWorkspace {
+    fn from(worktree: AddedWorktree) -> Self {
+        Self {
+            worktree,
+            source: None,
+            pull_request: None,
+            source_pull_request: None,
+        }
+    }
+}
+
+fn lock_store(state: &WorkspaceState) -> Result<MutexGuard<'_, WorkspaceStore>, WorkspaceError> {
+    state.store.lock().map_err(|_| {
+        WorkspaceError::InvalidInput(
+            "Workspace state is unavailable. Please restart the application.".to_string(),
+        )
+    })
+}
+
+/// Builds the `Creating` record inserted before the job starts. Inputs are
+/// validated here so obvious mistakes fail the command instead of the job.
+pub fn pending_workspace(
+    store: &WorkspaceStore,
+    workspace_id: String,
+    request: CreateWorkspaceRequest,
+) -> Result<Workspace, WorkspaceError> {
+    let mut parent_workspace_id = None;
+    let (name, source_type, source) = match &request {
+        CreateWorkspaceRequest::Local(input) => {
+            WorkspaceBranch::from_input(input.branch_name.clone(), input.checkout_existing)?;
+            (
+                input.workspace_name.clone(),
+                WorkspaceSourceType::Local,
+                input.repo_path.clone(),
+            )
+        }
+        CreateWorkspaceRequest::Github(input) => {
+            WorkspaceBranch::from_input(input.branch_name.clone(), input.checkout_existing)?;
+            (
+                input.workspace_name.clone(),
+                WorkspaceSourceType::Github,
+                input.repo_url.clone(),
+            )
+        }
+        CreateWorkspaceRequest::NewGithub(input) => (
+            input.workspace_name.clone(),
+            WorkspaceSourceType::Github,
+            input.repository_name.clone(),
+        ),
+        CreateWorkspaceRequest::FromSource(input) => {
+            let (repo_id, repo_url) = source_repository(input)?;
+            (
+                from_source_workspace_name(input, &repo_id),
+                WorkspaceSourceType::Github,
+                repo_url,
+            )
+        }
+        CreateWorkspaceRequest::Fork(input) => {
+            let source = store.get(&input.source_workspace_id)?;
+            if source.status != WorkspaceStatus::Ready {
+                return Err(WorkspaceError::InvalidInput(format!(
+                    "Only ready workspaces can be forked: {}",
+                    source.name
+                )));
+            }
+            parent_workspace_id = Some(source.id);
+            (
+                input.workspace_name.clone(),
+                source.source_type,
+                source.source,
+            )
+        }
+    };
+
+    let mut workspace = build_workspace(
+        workspace_id,
+        name,
+        source_type,
+        source,
+        PreparedWorkspace::default(),
+    );
+    workspace.status = WorkspaceStatus::Creating;
+    workspace.base_ref = None;
+    workspace.parent_workspace_id = parent_workspace_id;
+    workspace.create_job = Some(CreateJob {
+        id: next_job_id(),
+        request,
+    });
+    Ok(workspace)
+}
+
+fn source_repository(
+    input: &CreateWorkspaceFromSourceInput,
+) -> Result<(String, String), WorkspaceError> {
+    let repo_id = input.repo_id.trim().to_string();
+    if repo_id.is_empty() {
+        return Err(WorkspaceError::InvalidInput(
+            "Repository selection is required".to_string(),
+        ));
+    }
+    let repo_url = repo_url_from_id(&repo_id)?;
+    Ok((repo_id, repo_url))
+}
+
+fn from_source_workspace_name(input: &CreateWorkspaceFromSourceInput, repo_id: &str) -> String {
+    let fallback_name = repo_id
+        .split('/')
+        .rfind(|segment| !segment.trim().is_empty())
+        .unwrap_or("Workspace");
+    input
+        .workspace_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .unwrap_or(fallback_name)
+        .to_string()
+}
+
+/// Registers the job of a `Creating` record and runs it on a background
+/// thread, emitting `CREATE_PROGRESS_EVENT` when it starts and ends.
+pub fn spawn_create_job(app: AppHandle, workspace: Workspace) -> Result<(), WorkspaceError> {
+    let Some(job) = workspace.create_job.clone() else {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "Workspace has no creation job: {}",
+            workspace.id
+        )));
+    };
+    let handle = CreateJobHandle {
+        job_id: job.id.clone(),
+        cancel: CancelToken::default(),
+    };
+    {
+        let state = app.state::<WorkspaceState>();
+        let mut jobs = state.create_jobs.lock().map_err(|_| {
+            WorkspaceError::InvalidInput(
+                "Workspace state is unavailable. Please restart the application.".to_string(),
+            )
+        })?;
+        jobs.insert(workspace.id.clone(), handle.clone());
+    }
+
+    emit_create_event(
+        &app,
+        CreateJobEvent {
+            workspace_id: workspace.id.clone(),
+            job_id: job.id.clone(),
+            stage: CreateJobStage::Started,
+            message: None,
+            workspace: Some(workspace.clone()),
+        },
+    );
+    thread::spawn(move || {
+        let state = app.state::<WorkspaceState>();
+        let on_progress = |progress: GitProgress| {
+            send_git_progress(state.inner(), &workspace.id, &job.id, progress)
+        };
+        let monitor = GitMonitor {
+            on_progress: Some(&on_progress),
+            cancel: Some(&handle.cancel),
+        };
+        let result = run_create_request(state.inner(), &workspace, &job.request, monitor);
+        let event = finish_create_job(state.inner(), &workspace.id, &job, result, &handle);
+        emit_create_event(&app, event);
+    });
+    Ok(())
+}
+
+/// Forwards git progress to the channel watching the workspace, if any.
+fn send_git_progress(
+    state: &WorkspaceState,
+    workspace_id: &str,
+    job_id: &str,
+    progress: GitProgress,
+) {
+    let Ok(channels) = state.progress_channels.lock() else {
+        return;
+    };
+    let Some(channel) = channels.get(workspace_id) else {
+        return;
+    };
+    let event = GitProgressEvent {
+        workspace_id: workspace_id.to_string(),
+        job_id: job_id.to_string(),
+        progress,
+    };
+    if let Err(err) = channel.send(event) {
+        eprintln!("Warning: failed to send git progress for {workspace_id}: {err}");
+    }
+}
+
+fn emit_create_event(app: &AppHandle, event: CreateJobEvent) {
+    if let Err(err) = app.emit(CREATE_PROGRESS_EVENT, event.clone()) {
+        eprintln!(
+            "Warning: failed to emit create progress for {}: {err}",
+            event.workspace_id
+        );
+    }
+}
+
+/// Asks a running job to stop. A clone, fetch or checkout in flight is
+/// killed; other steps run to completion. The job then discards what it
+/// created and drops the record.
+pub fn cancel_create_job(state: &WorkspaceState, workspace_id: &str) -> Result<(), WorkspaceError> {
+    let jobs = state.create_jobs.lock().map_err(|_| {
+        WorkspaceError::InvalidInput(
+            "Workspace state is unavailable. Please restart the application.".to_string(),
+        )
+    })?;
+    let handle = jobs.get(workspace_id).ok_or_else(|| {
+        WorkspaceError::InvalidInput(format!("Workspace is not being created: {workspace_id}"))
+    })?;
+    handle.cancel.cancel();
+    Ok(())
+}
+
+/// Sends the git progress of a running job to `channel` until the job ends.
+/// A later watcher of the same workspace replaces the earlier one.
+pub fn watch_create_progress(
+    state: &WorkspaceState,
+    workspace_id: &str,
+    channel: Channel<GitProgressEvent>,
+) -> Result<(), WorkspaceError> {
+    let jobs = state.create_jobs.lock().map_err(|_| {
+        WorkspaceError::InvalidInput(
+            "Workspace state is unavailable. Please restart the application.".to_string(),
+        )
+    })?;
+    if !jobs.contains_key(workspace_id) {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "Workspace is not being created: {workspace_id}"
+        )));
+    }
+    state
+        .progress_channels
+        .lock()
+        .map_err(|_| {
+            WorkspaceError::InvalidInput(
+                "Workspace state is unavailable. Please restart the application.".to_string(),
+            )
+        })?
+        .insert(workspace_id.to_string(), channel);
+    Ok(())
+}
+
+/// Runs the create command recorded on the job. The repository or repo cache
+/// it works on stays locked in `state.repo_locks` until it returns.
+pub fn run_create_request(
+    state: &WorkspaceState,
+    workspace: &Workspace,
+    request: &CreateWorkspaceRequest,
+    monitor: GitMonitor<'_>,
+) -> Result<CreatedWorkspace, WorkspaceError> {
+    let suffix = workspace_suffix(&workspace.id);
+    let app_data_dir = state.app_data_dir.as_path();
+    let workspaces_root = app_data_dir.join("workspaces");
+
+    match request.clone() {
+        CreateWorkspaceRequest::Local(input) => {
+            let branch = WorkspaceBranch::from_input(input.branch_name, input.checkout_existing)?;
+            let _repo_lock = state
+                .repo_locks
+                .lock(Path::new(&input.repo_path), monitor)?;
+            create_local_workspace(
+                Path::new(&input.repo_path),
+                &input.workspace_name,
+                branch,
+                input.base_ref,
+                &input.sparse_paths,
+                suffix,
+                &workspaces_root,
+                monitor,
+            )
+            .map(CreatedWorkspace::from)
+        }
+        CreateWorkspaceRequest::Github(input) => {
+            let branch = WorkspaceBranch::from_input(input.branch_name, input.checkout_existing)?;
+            let cache = repo_cache_options(
+                state,
+                &input.repo_url,
+                input.clone_root_path,
+                input.clone_strategy,
+            )?;
+            let _repo_lock = state.repo_locks.lock(
+                &cache_repo_path(&input.repo_url, &cache, app_data_dir)?,
+                monitor,
+            )?;
+            create_github_workspace(
+                &input.repo_url,
+                &input.workspace_name,
+                cache,
+                branch,
+                input.base_ref,
+                &input.sparse_paths,
+                suffix,
+                app_data_dir,
+                monitor,
+            )
+            .map(CreatedWorkspace::from)
+        }
+        CreateWorkspaceRequest::NewGithub(input) => {
+            let repo_url = new_github_repo_url(&input.repository_name, app_data_dir)?;
+            let cache = repo_cache_options(state, &repo_url, input.clone_root_path, None)?;
+            let _repo_lock = state
+                .repo_locks
+                .lock(&cache_repo_path(&repo_url, &cache, app_data_dir)?, monitor)?;
+            let worktree = create_new_github_workspace(
+                &repo_url,
+                &input.workspace_name,
+                cache,
+                WorkspaceBranch::New(input.branch_name),
+                input.base_ref,
+                suffix,
+                app_data_dir,
+                monitor,
+            )?;
+            Ok(CreatedWorkspace {
+                source: Some(repo_url),
+                ..CreatedWorkspace::from(worktree)
+            })
+        }
+        CreateWorkspaceRequest::FromSource(input) => {
+            create_from_source(state, workspace, &input, suffix, monitor)
+        }
+        CreateWorkspaceRequest::Fork(input) => {
+            let source = lock_store(state)?.get(&input.source_workspace_id)?;
+            let _repo_lock = state
+                .repo_locks
+                .lock(Path::new(&source.repo_root_path), monitor)?;
+            fork_worktree(
+                &source,
+                &input.workspace_name,
+                input.branch_name,
+                input.include_uncommitted,
+                suffix,
+                &workspaces_root,
+                monitor,
+            )
+            .map(CreatedWorkspace::from)
+        }
+    }
+}
+
+fn create_from_source(
+    state: &WorkspaceState,
+    workspace: &Workspace,
+    input: &CreateWorkspaceFromSourceInput,
+    suffix: &str,
+    monitor: GitMonitor<'_>,
+) -> Result<CreatedWorkspace, WorkspaceError> {
+    let app_data_dir = state.app_data_dir.as_path();
+    let (repo_id, repo_url) = source_repository(input)?;
+    let cache = repo_cache_options(
+        state,
+        &repo_url,
+        input.clone_root_path.clone(),
+        input.clone_strategy,
+    )?;
+    let _repo_lock = state
+        .repo_locks
+        .lock(&cache_repo_path(&repo_url, &cache, app_data_dir)?, monitor)?;
+
+    let (branch, base_ref) = match input.source.clone() {
+        WorkspaceCreateFromSource::Default => (WorkspaceBranch::New(None), None),
+        WorkspaceCreateFromSource::PullRequest {
+            value,
+            checkout_existing,
+        } => {
+            let pull_request = pull_request_head(&repo_id, value)?;
+            let created = create_pull_request_workspace(
+                &repo_url,
+                pull_request,
+                checkout_existing,
+                &workspace.name,
+                cache,
+                &input.sparse_paths,
+                suffix,
+                app_data_dir,
+                monitor,
+            )?;
+            return Ok(CreatedWorkspace {
+                worktree: created.worktree,
+                source: None,
+                pull_request: created.pull_request,
+                source_pull_request: created.source_pull_request,
+            });
+        }
+        WorkspaceCreateFromSource::Branch {
+            value,
+            checkout_existing,
+        } => {
+            let normalized = value.trim().trim_start_matches("origin/").to_string();
+            if normalized.is_empty() {
+                return Err(WorkspaceError::InvalidInput(
+                    "Branch selection is required".to_string(),
+                ));
+            }
+            if checkout_existing {
+                (WorkspaceBranch::Existing(normalized), None)
+            } else {
+                (
+                    WorkspaceBranch::New(None),
+                    Some(format!("origin/{normalized}")),
+                )
+            }
+        }
+        WorkspaceCreateFromSource::Issue { value } => {
+            let default_branch = repo_default_branch(&repo_id)?;
+            (
+                WorkspaceBranch::New(Some(format!("feature/issue-{value}"))),
+                Some(format!("origin/{default_branch}")),
+            )
+        }
+    };
+
+    create_github_workspace(
+        &repo_url,
+        &workspace.name,
+        cache,
+        branch,
+        base_ref,
+        &input.sparse_paths,
+        suffix,
+        app_data_dir,
+        monitor,
+    )
+    .map(CreatedWorkspace::from)
+}
+
+/// Repo cache settings for `repo_url`: the requested clone strategy, or the
+/// repository's saved default.
+fn repo_cache_options(
+    state: &WorkspaceState,
+    repo_url: &str,
+    clone_root_path: Option<String>,
+    clone_strategy: Option<CloneStrategy>,
+) -> Result<RepoCacheOptions, WorkspaceError> {
+    let clone_strategy = match clone_strategy {
+        Some(strategy) => strategy,
+        None => match repo_id_from_url(repo_url) {
+            Ok(repo_id) => lock_store(state)?.clone_strategy(&repo_id),
+            Err(_) => CloneStrategy::default(),
+        },
+    };
+    Ok(RepoCacheOptions {
+        clone_root_path,
+        clone_strategy,
+    })
+}
+
+/// Records the outcome of a job on its workspace: `Ready` with the created
+/// worktree, `Error` with the failure reason, or no record at all when the
+/// job was cancelled or the workspace deleted meanwhile.
+pub fn finish_create_job(
+    state: &WorkspaceState,
+    workspace_id: &str,
+    job: &CreateJob,
+    result: Result<CreatedWorkspace, WorkspaceError>,
+    handle: &CreateJobHandle,
+) -> CreateJobEvent {
+    if let Ok(mut jobs) = state.create_jobs.lock() {
+        if jobs
+            .get(workspace_id)
+            .is_some_and(|running| running.job_id == handle.job_id)
+        {
+            jobs.remove(workspace_id);
+            if let Ok(mut channels) = state.progress_channels.lock() {
+                channels.remove(workspace_id);
+            }
+        }
+    }
+    let event = |stage, message, workspace| CreateJobEvent {
+        workspace_id: workspace_id.to_string(),
+        job_id: job.id.clone(),
+        stage,
+        message,
+        workspace,
+    };
+
+    let mut store = match lock_store(state) {
+        Ok(store) => store,
+        Err(err) => return event(CreateJobStage::Failed, Some(err.to_string()), None),
+    };
+    let still_registered = store.get(workspace_id).is_ok();
+    if handle.cancel.is_cancelled() || !still_registered {
+        if still_registered {
+            let removed = store.remove(workspace_id).and_then(|_| store.save());
+            if let Err(err) = removed {
+                eprintln!("Warning: failed to remove cancelled workspace {workspace_id}: {err}");
+            }
+        }
+        drop(store);
+        if let Ok(created) = result {
+            roll_back_worktree(state, created.worktree);
+        }
+        return event(CreateJobStage::Cancelled, None, None);
+    }
+
+    let (saved, unsaved) = match result {
+        Ok(created) => record_created(&mut store, workspace_id, created),
+        Err(err) => {
+            let saved = store
+                .mark_error(workspace_id, err.to_string())
+                .and_then(|workspace| store.save().map(|_| workspace));
+            (saved, None)
+        }
+    };
+    drop(store);
+    if let Some(worktree) = unsaved {
+        roll_back_worktree(state, worktree);
+    }
+    match saved {
+        Ok(workspace) if workspace.status == WorkspaceStatus::Ready => {
+            event(CreateJobStage::Ready, None, Some(workspace))
+        }
+        Ok(workspace) => event(
+            CreateJobStage::Failed,
+            workspace.error_message.clone(),
+            Some(workspace),
+        ),
+        Err(err) => event(CreateJobStage::Failed, Some(err.to_string()), None),
+    }
+}
+
+/// Fills in the pending record with the created worktree and saves it, which
+/// closes the worktree's journal entry. If the save fails, the record goes
+/// back to pending, marked as failed so it can be retried, and the worktree
+/// is returned for the caller to roll back once the store is unlocked.
+fn record_created(
+    store: &mut WorkspaceStore,
+    workspace_id: &str,
+    created: CreatedWorkspace,
+) -> (Result<Workspace, WorkspaceError>, Option<AddedWorktree>) {
+    let pending = store.get(workspace_id);
+    let prepared = created.worktree.prepared.clone();
+    let saved = store
+        .update(workspace_id, |workspace| {
+            workspace.repo_root_path = prepared.repo_root_path;
+            workspace.worktree_path = prepared.worktree_path;
+            workspace.branch = prepared.branch;
+            workspace.base_ref = Some(prepared.base_ref);
+            workspace.sparse_paths = prepared.sparse_paths;
+            if let Some(source) = created.source {
+                workspace.source = source;
+            }
+            workspace.pull_request = created.pull_request;
+            workspace.source_pull_request = created.source_pull_request;
+            workspace.status = WorkspaceStatus::Ready;
+            workspace.error_message = None;
+            workspace.create_job = None;
+        })
+        .and_then(|workspace| store.save().map(|_| workspace));
+
+    match (&saved, pending) {
+        (Ok(_), _) => {
+            created.worktree.operation.finish();
+            (saved, None)
+        }
+        (Err(err), Ok(pending)) => {
+            let reason = err.to_string();
+            let restored = store.update(workspace_id, |workspace| {
+                *workspace = pending;
+                workspace.status = WorkspaceStatus::Error;
+                workspace.error_message = Some(reason);
+            });
+            if let Err(err) = restored {
+                eprintln!("Warning: failed to restore pending workspace {workspace_id}: {err}");
+            }
+            (saved, Some(created.worktree))
+        }
+        (Err(_), Err(_)) => (saved, Some(created.worktree)),
+    }
+}
+
+/// Removes a worktree the job created but that never made it into the
+/// registry, holding the repository's lock like the job itself did.
+fn roll_back_worktree(state: &WorkspaceState, worktree: AddedWorktree) {
+    let repo_root = Path::new(&worktree.prepared.repo_root_path).to_path_buf();
+    let _repo_lock = state.repo_locks.lock(&repo_root, GitMonitor::default());
+    worktree.roll_back();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    use tempfile::tempdir;
+
+    use super::{
+        finish_create_job, pending_workspace, run_create_request, CreateJobHandle, CreateJobStage,
+    };
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::git_status::StatusCache;
+    use crate::workspaces::model::{
+        next_workspace_id, CreateLocalWorkspaceInput, CreateWorkspaceRequest, WorkspaceStatus,
+    };
+    use crate::workspaces::process::CancelToken;
+    use crate::workspaces::repo_locks::RepoLocks;
+    use crate::workspaces::{WorkspaceError, WorkspaceState, WorkspaceStore};
+    use crate::workspaces::test_support::{git, init_repo};
+
+    /// A repository with one commit and a state with a pending workspace for
+    /// it. Returns the state, the workspace ID and its job handle.
+    fn pending_local(root: &Path) -> (WorkspaceState, String, CreateJobHandle) {
+        let repo = root.join("repo");
+        init_repo(&repo);
+
+        let mut store = WorkspaceStore::new(root.join("app-data"));
+        let request = CreateWorkspaceRequest::Local(CreateLocalWorkspaceInput {
+            repo_path: repo.to_string_lossy().to_string(),
+            workspace_name: "KAT-21".to_string(),
+            branch_name: None,
+            base_ref: None,
+            checkout_existing: false,
+            sparse_paths: Vec::new(),
+        });
+        let workspace = pending_workspace(&store, next_workspace_id(), request).unwrap();
+        let handle = CreateJobHandle {
+            job_id: workspace.create_job.as_ref().unwrap().id.clone(),
+            cancel: CancelToken::default(),
+        };
+        let id = workspace.id.clone();
+        store.insert(workspace);
+        let state = WorkspaceState {
+            app_data_dir: root.join("app-data"),
+            store: Mutex::new(store),
+            startup_notices: Mutex::new(Vec::new()),
+            status_cache: Mutex::new(StatusCache::default()),
+            create_jobs: Mutex::new(HashMap::from([(id.clone(), handle.clone())])),
+            progress_channels: Mutex::new(HashMap::new()),
+            repo_locks: RepoLocks::default(),
+        };
+        (state, id, handle)
+    }
+
+    fn run(state: &WorkspaceState, id: &str, handle: &CreateJobHandle) -> CreateJobStage {
+        let workspace = state.store.lock().unwrap().get(id).unwrap();
+        let job = workspace.create_job.clone().unwrap();
+        let result = run_create_request(state, &workspace, &job.request, GitMonitor::default());
+        finish_create_job(state, id, &job, result, handle).stage
+    }
+
+    #[test]
+    fn pending_workspace_becomes_ready() {
+        let dir = tempdir().unwrap();
+        let (state, id, handle) = pending_local(dir.path());
+        let pending = state.store.lock().unwrap().get(&id).unwrap();
+        assert_eq!(pending.status, WorkspaceStatus::Creating);
+        assert!(pending.worktree_path.is_empty());
+
+        assert_eq!(run(&state, &id, &handle), CreateJobStage::Ready);
+
+        let workspace = state.store.lock().unwrap().get(&id).unwrap();
+        assert_eq!(workspace.status, WorkspaceStatus::Ready);
+        assert!(workspace.create_job.is_none());
+        assert!(Path::new(&workspace.worktree_path).join(".git").exists());
+        assert!(state.create_jobs.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn failed_job_keeps_request_for_retry() {
+        let dir = tempdir().unwrap();
+        let (state, id, handle) = pending_local(dir.path());
+        let workspace = state.store.lock().unwrap().get(&id).unwrap();
+        let job = workspace.create_job.clone().unwrap();
+
+        let event = finish_create_job(
+            &state,
+            &id,
+            &job,
+            Err(WorkspaceError::GitFailed("clone failed".to_string())),
+            &handle,
+        );
+
+        assert_eq!(event.stage, CreateJobStage::Failed);
+        assert_eq!(event.message.as_deref(), Some("clone failed"));
+        let workspace = state.store.lock().unwrap().get(&id).unwrap();
+        assert_eq!(workspace.status, WorkspaceStatus::Error);
+        assert_eq!(workspace.create_job, Some(job));
+    }
+
+    #[test]
+    fn worktree_is_rolled_back_when_its_record_cannot_be_saved() {
+        let dir = tempdir().unwrap();
+        let (state, id, handle) = pending_local(dir.path());
+        let registry_path = state.store.lock().unwrap().registry_path();
+        fs::create_dir_all(&registry_path).unwrap();
+
+        assert_eq!(run(&state, &id, &handle), CreateJobStage::Failed);
+
+        let workspace = state.store.lock().unwrap().get(&id).unwrap();
+        assert_eq!(workspace.status, WorkspaceStatus::Error);
+        assert!(workspace.create_job.is_some());
+        let repo = dir.path().join("repo");
+        assert_eq!(git(&repo, &["worktree", "list"]).lines().count(), 1);
+        assert_eq!(git(&repo, &["branch", "--list", "workspace/*"]), "");
+
+        // A retry reuses the same worktree path and branch.
+        fs::remove_dir(&registry_path).unwrap();
+        assert_eq!(run(&state, &id, &handle), CreateJobStage::Ready);
+    }
+
+    #[test]
+    fn cancelled_job_discards_worktree_and_record() {
+        let dir = tempdir().unwrap();
+        let (state, id, handle) = pending_local(dir.path());
+        handle.cancel.cancel();
+
+        assert_eq!(run(&state, &id, &handle), CreateJobStage::Cancelled);
+
+        assert!(state.store.lock().unwrap().get(&id).is_err());
+        let repo = dir.path().join("repo");
+        assert_eq!(git(&repo, &["worktree", "list"]).lines().count(), 1);
+        assert_eq!(git(&repo, &["branch", "--list", "workspace/*"]), "");
+    }
+}
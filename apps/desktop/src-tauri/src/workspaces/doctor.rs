@@ -0,0 +1,392 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::git_local::{branch_exists, canonicalize_path, list_worktrees, run_git};
+use super::model::{now_iso8601, Workspace, WorkspaceStatus};
+use super::recovery::adopt_worktree;
+use super::WorkspaceError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoctorFindingKind {
+    MissingRepository,
+    MissingWorktree,
+    MissingBranch,
+    OrphanWorktree,
+    StaleWorktreeMetadata,
+}
+
+/// A repair the user can apply to resolve a finding. Each repair carries
+/// everything needed to run it, so the frontend can pass it straight back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DoctorRepair {
+    #[serde(rename_all = "camelCase")]
+    PruneWorktrees { repo_root_path: String },
+    #[serde(rename_all = "camelCase")]
+    ReAddWorktree { workspace_id: String },
+    #[serde(rename_all = "camelCase")]
+    AdoptWorktree { worktree_path: String },
+    #[serde(rename_all = "camelCase")]
+    MarkError {
+        workspace_id: String,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoctorFinding {
+    pub id: String,
+    pub kind: DoctorFindingKind,
+    pub message: String,
+    pub workspace_id: Option<String>,
+    pub path: Option<String>,
+    pub repairs: Vec<DoctorRepair>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DoctorReport {
+    pub findings: Vec<DoctorFinding>,
+    pub checked_at: String,
+}
+
+/// Compares the registry with the worktrees on disk and git's own worktree
+/// metadata. Nothing is modified; every finding lists the repairs that apply.
+pub fn diagnose(workspaces: &[Workspace], workspaces_root: &Path) -> DoctorReport {
+    let mut findings = Vec::new();
+    let mut repo_roots = BTreeSet::new();
+
+    for workspace in workspaces {
+        // Archived workspaces have had their worktree removed on purpose.
+        if matches!(
+            workspace.status,
+            WorkspaceStatus::Creating | WorkspaceStatus::Archived
+        ) {
+            continue;
+        }
+        findings.extend(diagnose_workspace(workspace));
+        if Path::new(&workspace.repo_root_path).exists() {
+            repo_roots.insert(workspace.repo_root_path.clone());
+        }
+    }
+
+    for orphan in find_orphan_worktrees(workspaces, workspaces_root) {
+        repo_roots.insert(orphan.repo_root_path.clone());
+        findings.push(DoctorFinding {
+            id: format!("orphan_worktree:{}", orphan.worktree_path),
+            kind: DoctorFindingKind::OrphanWorktree,
+            message: format!(
+                "Worktree on branch {} is not tracked by any workspace",
+                orphan.branch
+            ),
+            workspace_id: None,
+            path: Some(orphan.worktree_path.clone()),
+            repairs: vec![DoctorRepair::AdoptWorktree {
+                worktree_path: orphan.worktree_path,
+            }],
+        });
+    }
+
+    for repo_root_path in repo_roots {
+        let Ok(entries) = list_worktrees(Path::new(&repo_root_path)) else {
+            continue;
+        };
+        for entry in entries.into_iter().filter(|entry| entry.prunable) {
+            let path = entry.path.to_string_lossy().to_string();
+            findings.push(DoctorFinding {
+                id: format!("stale_worktree_metadata:{path}"),
+                kind: DoctorFindingKind::StaleWorktreeMetadata,
+                message: format!(
+                    "{repo_root_path} still has git metadata for missing worktree {path}"
+                ),
+                workspace_id: None,
+                path: Some(path),
+                repairs: vec![DoctorRepair::PruneWorktrees {
+                    repo_root_path: repo_root_path.clone(),
+                }],
+            });
+        }
+    }
+
+    DoctorReport {
+        findings,
+        checked_at: now_iso8601(),
+    }
+}
+
+fn diagnose_workspace(workspace: &Workspace) -> Option<DoctorFinding> {
+    let repo_root = Path::new(&workspace.repo_root_path);
+    let mark_error = |reason: &str| DoctorRepair::MarkError {
+        workspace_id: workspace.id.clone(),
+        reason: reason.to_string(),
+    };
+
+    if !repo_root.exists() {
+        let reason = format!("Repository is missing: {}", workspace.repo_root_path);
+        return Some(DoctorFinding {
+            id: format!("missing_repository:{}", workspace.id),
+            kind: DoctorFindingKind::MissingRepository,
+            repairs: vec![mark_error(&reason)],
+            message: reason,
+            workspace_id: Some(workspace.id.clone()),
+            path: Some(workspace.repo_root_path.clone()),
+        });
+    }
+
+    let has_branch = branch_exists(repo_root, &workspace.branch).unwrap_or(false);
+    if !Path::new(&workspace.worktree_path).exists() {
+        let reason = format!("Worktree folder is missing: {}", workspace.worktree_path);
+        let mut repairs = Vec::new();
+        if has_branch {
+            repairs.push(DoctorRepair::ReAddWorktree {
+                workspace_id: workspace.id.clone(),
+            });
+        }
+        repairs.push(mark_error(&reason));
+        return Some(DoctorFinding {
+            id: format!("missing_worktree:{}", workspace.id),
+            kind: DoctorFindingKind::MissingWorktree,
+            message: reason,
+            workspace_id: Some(workspace.id.clone()),
+            path: Some(workspace.worktree_path.clone()),
+            repairs,
+        });
+    }
+
+    if !has_branch {
+        let reason = format!("Branch no longer exists: {}", workspace.branch);
+        return Some(DoctorFinding {
+            id: format!("missing_branch:{}", workspace.id),
+            kind: DoctorFindingKind::MissingBranch,
+            repairs: vec![mark_error(&reason)],
+            message: reason,
+            workspace_id: Some(workspace.id.clone()),
+            path: Some(workspace.worktree_path.clone()),
+        });
+    }
+
+    None
+}
+
+fn find_orphan_worktrees(workspaces: &[Workspace], workspaces_root: &Path) -> Vec<Workspace> {
+    let Ok(entries) = fs::read_dir(workspaces_root) else {
+        return Vec::new();
+    };
+    let tracked = workspaces
+        .iter()
+        .map(|workspace| {
+            canonicalize_path(Path::new(&workspace.worktree_path))
+                .unwrap_or_else(|_| workspace.worktree_path.clone())
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut candidates = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    candidates.sort();
+
+    candidates
+        .iter()
+        .filter(|path| {
+            canonicalize_path(path)
+                .map(|canonical| !tracked.contains(&canonical))
+                .unwrap_or(false)
+        })
+        .filter_map(|path| adopt_worktree(path, None).ok().flatten())
+        .collect()
+}
+
+/// Runs `git worktree prune` so git forgets worktrees whose folders are gone.
+pub fn prune_worktrees(repo_root_path: &Path) -> Result<(), WorkspaceError> {
+    run_git(repo_root_path, &["worktree", "prune"]).map(|_| ())
+}
+
+/// Recreates a missing worktree folder on the workspace's existing branch.
+pub fn re_add_worktree(workspace: &Workspace) -> Result<(), WorkspaceError> {
+    let repo_root = Path::new(&workspace.repo_root_path);
+    if Path::new(&workspace.worktree_path).exists() {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "Worktree path already exists: {}",
+            workspace.worktree_path
+        )));
+    }
+    prune_worktrees(repo_root)?;
+    run_git(
+        repo_root,
+        &[
+            "worktree",
+            "add",
+            &workspace.worktree_path,
+            &workspace.branch,
+        ],
+    )
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
+    use super::{diagnose, prune_worktrees, re_add_worktree, DoctorFindingKind, DoctorRepair};
+    use crate::workspaces::git_local::create_local_workspace;
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::{
+        build_workspace, next_workspace_id, Workspace, WorkspaceBranch, WorkspaceSourceType,
+        WorkspaceStatus,
+    };
+    use crate::workspaces::test_support::git;
+
+    struct DoctorFixture {
+        _tmpdir: TempDir,
+        repo_path: PathBuf,
+        workspaces_root: PathBuf,
+    }
+
+    impl DoctorFixture {
+        fn new() -> Self {
+            let tmpdir = tempfile::tempdir().unwrap();
+            let repo_path = tmpdir.path().join("repo");
+            fs::create_dir_all(&repo_path).unwrap();
+            git(&repo_path, &["init"]);
+            git(&repo_path, &["checkout", "-B", "main"]);
+            fs::write(repo_path.join("README.md"), "# fixture\n").unwrap();
+            git(&repo_path, &["add", "."]);
+            git(&repo_path, &["commit", "-m", "initial"]);
+            let workspaces_root = tmpdir.path().join("workspaces");
+
+            Self {
+                _tmpdir: tmpdir,
+                repo_path,
+                workspaces_root,
+            }
+        }
+
+        fn workspace(&self, name: &str, suffix: &str) -> Workspace {
+            let prepared = create_local_workspace(
+                &self.repo_path,
+                name,
+                WorkspaceBranch::New(None),
+                None,
+                &[],
+                suffix,
+                &self.workspaces_root,
+                GitMonitor::default(),
+            )
+            .unwrap()
+            .prepared;
+            build_workspace(
+                next_workspace_id(),
+                name.to_string(),
+                WorkspaceSourceType::Local,
+                self.repo_path.to_string_lossy().to_string(),
+                prepared,
+            )
+        }
+    }
+
+    #[test]
+    fn reports_nothing_for_consistent_registry() {
+        let fixture = DoctorFixture::new();
+        let workspace = fixture.workspace("KAT-154", "ab12");
+
+        let report = diagnose(&[workspace], &fixture.workspaces_root);
+
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+    }
+
+    #[test]
+    fn reports_missing_worktree_with_stale_metadata_and_repairs_it() {
+        let fixture = DoctorFixture::new();
+        let workspace = fixture.workspace("KAT-154", "ab12");
+        fs::remove_dir_all(&workspace.worktree_path).unwrap();
+
+        let report = diagnose(std::slice::from_ref(&workspace), &fixture.workspaces_root);
+
+        let kinds = report
+            .findings
+            .iter()
+            .map(|finding| finding.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                DoctorFindingKind::MissingWorktree,
+                DoctorFindingKind::StaleWorktreeMetadata
+            ]
+        );
+        assert!(matches!(
+            report.findings[0].repairs[0],
+            DoctorRepair::ReAddWorktree { .. }
+        ));
+
+        re_add_worktree(&workspace).unwrap();
+        assert!(diagnose(&[workspace], &fixture.workspaces_root)
+            .findings
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_missing_branch_and_orphan_worktree() {
+        let fixture = DoctorFixture::new();
+        let tracked = fixture.workspace("KAT-154", "ab12");
+        let orphan = fixture.workspace("KAT-155", "cd34");
+        git(
+            &fixture.repo_path,
+            &[
+                "update-ref",
+                "-d",
+                &format!("refs/heads/{}", tracked.branch),
+            ],
+        );
+
+        let report = diagnose(&[tracked], &fixture.workspaces_root);
+
+        assert_eq!(report.findings.len(), 2, "{:?}", report.findings);
+        assert_eq!(report.findings[0].kind, DoctorFindingKind::MissingBranch);
+        assert_eq!(report.findings[1].kind, DoctorFindingKind::OrphanWorktree);
+        assert_eq!(
+            report.findings[1].repairs,
+            vec![DoctorRepair::AdoptWorktree {
+                worktree_path: orphan.worktree_path
+            }]
+        );
+    }
+
+    #[test]
+    fn skips_archived_workspace_without_worktree() {
+        let fixture = DoctorFixture::new();
+        let mut workspace = fixture.workspace("KAT-154", "ab12");
+        git(
+            &fixture.repo_path,
+            &["worktree", "remove", "--force", &workspace.worktree_path],
+        );
+        workspace.status = WorkspaceStatus::Archived;
+
+        let report = diagnose(&[workspace], &fixture.workspaces_root);
+
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+    }
+
+    #[test]
+    fn prune_clears_stale_worktree_metadata() {
+        let fixture = DoctorFixture::new();
+        let workspace = fixture.workspace("KAT-154", "ab12");
+        fs::remove_dir_all(&workspace.worktree_path).unwrap();
+
+        prune_worktrees(&fixture.repo_path).unwrap();
+
+        let report = diagnose(&[workspace], &fixture.workspaces_root);
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].kind, DoctorFindingKind::MissingWorktree);
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::git_local::{create_local_workspace, run_git, run_git_untrimmed, AddedWorktree};
+use super::git_progress::GitMonitor;
+use super::model::{Workspace, WorkspaceBranch};
+use super::WorkspaceError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceForkInput {
+    pub source_workspace_id: String,
+    pub workspace_name: String,
+    pub branch_name: Option<String>,
+    /// Carry over staged, unstaged and untracked changes of the source.
+    #[serde(default)]
+    pub include_uncommitted: bool,
+}
+
+/// Adds a sibling worktree on a new branch starting at the source
+/// workspace's `HEAD`. The new workspace keeps the source's `base_ref`, so
+/// diffs and syncs still compare against the same upstream branch, and its
+/// sparse checkout paths.
+pub fn fork_worktree(
+    source: &Workspace,
+    workspace_name: &str,
+    branch_name: Option<String>,
+    include_uncommitted: bool,
+    suffix: &str,
+    workspaces_root: &Path,
+    monitor: GitMonitor<'_>,
+) -> Result<AddedWorktree, WorkspaceError> {
+    let source_path = Path::new(&source.worktree_path);
+    if !source_path.exists() {
+        return Err(WorkspaceError::NotFound(format!(
+            "Worktree path does not exist: {}",
+            source.worktree_path
+        )));
+    }
+    let head = run_git(source_path, &["rev-parse", "--verify", "HEAD"])?;
+    // `stash create` records tracked changes as a commit without touching
+    // the source worktree; it prints nothing when the worktree is clean.
+    let stash = if include_uncommitted {
+        Some(run_git(source_path, &["stash", "create"])?).filter(|sha| !sha.is_empty())
+    } else {
+        None
+    };
+
+    let mut worktree = create_local_workspace(
+        Path::new(&source.repo_root_path),
+        workspace_name,
+        WorkspaceBranch::New(branch_name),
+        Some(head.clone()),
+        &source.sparse_paths,
+        suffix,
+        workspaces_root,
+        monitor,
+    )?;
+    worktree.prepared.base_ref = source.base_ref.clone().unwrap_or(head);
+
+    if include_uncommitted {
+        let target_path = Path::new(&worktree.prepared.worktree_path);
+        let copied = stash
+            .map_or(Ok(()), |sha| {
+                run_git(target_path, &["stash", "apply", "--index", &sha]).map(|_| ())
+            })
+            .and_then(|_| copy_untracked_files(source_path, target_path));
+        if let Err(err) = copied {
+            worktree.roll_back();
+            return Err(err);
+        }
+    }
+    Ok(worktree)
+}
+
+fn copy_untracked_files(source: &Path, target: &Path) -> Result<(), WorkspaceError> {
+    let output = run_git_untrimmed(
+        source,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+    )?;
+    for relative in output.split('\0').filter(|path| !path.is_empty()) {
+        let destination = target.join(relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source.join(relative), destination)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::fork_worktree;
+    use crate::workspaces::git_local::create_local_workspace;
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::{
+        build_workspace, Workspace, WorkspaceBranch, WorkspaceSourceType,
+    };
+    use crate::workspaces::test_support::git;
+
+    /// A workspace one commit ahead of `main` with a staged, an unstaged and
+    /// an untracked change.
+    fn dirty_source(root: &Path) -> Workspace {
+        let repo = root.join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "-b", "main"]);
+        fs::write(repo.join("a.txt"), "a\n").unwrap();
+        fs::write(repo.join("b.txt"), "b\n").unwrap();
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "-m", "init"]);
+        let prepared = create_local_workspace(
+            &repo,
+            "Source",
+            WorkspaceBranch::New(None),
+            Some("main".to_string()),
+            &[],
+            "src1",
+            &root.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+        let worktree = Path::new(&prepared.worktree_path).to_path_buf();
+        fs::write(worktree.join("c.txt"), "c\n").unwrap();
+        git(&worktree, &["add", "c.txt"]);
+        git(&worktree, &["commit", "-m", "add c"]);
+        fs::write(worktree.join("a.txt"), "a staged\n").unwrap();
+        git(&worktree, &["add", "a.txt"]);
+        fs::write(worktree.join("b.txt"), "b unstaged\n").unwrap();
+        fs::create_dir_all(worktree.join("notes")).unwrap();
+        fs::write(worktree.join("notes").join("todo.md"), "todo\n").unwrap();
+        build_workspace(
+            "ws_src1".to_string(),
+            "Source".to_string(),
+            WorkspaceSourceType::Local,
+            repo.to_string_lossy().to_string(),
+            prepared,
+        )
+    }
+
+    #[test]
+    fn forks_from_source_head_without_uncommitted_changes() {
+        let dir = tempdir().unwrap();
+        let source = dirty_source(dir.path());
+
+        let prepared = fork_worktree(
+            &source,
+            "Second try",
+            None,
+            false,
+            "frk1",
+            &dir.path().join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        let fork = Path::new(&prepared.worktree_path);
+        assert_eq!(prepared.base_ref, "main");
+        assert_eq!(
+            git(fork, &["rev-parse", "HEAD"]),
+            git(Path::new(&source.worktree_path), &["rev-parse", "HEAD"])
+        );
+        assert_eq!(git(fork, &["status", "--porcelain"]), "");
+    }
+
+    #[test]
+    fn carries_over_staged_unstaged_and_untracked_changes() {
+        let dir = tempdir().unwrap();
+        let source = dirty_source(dir.path());
+
+        let prepared = fork_worktree(
+            &source,
+            "Second try",
+            Some("experiment/second-try".to_string()),
+            true,
+            "frk1",
+            &dir.path().join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        let fork = Path::new(&prepared.worktree_path);
+        assert_eq!(prepared.branch, "experiment/second-try");
+        assert_eq!(
+            git(fork, &["status", "--porcelain"]),
+            git(Path::new(&source.worktree_path), &["status", "--porcelain"])
+        );
+        assert_eq!(
+            fs::read_to_string(fork.join("notes").join("todo.md")).unwrap(),
+            "todo\n"
+        );
+        // The source keeps its changes.
+        assert_eq!(
+            fs::read_to_string(Path::new(&source.worktree_path).join("b.txt")).unwrap(),
+            "b unstaged\n"
+        );
+    }
+}
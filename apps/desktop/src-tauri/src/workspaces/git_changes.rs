@@ -0,0 +1,432 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+
+use super::git_diff::{select_hunks, DiffTarget};
+use super::git_local::{run_git, run_git_untrimmed};
+use super::git_status::{read_head_summary, CommitSummary};
+use super::model::Workspace;
+use super::process::{run_command, run_command_with, RunLimits, HOOK_TIMEOUT, LOCAL_TIMEOUT};
+use super::WorkspaceError;
+
+/// Branches no workspace command may write to, in addition to the repo's
+/// default branch and the workspace's base branch.
+const PROTECTED_BRANCHES: [&str; 2] = ["main", "master"];
+
+/// Files and hunks a stage, unstage or discard applies to. Hunk IDs come from
+/// `workspace_diff` with default options: the `unstaged` target for stage and
+/// discard, the `staged` target for unstage.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceChangeSelection {
+    pub workspace_id: String,
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub hunk_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceCommitInput {
+    pub workspace_id: String,
+    /// Required unless amending, where an empty message keeps the old one.
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub amend: bool,
+}
+
+/// Fails when the worktree has a protected branch checked out. A detached
+/// HEAD is allowed.
+pub fn ensure_branch_writable(workspace: &Workspace) -> Result<(), WorkspaceError> {
+    let worktree_path = Path::new(&workspace.worktree_path);
+    let Ok(branch) = run_git(
+        worktree_path,
+        &["symbolic-ref", "--quiet", "--short", "HEAD"],
+    ) else {
+        return Ok(());
+    };
+
+    if is_protected_branch(worktree_path, &branch, workspace.base_ref.as_deref()) {
+        return Err(WorkspaceError::ProtectedBranch { branch });
+    }
+    Ok(())
+}
+
+/// Whether `branch` is `main`, `master`, the branch behind `base_ref` or the
+/// default branch of `origin` in `repo_path`.
+pub fn is_protected_branch(repo_path: &Path, branch: &str, base_ref: Option<&str>) -> bool {
+    let mut protected = PROTECTED_BRANCHES
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    protected.extend(base_ref.map(remote_branch_name));
+    if let Ok(default_ref) = run_git(
+        repo_path,
+        &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    ) {
+        protected.push(remote_branch_name(&default_ref));
+    }
+    protected.iter().any(|name| name == branch)
+}
+
+fn remote_branch_name(reference: &str) -> String {
+    reference
+        .strip_prefix("origin/")
+        .unwrap_or(reference)
+        .to_string()
+}
+
+pub fn stage_changes(
+    worktree_path: &Path,
+    paths: &[String],
+    hunk_ids: &[String],
+) -> Result<(), WorkspaceError> {
+    if !hunk_ids.is_empty() {
+        let patch = select_hunks(worktree_path, &DiffTarget::Unstaged, hunk_ids)?;
+        apply_patch(worktree_path, &["--cached"], &patch)?;
+    }
+    if !paths.is_empty() {
+        run_git(worktree_path, &with_paths(&["add", "--all"], paths))?;
+    }
+    Ok(())
+}
+
+pub fn unstage_changes(
+    worktree_path: &Path,
+    paths: &[String],
+    hunk_ids: &[String],
+) -> Result<(), WorkspaceError> {
+    if !hunk_ids.is_empty() {
+        let patch = select_hunks(worktree_path, &DiffTarget::Staged, hunk_ids)?;
+        apply_patch(worktree_path, &["--cached", "--reverse"], &patch)?;
+    }
+    if !paths.is_empty() {
+        run_git(worktree_path, &with_paths(&["restore", "--staged"], paths))?;
+    }
+    Ok(())
+}
+
+/// Throws away unstaged changes: selected hunks are reverted, tracked paths
+/// are restored from the index and untracked paths are deleted. Staged
+/// changes are kept.
+pub fn discard_changes(
+    worktree_path: &Path,
+    paths: &[String],
+    hunk_ids: &[String],
+) -> Result<(), WorkspaceError> {
+    if !hunk_ids.is_empty() {
+        let patch = select_hunks(worktree_path, &DiffTarget::Unstaged, hunk_ids)?;
+        apply_patch(worktree_path, &["--reverse"], &patch)?;
+    }
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let tracked = run_git_untrimmed(worktree_path, &with_paths(&["ls-files", "-z"], paths))?;
+    let tracked = tracked
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if !tracked.is_empty() {
+        run_git(
+            worktree_path,
+            &with_paths(&["restore", "--worktree"], &tracked),
+        )?;
+    }
+    run_git(worktree_path, &with_paths(&["clean", "-f", "-d"], paths))?;
+    Ok(())
+}
+
+/// Commits the index. `pre-commit` and `commit-msg` run through
+/// `git hook run` first so their failures surface as `HookFailed`; the commit
+/// itself then skips them.
+pub fn commit_changes(
+    worktree_path: &Path,
+    message: &str,
+    amend: bool,
+) -> Result<CommitSummary, WorkspaceError> {
+    let message = if message.trim().is_empty() {
+        if !amend {
+            return Err(WorkspaceError::InvalidInput(
+                "Commit message must not be empty".to_string(),
+            ));
+        }
+        run_git(worktree_path, &["log", "-1", "--format=%B"])?
+    } else {
+        message.to_string()
+    };
+    if !amend && run_git(worktree_path, &["diff", "--cached", "--quiet"]).is_ok() {
+        return Err(WorkspaceError::InvalidInput(
+            "Nothing is staged to commit".to_string(),
+        ));
+    }
+
+    let git_dir = run_git(worktree_path, &["rev-parse", "--absolute-git-dir"])?;
+    let message_path = Path::new(&git_dir).join("KATA_COMMIT_EDITMSG");
+    fs::write(&message_path, message)?;
+    let message_arg = message_path.to_string_lossy().to_string();
+
+    let result = run_hook(worktree_path, "pre-commit", &[])
+        .and_then(|()| run_hook(worktree_path, "commit-msg", &[&message_arg]))
+        .and_then(|()| {
+            let mut args = vec!["commit", "--no-verify", "--file", &message_arg];
+            if amend {
+                args.push("--amend");
+            }
+            run_git(worktree_path, &args)
+        });
+    let _ = fs::remove_file(&message_path);
+    result?;
+
+    read_head_summary(worktree_path)?.ok_or_else(|| {
+        WorkspaceError::GitFailed("Commit succeeded but HEAD could not be read".to_string())
+    })
+}
+
+fn run_hook(worktree_path: &Path, hook: &str, args: &[&str]) -> Result<(), WorkspaceError> {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(worktree_path)
+        .args(["hook", "run", "--ignore-missing", hook, "--"])
+        .args(args);
+    let output = run_command(
+        command,
+        &format!("The {hook} hook"),
+        RunLimits::timeout(HOOK_TIMEOUT),
+    )?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let combined = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout).trim(),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Err(WorkspaceError::HookFailed {
+        hook: hook.to_string(),
+        exit_code: output.status.code(),
+        output: combined.trim().to_string(),
+    })
+}
+
+fn apply_patch(worktree_path: &Path, args: &[&str], patch: &str) -> Result<(), WorkspaceError> {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(worktree_path)
+        .args(["apply", "--whitespace=nowarn"])
+        .args(args)
+        .arg("-");
+    let output = run_command_with(
+        command,
+        "git apply",
+        RunLimits::timeout(LOCAL_TIMEOUT),
+        Some(patch.as_bytes()),
+        &mut |_| {},
+    )?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(WorkspaceError::GitFailed(format!(
+            "git apply failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+fn with_paths<'a>(args: &[&'a str], paths: &'a [String]) -> Vec<&'a str> {
+    let mut combined = args.to_vec();
+    combined.push("--");
+    combined.extend(paths.iter().map(String::as_str));
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::{
+        commit_changes, discard_changes, ensure_branch_writable, stage_changes, unstage_changes,
+    };
+    use crate::workspaces::git_diff::{diff_workspace, DiffOptions, DiffTarget};
+    use crate::workspaces::model::{
+        build_workspace, next_workspace_id, PreparedWorkspace, Workspace, WorkspaceSourceType,
+    };
+    use crate::workspaces::WorkspaceError;
+    use crate::workspaces::test_support::git;
+
+    /// Repo on `feature/x` whose `notes.txt` has two separate unstaged edits.
+    fn changes_fixture(repo_path: &Path) -> Workspace {
+        fs::create_dir_all(repo_path).unwrap();
+        git(repo_path, &["init"]);
+        git(repo_path, &["config", "user.name", "Kata Test"]);
+        git(repo_path, &["config", "user.email", "kata@example.com"]);
+        git(repo_path, &["checkout", "-B", "main"]);
+        let original = (1..=20).map(|n| format!("line {n}\n")).collect::<String>();
+        fs::write(repo_path.join("notes.txt"), &original).unwrap();
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "initial"]);
+        git(repo_path, &["checkout", "-b", "feature/x"]);
+        let edited = original
+            .replace("line 2\n", "line two\n")
+            .replace("line 19\n", "line nineteen\n");
+        fs::write(repo_path.join("notes.txt"), edited).unwrap();
+
+        build_workspace(
+            next_workspace_id(),
+            "KAT-154".to_string(),
+            WorkspaceSourceType::Local,
+            repo_path.to_string_lossy().to_string(),
+            PreparedWorkspace {
+                repo_root_path: repo_path.to_string_lossy().to_string(),
+                worktree_path: repo_path.to_string_lossy().to_string(),
+                branch: "feature/x".to_string(),
+                base_ref: "main".to_string(),
+                sparse_paths: Vec::new(),
+            },
+        )
+    }
+
+    fn hunk_ids(workspace: &Workspace, target: DiffTarget) -> Vec<String> {
+        diff_workspace(workspace, &target, &DiffOptions::default())
+            .unwrap()
+            .files
+            .into_iter()
+            .flat_map(|file| file.hunks)
+            .map(|hunk| hunk.id)
+            .collect()
+    }
+
+    #[test]
+    fn stages_and_unstages_single_hunks() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        let workspace = changes_fixture(&repo_path);
+        let unstaged = hunk_ids(&workspace, DiffTarget::Unstaged);
+        assert_eq!(unstaged.len(), 2);
+
+        stage_changes(&repo_path, &[], &unstaged[..1]).unwrap();
+
+        let staged_patch = git(&repo_path, &["diff", "--cached"]);
+        assert!(staged_patch.contains("+line two"));
+        assert!(!staged_patch.contains("+line nineteen"));
+        assert!(git(&repo_path, &["diff"]).contains("+line nineteen"));
+
+        let staged = hunk_ids(&workspace, DiffTarget::Staged);
+        unstage_changes(&repo_path, &[], &staged).unwrap();
+        assert!(git(&repo_path, &["diff", "--cached"]).is_empty());
+    }
+
+    #[test]
+    fn rejects_stale_hunk_ids() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        changes_fixture(&repo_path);
+
+        let err = stage_changes(&repo_path, &[], &["0000000000000000".to_string()]).unwrap_err();
+        assert!(matches!(err, WorkspaceError::NotFound(_)));
+    }
+
+    #[test]
+    fn commits_and_amends_staged_changes() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        changes_fixture(&repo_path);
+
+        let err = commit_changes(&repo_path, "Edit notes", false).unwrap_err();
+        assert!(err.to_string().contains("Nothing is staged"));
+
+        stage_changes(&repo_path, &["notes.txt".to_string()], &[]).unwrap();
+        let commit = commit_changes(&repo_path, "Edit notes", false).unwrap();
+        assert_eq!(commit.subject, "Edit notes");
+
+        fs::write(repo_path.join("extra.txt"), "extra\n").unwrap();
+        stage_changes(&repo_path, &["extra.txt".to_string()], &[]).unwrap();
+        let amended = commit_changes(&repo_path, "", true).unwrap();
+        assert_eq!(amended.subject, "Edit notes");
+        assert_ne!(amended.sha, commit.sha);
+        assert_eq!(git(&repo_path, &["rev-list", "--count", "HEAD"]), "2");
+    }
+
+    #[test]
+    fn reports_commit_msg_hook_failure() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        changes_fixture(&repo_path);
+        let hook_path = repo_path.join(".git/hooks/commit-msg");
+        fs::write(
+            &hook_path,
+            "#!/bin/sh\necho 'subject must reference a ticket' >&2\nexit 3\n",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        stage_changes(&repo_path, &["notes.txt".to_string()], &[]).unwrap();
+
+        let err = commit_changes(&repo_path, "Edit notes", false).unwrap_err();
+
+        match err {
+            WorkspaceError::HookFailed {
+                hook,
+                exit_code,
+                output,
+            } => {
+                assert_eq!(hook, "commit-msg");
+                assert_eq!(exit_code, Some(3));
+                assert!(output.contains("must reference a ticket"));
+            }
+            other => panic!("expected HookFailed, got {other:?}"),
+        }
+        assert_eq!(git(&repo_path, &["rev-list", "--count", "HEAD"]), "1");
+    }
+
+    #[test]
+    fn discards_tracked_and_untracked_paths_but_keeps_staged() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        changes_fixture(&repo_path);
+        fs::write(repo_path.join("staged.txt"), "keep\n").unwrap();
+        git(&repo_path, &["add", "staged.txt"]);
+        fs::write(repo_path.join("scratch.txt"), "scratch\n").unwrap();
+
+        discard_changes(
+            &repo_path,
+            &["notes.txt".to_string(), "scratch.txt".to_string()],
+            &[],
+        )
+        .unwrap();
+
+        assert!(git(&repo_path, &["diff"]).is_empty());
+        assert!(!repo_path.join("scratch.txt").exists());
+        assert!(repo_path.join("staged.txt").exists());
+    }
+
+    #[test]
+    fn refuses_protected_branches() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        let workspace = changes_fixture(&repo_path);
+        assert!(ensure_branch_writable(&workspace).is_ok());
+
+        git(&repo_path, &["checkout", "-b", "release"]);
+        let mut on_base = workspace.clone();
+        on_base.base_ref = Some("origin/release".to_string());
+        let err = ensure_branch_writable(&on_base).unwrap_err();
+        assert!(matches!(err, WorkspaceError::ProtectedBranch { branch } if branch == "release"));
+
+        git(&repo_path, &["stash"]);
+        git(&repo_path, &["checkout", "main"]);
+        assert!(ensure_branch_writable(&workspace).is_err());
+    }
+}
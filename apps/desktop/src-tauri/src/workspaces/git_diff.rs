@@ -0,0 +1,831 @@
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use super::git_local::{detect_default_base_ref, run_git, run_git_untrimmed};
+use super::model::Workspace;
+use super::process::{git_timeout, run_command, RunLimits};
+use super::WorkspaceError;
+
+/// Patch bytes returned to the frontend when the caller sets no limit.
+pub const DEFAULT_DIFF_MAX_BYTES: usize = 2 * 1024 * 1024;
+
+/// What the workspace's changes are compared against.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DiffTarget {
+    /// Working tree, staged and unstaged, against the merge-base of HEAD and
+    /// `base_ref`. Untracked files are not included.
+    #[default]
+    Worktree,
+    /// Commit range ending at `to`. `from` defaults to the merge-base of `to`
+    /// and `base_ref`.
+    #[serde(rename_all = "camelCase")]
+    Range { from: Option<String>, to: String },
+    /// Working tree against the index, i.e. what `git add` would stage.
+    Unstaged,
+    /// Index against HEAD, i.e. what the next commit would contain.
+    Staged,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffWhitespace {
+    #[default]
+    Show,
+    IgnoreAll,
+    IgnoreChange,
+    IgnoreAtEol,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffOptions {
+    #[serde(default)]
+    pub whitespace: DiffWhitespace,
+    /// Lines of context around each hunk; git's default of 3 when unset.
+    pub context_lines: Option<u32>,
+    /// Pathspecs limiting the diff, relative to the worktree root.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Cap on patch bytes parsed into hunks; `DEFAULT_DIFF_MAX_BYTES` when unset.
+    pub max_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDiffInput {
+    pub workspace_id: String,
+    #[serde(default)]
+    pub target: DiffTarget,
+    #[serde(default)]
+    pub options: DiffOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffFileStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffLineKind {
+    Context,
+    Addition,
+    Deletion,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub content: String,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+    /// Set when git reported "\ No newline at end of file" after this line.
+    pub no_newline_at_eof: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    /// Stable hash of the file path and hunk contents, used to address the
+    /// hunk in later requests.
+    pub id: String,
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffFile {
+    pub path: String,
+    /// Source path of a rename or copy.
+    pub old_path: Option<String>,
+    pub status: DiffFileStatus,
+    /// Rename or copy similarity in percent.
+    pub similarity: Option<u8>,
+    pub binary: bool,
+    pub additions: u32,
+    pub deletions: u32,
+    pub hunks: Vec<DiffHunk>,
+    /// Hunks are missing or incomplete because the diff hit its size cap.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDiff {
+    pub base_ref: String,
+    /// Commit the changes are compared against; `None` for the index.
+    pub from_commit: Option<String>,
+    /// End of the range, `None` when diffing the working tree.
+    pub to_commit: Option<String>,
+    pub files: Vec<DiffFile>,
+    pub additions: u32,
+    pub deletions: u32,
+    /// The patch exceeded `max_bytes`; files past the cap carry stats only.
+    pub truncated: bool,
+}
+
+pub fn diff_workspace(
+    workspace: &Workspace,
+    target: &DiffTarget,
+    options: &DiffOptions,
+) -> Result<WorkspaceDiff, WorkspaceError> {
+    let worktree_path = Path::new(&workspace.worktree_path);
+    if !worktree_path.exists() {
+        return Err(WorkspaceError::NotFound(format!(
+            "Worktree path does not exist: {}",
+            worktree_path.display()
+        )));
+    }
+    let base_ref = match workspace
+        .base_ref
+        .as_deref()
+        .filter(|value| !value.trim().is_empty())
+    {
+        Some(base_ref) => base_ref.to_string(),
+        None => detect_default_base_ref(worktree_path)?,
+    };
+
+    let (from_commit, to_commit) = match target {
+        DiffTarget::Worktree => (Some(merge_base(worktree_path, "HEAD", &base_ref)?), None),
+        DiffTarget::Range { from, to } => {
+            let to_commit = resolve_commit(worktree_path, to)?;
+            let from_commit = match from.as_deref().filter(|value| !value.trim().is_empty()) {
+                Some(from) => resolve_commit(worktree_path, from)?,
+                None => merge_base(worktree_path, &to_commit, &base_ref)?,
+            };
+            (Some(from_commit), Some(to_commit))
+        }
+        DiffTarget::Unstaged => (None, None),
+        DiffTarget::Staged => (Some(resolve_commit(worktree_path, "HEAD")?), None),
+    };
+
+    let mut range_args = target_range_args(target);
+    if range_args.is_empty() {
+        range_args.extend(from_commit.clone());
+        range_args.extend(to_commit.clone());
+    }
+    let mut diff = read_diff(worktree_path, &range_args, options)?;
+    diff.base_ref = base_ref;
+    diff.from_commit = from_commit;
+    diff.to_commit = to_commit;
+    Ok(diff)
+}
+
+/// Arguments selecting the index-based targets. Empty for the commit-based
+/// targets, whose revisions the caller resolves.
+fn target_range_args(target: &DiffTarget) -> Vec<String> {
+    match target {
+        DiffTarget::Staged => vec!["--cached".to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// Builds a patch holding only the hunks of `hunk_ids`, ready for
+/// `git apply`. IDs refer to a `workspace_diff` of the same target with
+/// default options; any ID that no longer matches fails the whole selection.
+pub fn select_hunks(
+    repo_path: &Path,
+    target: &DiffTarget,
+    hunk_ids: &[String],
+) -> Result<String, WorkspaceError> {
+    let common = diff_args(&target_range_args(target), &DiffOptions::default());
+    let summary = run_git_untrimmed(repo_path, &as_str_args(&summary_args(&common)))?;
+    let files = parse_raw_numstat(&summary);
+    let patch = run_git_untrimmed(repo_path, &as_str_args(&patch_args(&common)))?;
+
+    let mut selected = String::new();
+    let mut matched = 0;
+    for (file, section) in files.iter().zip(split_file_sections(&patch)) {
+        let (header, hunks) = split_hunk_chunks(section);
+        let chosen = hunks
+            .into_iter()
+            .filter(|chunk| {
+                parse_file_section(&file.path, chunk)
+                    .hunks
+                    .first()
+                    .is_some_and(|hunk| hunk_ids.contains(&hunk.id))
+            })
+            .collect::<Vec<_>>();
+        if chosen.is_empty() {
+            continue;
+        }
+        matched += chosen.len();
+        selected.push_str(header);
+        selected.extend(chosen);
+    }
+
+    if matched < hunk_ids.len() {
+        return Err(WorkspaceError::NotFound(
+            "Some hunks no longer match the working tree. Refresh the diff and try again."
+                .to_string(),
+        ));
+    }
+    Ok(selected)
+}
+
+/// Splits one file section into its header and the raw text of each hunk.
+fn split_hunk_chunks(section: &str) -> (&str, Vec<&str>) {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in section.split_inclusive('\n') {
+        if line.starts_with("@@ -") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    let header_end = starts.first().copied().unwrap_or(section.len());
+    let chunks = starts
+        .iter()
+        .enumerate()
+        .map(|(index, start)| {
+            let end = starts.get(index + 1).copied().unwrap_or(section.len());
+            &section[*start..end]
+        })
+        .collect();
+    (&section[..header_end], chunks)
+}
+
+fn diff_args(range_args: &[String], options: &DiffOptions) -> Vec<String> {
+    let mut common = vec![
+        "--no-color".to_string(),
+        "--no-ext-diff".to_string(),
+        "--find-renames".to_string(),
+        "--find-copies".to_string(),
+    ];
+    match options.whitespace {
+        DiffWhitespace::Show => {}
+        DiffWhitespace::IgnoreAll => common.push("--ignore-all-space".to_string()),
+        DiffWhitespace::IgnoreChange => common.push("--ignore-space-change".to_string()),
+        DiffWhitespace::IgnoreAtEol => common.push("--ignore-space-at-eol".to_string()),
+    }
+    if let Some(context_lines) = options.context_lines {
+        common.push(format!("--unified={context_lines}"));
+    }
+    common.extend(range_args.iter().cloned());
+    common.push("--".to_string());
+    common.extend(options.paths.iter().cloned());
+    common
+}
+
+fn summary_args(common: &[String]) -> Vec<String> {
+    let mut args = ["diff", "--raw", "--numstat", "-z"]
+        .map(String::from)
+        .to_vec();
+    args.extend(common.iter().cloned());
+    args
+}
+
+fn patch_args(common: &[String]) -> Vec<String> {
+    let mut args = ["diff", "--patch"].map(String::from).to_vec();
+    args.extend(common.iter().cloned());
+    args
+}
+
+/// Runs `git diff` with `range_args` (revisions or `--cached`). File list and
+/// stats come from `--raw --numstat`, hunks from a patch that is read up to
+/// the byte cap.
+pub fn read_diff(
+    repo_path: &Path,
+    range_args: &[String],
+    options: &DiffOptions,
+) -> Result<WorkspaceDiff, WorkspaceError> {
+    let common = diff_args(range_args, options);
+    let summary = run_git_untrimmed(repo_path, &as_str_args(&summary_args(&common)))?;
+    let mut files = parse_raw_numstat(&summary);
+
+    let max_bytes = options.max_bytes.unwrap_or(DEFAULT_DIFF_MAX_BYTES);
+    let (patch, truncated) =
+        read_capped_stdout(repo_path, &as_str_args(&patch_args(&common)), max_bytes)?;
+
+    let sections = split_file_sections(&patch);
+    let parsed_count = sections.len();
+    for (file, section) in files.iter_mut().zip(sections) {
+        let parsed = parse_file_section(&file.path, section);
+        file.binary = file.binary || parsed.binary;
+        file.hunks = parsed.hunks;
+    }
+    if truncated {
+        // The last parsed section may have been cut mid-hunk.
+        let incomplete_from = parsed_count.saturating_sub(1);
+        for file in files.iter_mut().skip(incomplete_from) {
+            file.truncated = true;
+        }
+    }
+
+    Ok(WorkspaceDiff {
+        base_ref: String::new(),
+        from_commit: None,
+        to_commit: None,
+        additions: files.iter().map(|file| file.additions).sum(),
+        deletions: files.iter().map(|file| file.deletions).sum(),
+        files,
+        truncated,
+    })
+}
+
+fn as_str_args(args: &[String]) -> Vec<&str> {
+    args.iter().map(String::as_str).collect()
+}
+
+fn merge_base(repo_path: &Path, left: &str, right: &str) -> Result<String, WorkspaceError> {
+    run_git(repo_path, &["merge-base", left, right]).map_err(|_| {
+        WorkspaceError::GitFailed(format!("No common ancestor between {left} and {right}"))
+    })
+}
+
+fn resolve_commit(repo_path: &Path, revision: &str) -> Result<String, WorkspaceError> {
+    let spec = format!("{revision}^{{commit}}");
+    run_git(repo_path, &["rev-parse", "--verify", "--quiet", &spec])
+        .map_err(|_| WorkspaceError::NotFound(format!("Unknown revision: {revision}")))
+}
+
+/// Reads stdout of `git <args>` up to `max_bytes`, then stops the process.
+/// Output is cut at the last full line; the flag reports whether it was cut.
+fn read_capped_stdout(
+    repo_path: &Path,
+    args: &[&str],
+    max_bytes: usize,
+) -> Result<(String, bool), WorkspaceError> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo_path).args(args);
+    let description = format!("git {}", args.join(" "));
+    let limits = RunLimits {
+        max_stdout_bytes: Some(max_bytes),
+        ..RunLimits::timeout(git_timeout(args))
+    };
+    let output = run_command(command, &description, limits)?;
+    let mut buffer = output.stdout;
+
+    let truncated = buffer.len() > max_bytes;
+    if truncated {
+        buffer.truncate(max_bytes);
+        let line_end = buffer
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |index| index + 1);
+        buffer.truncate(line_end);
+    }
+
+    if !truncated && !output.status.success() {
+        return Err(WorkspaceError::GitFailed(format!(
+            "{description} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok((String::from_utf8_lossy(&buffer).to_string(), truncated))
+}
+
+/// Parses `git diff --raw --numstat -z`. Raw records come first and carry the
+/// status and paths, numstat records follow in the same order with counts.
+fn parse_raw_numstat(output: &str) -> Vec<DiffFile> {
+    let mut files = Vec::new();
+    let mut stats = Vec::new();
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+
+    while let Some(field) = fields.next() {
+        if let Some(raw) = field.strip_prefix(':') {
+            let status_code = raw.rsplit(' ').next().unwrap_or_default();
+            let (status, similarity) = parse_status_code(status_code);
+            let first_path = fields.next().unwrap_or_default().to_string();
+            let (path, old_path) = match status {
+                DiffFileStatus::Renamed | DiffFileStatus::Copied => (
+                    fields.next().unwrap_or_default().to_string(),
+                    Some(first_path),
+                ),
+                _ => (first_path, None),
+            };
+            files.push(DiffFile {
+                path,
+                old_path,
+                status,
+                similarity,
+                binary: false,
+                additions: 0,
+                deletions: 0,
+                hunks: Vec::new(),
+                truncated: false,
+            });
+        } else {
+            let mut counts = field.splitn(3, '\t');
+            let additions = counts.next().unwrap_or_default();
+            let deletions = counts.next().unwrap_or_default();
+            // Renames and copies list their paths in the next two fields.
+            if counts.next().unwrap_or_default().is_empty() {
+                fields.next();
+                fields.next();
+            }
+            stats.push((additions.parse::<u32>().ok(), deletions.parse::<u32>().ok()));
+        }
+    }
+
+    for (file, (additions, deletions)) in files.iter_mut().zip(stats) {
+        file.binary = additions.is_none() && deletions.is_none();
+        file.additions = additions.unwrap_or(0);
+        file.deletions = deletions.unwrap_or(0);
+    }
+    files
+}
+
+fn parse_status_code(code: &str) -> (DiffFileStatus, Option<u8>) {
+    let similarity = code.get(1..).and_then(|score| score.parse::<u8>().ok());
+    let status = match code.chars().next() {
+        Some('A') => DiffFileStatus::Added,
+        Some('D') => DiffFileStatus::Deleted,
+        Some('R') => DiffFileStatus::Renamed,
+        Some('C') => DiffFileStatus::Copied,
+        Some('T') => DiffFileStatus::TypeChanged,
+        _ => DiffFileStatus::Modified,
+    };
+    (status, similarity)
+}
+
+/// Splits a patch into per-file sections, each starting at `diff --git`.
+fn split_file_sections(patch: &str) -> Vec<&str> {
+    let mut starts = Vec::new();
+    let mut offset = 0;
+    for line in patch.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, start)| {
+            let end = starts.get(index + 1).copied().unwrap_or(patch.len());
+            &patch[*start..end]
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct ParsedSection {
+    binary: bool,
+    hunks: Vec<DiffHunk>,
+}
+
+fn parse_file_section(path: &str, section: &str) -> ParsedSection {
+    let mut parsed = ParsedSection::default();
+    let mut current: Option<DiffHunk> = None;
+    let (mut old_line, mut new_line) = (0, 0);
+
+    for line in section.lines() {
+        if let Some(hunk) = parse_hunk_header(line) {
+            if let Some(done) = current.take() {
+                parsed.hunks.push(finish_hunk(path, done));
+            }
+            old_line = hunk.old_start;
+            new_line = hunk.new_start;
+            current = Some(hunk);
+            continue;
+        }
+        let Some(hunk) = current.as_mut() else {
+            if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                parsed.binary = true;
+            }
+            continue;
+        };
+
+        let (kind, content) = match line.split_at_checked(1) {
+            Some((" ", content)) => (DiffLineKind::Context, content),
+            Some(("+", content)) => (DiffLineKind::Addition, content),
+            Some(("-", content)) => (DiffLineKind::Deletion, content),
+            Some(("\\", _)) => {
+                if let Some(previous) = hunk.lines.last_mut() {
+                    previous.no_newline_at_eof = true;
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        let (old_number, new_number) = match kind {
+            DiffLineKind::Context => (Some(old_line), Some(new_line)),
+            DiffLineKind::Addition => (None, Some(new_line)),
+            DiffLineKind::Deletion => (Some(old_line), None),
+        };
+        if old_number.is_some() {
+            old_line += 1;
+        }
+        if new_number.is_some() {
+            new_line += 1;
+        }
+        hunk.lines.push(DiffLine {
+            kind,
+            content: content.to_string(),
+            old_line: old_number,
+            new_line: new_number,
+            no_newline_at_eof: false,
+        });
+    }
+    if let Some(done) = current {
+        parsed.hunks.push(finish_hunk(path, done));
+    }
+    parsed
+}
+
+/// Parses `@@ -a,b +c,d @@ section`; an omitted count means one line.
+fn parse_hunk_header(line: &str) -> Option<DiffHunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, _) = rest.split_once(" @@")?;
+    let (old_range, new_range) = ranges.split_once(" +")?;
+    let (old_start, old_lines) = parse_range(old_range)?;
+    let (new_start, new_lines) = parse_range(new_range)?;
+    Some(DiffHunk {
+        id: String::new(),
+        header: line.to_string(),
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines: Vec::new(),
+    })
+}
+
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn finish_hunk(path: &str, mut hunk: DiffHunk) -> DiffHunk {
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, path.as_bytes());
+    hash = fnv1a(hash, b"\0");
+    hash = fnv1a(hash, hunk.header.as_bytes());
+    for line in &hunk.lines {
+        let marker: &[u8] = match line.kind {
+            DiffLineKind::Context => b"\n ",
+            DiffLineKind::Addition => b"\n+",
+            DiffLineKind::Deletion => b"\n-",
+        };
+        hash = fnv1a(hash, marker);
+        hash = fnv1a(hash, line.content.as_bytes());
+    }
+    hunk.id = format!("{hash:016x}");
+    hunk
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::{
+        diff_workspace, parse_file_section, parse_raw_numstat, read_capped_stdout, DiffFileStatus,
+        DiffLineKind, DiffOptions, DiffTarget, DiffWhitespace,
+    };
+    use crate::workspaces::model::{
+        build_workspace, next_workspace_id, PreparedWorkspace, Workspace, WorkspaceSourceType,
+    };
+    use crate::workspaces::test_support::git;
+
+    /// Repo on `feature/x` branched from `main`, with `main` moved on since.
+    fn diff_fixture(repo_path: &Path) -> Workspace {
+        fs::create_dir_all(repo_path).unwrap();
+        git(repo_path, &["init"]);
+        git(repo_path, &["checkout", "-B", "main"]);
+        fs::write(repo_path.join("notes.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(repo_path.join("old-name.txt"), "a\nb\nc\nd\ne\nf\n").unwrap();
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "initial"]);
+        git(repo_path, &["checkout", "-b", "feature/x"]);
+        git(repo_path, &["mv", "old-name.txt", "new-name.txt"]);
+        fs::write(repo_path.join("notes.txt"), "one\n2\nthree\nfour").unwrap();
+        fs::write(repo_path.join("image.bin"), [0u8, 159, 146, 150, 0, 1]).unwrap();
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "feature"]);
+        git(repo_path, &["checkout", "main"]);
+        fs::write(repo_path.join("main-only.txt"), "main\n").unwrap();
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "main moves on"]);
+        git(repo_path, &["checkout", "feature/x"]);
+
+        build_workspace(
+            next_workspace_id(),
+            "KAT-154".to_string(),
+            WorkspaceSourceType::Local,
+            repo_path.to_string_lossy().to_string(),
+            PreparedWorkspace {
+                repo_root_path: repo_path.to_string_lossy().to_string(),
+                worktree_path: repo_path.to_string_lossy().to_string(),
+                branch: "feature/x".to_string(),
+                base_ref: "main".to_string(),
+                sparse_paths: Vec::new(),
+            },
+        )
+    }
+
+    #[test]
+    fn diffs_worktree_against_merge_base() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        let workspace = diff_fixture(&repo_path);
+        fs::write(repo_path.join("notes.txt"), "one\n2\nthree\nfour\nfive\n").unwrap();
+
+        let diff =
+            diff_workspace(&workspace, &DiffTarget::Worktree, &DiffOptions::default()).unwrap();
+
+        assert!(!diff.truncated);
+        assert_eq!(
+            diff.from_commit,
+            Some(git(&repo_path, &["rev-parse", "main~1"]))
+        );
+        let paths = diff
+            .files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect::<Vec<_>>();
+        assert!(!paths.contains(&"main-only.txt"));
+
+        let renamed = diff
+            .files
+            .iter()
+            .find(|file| file.path == "new-name.txt")
+            .unwrap();
+        assert_eq!(renamed.status, DiffFileStatus::Renamed);
+        assert_eq!(renamed.old_path.as_deref(), Some("old-name.txt"));
+        assert_eq!(renamed.similarity, Some(100));
+
+        let binary = diff
+            .files
+            .iter()
+            .find(|file| file.path == "image.bin")
+            .unwrap();
+        assert!(binary.binary);
+        assert!(binary.hunks.is_empty());
+
+        let notes = diff
+            .files
+            .iter()
+            .find(|file| file.path == "notes.txt")
+            .unwrap();
+        assert_eq!((notes.additions, notes.deletions), (3, 1));
+        let lines = &notes.hunks[0].lines;
+        assert_eq!(lines[1].kind, DiffLineKind::Deletion);
+        assert_eq!(lines[1].old_line, Some(2));
+        assert_eq!(lines[2].content, "2");
+        assert_eq!(lines[2].new_line, Some(2));
+    }
+
+    #[test]
+    fn diffs_commit_range_and_filters_paths() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        let workspace = diff_fixture(&repo_path);
+        fs::write(repo_path.join("notes.txt"), "uncommitted\n").unwrap();
+
+        let diff = diff_workspace(
+            &workspace,
+            &DiffTarget::Range {
+                from: None,
+                to: "HEAD".to_string(),
+            },
+            &DiffOptions {
+                whitespace: DiffWhitespace::IgnoreAll,
+                context_lines: Some(0),
+                paths: vec!["notes.txt".to_string()],
+                max_bytes: None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(diff.files.len(), 1);
+        assert_eq!(
+            diff.to_commit,
+            Some(git(&repo_path, &["rev-parse", "HEAD"]))
+        );
+        let hunks = &diff.files[0].hunks;
+        assert!(hunks
+            .iter()
+            .all(|hunk| hunk.lines.iter().all(|line| line.content != "uncommitted")));
+        assert!(hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .all(|line| line.kind != DiffLineKind::Context));
+    }
+
+    #[test]
+    fn caps_patch_size_and_keeps_file_stats() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        let workspace = diff_fixture(&repo_path);
+        let large = (0..2000).map(|n| format!("line {n}\n")).collect::<String>();
+        fs::write(repo_path.join("notes.txt"), large).unwrap();
+
+        let diff = diff_workspace(
+            &workspace,
+            &DiffTarget::Worktree,
+            &DiffOptions {
+                max_bytes: Some(4096),
+                ..DiffOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert!(diff.truncated);
+        let notes = diff
+            .files
+            .iter()
+            .find(|file| file.path == "notes.txt")
+            .unwrap();
+        assert!(notes.truncated);
+        assert_eq!(notes.additions, 2000);
+    }
+
+    #[test]
+    fn capped_read_does_not_block_on_a_full_stderr_pipe() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path();
+        git(repo_path, &["init"]);
+        // More stderr than a pipe buffer holds, written before any stdout.
+        let noisy = "alias.noisy=!head -c 1000000 /dev/zero | tr '\\0' w >&2; yes";
+
+        let (output, truncated) =
+            read_capped_stdout(repo_path, &["-c", noisy, "noisy"], 100).unwrap();
+
+        assert!(truncated);
+        assert_eq!(output, "y\n".repeat(50));
+    }
+
+    #[test]
+    fn parses_hunks_with_missing_newline_and_stable_ids() {
+        let section = "diff --git a/x.txt b/x.txt\n\
+                       --- a/x.txt\n\
+                       +++ b/x.txt\n\
+                       @@ -1 +1,2 @@ fn main\n\
+                       -old\n\
+                       \\ No newline at end of file\n\
+                       +new\n\
+                       +--- not a header\n";
+
+        let first = parse_file_section("x.txt", section);
+        let second = parse_file_section("x.txt", section);
+        let other_path = parse_file_section("y.txt", section);
+
+        let hunk = &first.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines), (1, 1));
+        assert_eq!((hunk.new_start, hunk.new_lines), (1, 2));
+        assert!(hunk.lines[0].no_newline_at_eof);
+        assert_eq!(hunk.lines[2].content, "--- not a header");
+        assert_eq!(hunk.id, second.hunks[0].id);
+        assert_ne!(hunk.id, other_path.hunks[0].id);
+    }
+
+    #[test]
+    fn parses_raw_and_numstat_records() {
+        let output = [
+            ":100644 100644 aaaa bbbb M",
+            "src/with space.rs",
+            ":100644 100644 aaaa aaaa R090",
+            "old.rs",
+            "new.rs",
+            ":000000 100644 0000 cccc A",
+            "logo.png",
+            "3\t1\tsrc/with space.rs",
+            "1\t1\t",
+            "old.rs",
+            "new.rs",
+            "-\t-\tlogo.png",
+            "",
+        ]
+        .join("\0");
+
+        let files = parse_raw_numstat(&output);
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].path, "src/with space.rs");
+        assert_eq!((files[0].additions, files[0].deletions), (3, 1));
+        assert_eq!(files[1].status, DiffFileStatus::Renamed);
+        assert_eq!(files[1].old_path.as_deref(), Some("old.rs"));
+        assert_eq!(files[1].similarity, Some(90));
+        assert_eq!(files[2].status, DiffFileStatus::Added);
+        assert!(files[2].binary);
+    }
+}
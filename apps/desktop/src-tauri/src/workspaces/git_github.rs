@@ -6,116 +6,419 @@ use chrono::Utc;
 use serde::Deserialize;
 use url::Url;
 
-use super::git_local::create_local_workspace;
+use super::ci_checks::{check_rollup, StatusCheckRollupItem};
+use super::git_local::{
+    branch_exists, create_local_workspace, ensure_branch_not_checked_out, origin_remote_url,
+    AddedWorktree,
+};
+use super::git_progress::{run_git_with_progress, GitMonitor};
 use super::model::{
-    WorkspaceBranchOption, WorkspaceIssueOption, WorkspacePullRequestOption, PreparedWorkspace,
+    CloneStrategy, OpenPullRequestInput, PullRequestState, PullRequestStatus, RepoCacheOptions,
+    ReviewDecision, Workspace, WorkspaceBranch, WorkspaceBranchOption, WorkspaceIssueOption,
+    WorkspacePullRequest, WorkspacePullRequestOption, WorkspaceSourceType,
 };
+use super::operations::{begin_operation, Operation};
+use super::process::{git_timeout, run_command, RunLimits, GH_TIMEOUT, LONG_TIMEOUT};
 use super::WorkspaceError;
 
-pub struct CreatedGitHubWorkspace {
-    pub prepared: PreparedWorkspace,
-    pub repo_url: String,
+#[derive(Debug)]
+pub struct CreatedPullRequestWorkspace {
+    pub worktree: AddedWorktree,
+    /// Set when the worktree is on the pull request's head branch.
+    pub pull_request: Option<WorkspacePullRequest>,
+    /// Set when the worktree is on a new branch started from the head.
+    pub source_pull_request: Option<WorkspacePullRequest>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_github_workspace(
     repo_url: &str,
     workspace_name: &str,
-    clone_root_path: Option<String>,
-    branch_name: Option<String>,
+    cache: RepoCacheOptions,
+    branch: WorkspaceBranch,
     base_ref: Option<String>,
+    sparse_paths: &[String],
     suffix: &str,
     app_data_dir: &Path,
-) -> Result<PreparedWorkspace, WorkspaceError> {
+    monitor: GitMonitor<'_>,
+) -> Result<AddedWorktree, WorkspaceError> {
+    let cache_repo_path = ensure_cache_repo(repo_url, &cache, app_data_dir, monitor)?;
+
+    let workspaces_root = app_data_dir.join("workspaces");
+    create_local_workspace(
+        &cache_repo_path,
+        workspace_name,
+        branch,
+        base_ref,
+        sparse_paths,
+        suffix,
+        &workspaces_root,
+        monitor,
+    )
+}
+
+/// Creates a workspace from a pull request. The PR head is fetched from
+/// `refs/pull/<n>/head`, which also covers PRs opened from forks. With
+/// `checkout_existing` the worktree is on the PR's head branch; for forks that
+/// is a local `pr/<n>/<head>` branch whose pushes go to the fork when the
+/// author allows maintainer edits, moved to the fetched head if left from an
+/// earlier checkout. Otherwise a new branch starts at the head, and the pull
+/// request is only recorded as the one the workspace was started from.
+#[allow(clippy::too_many_arguments)]
+pub fn create_pull_request_workspace(
+    repo_url: &str,
+    mut pull_request: WorkspacePullRequest,
+    checkout_existing: bool,
+    workspace_name: &str,
+    cache: RepoCacheOptions,
+    sparse_paths: &[String],
+    suffix: &str,
+    app_data_dir: &Path,
+    monitor: GitMonitor<'_>,
+) -> Result<CreatedPullRequestWorkspace, WorkspaceError> {
+    let cache_repo_path = ensure_cache_repo(repo_url, &cache, app_data_dir, monitor)?;
+    let pull_ref = format!("refs/pull/{}/head", pull_request.number);
+    run_git_with_progress(
+        &cache_repo_path,
+        &[
+            "fetch",
+            "--progress",
+            "origin",
+            &format!("+{pull_ref}:{pull_ref}"),
+        ],
+        monitor,
+    )?;
+
+    let workspaces_root = app_data_dir.join("workspaces");
+    if !checkout_existing {
+        let base_ref = if pull_request.is_cross_repository {
+            pull_ref
+        } else {
+            format!("origin/{}", pull_request.head_branch)
+        };
+        let worktree = create_local_workspace(
+            &cache_repo_path,
+            workspace_name,
+            WorkspaceBranch::New(None),
+            Some(base_ref),
+            sparse_paths,
+            suffix,
+            &workspaces_root,
+            monitor,
+        )?;
+        return Ok(CreatedPullRequestWorkspace {
+            worktree,
+            pull_request: None,
+            source_pull_request: Some(pull_request),
+        });
+    }
+
+    let base_ref = format!("origin/{}", pull_request.base_branch);
+    let mut leftover_head = None;
+    let (branch, start_ref) = if !pull_request.is_cross_repository {
+        (
+            WorkspaceBranch::Existing(pull_request.head_branch.clone()),
+            base_ref.clone(),
+        )
+    } else {
+        let local_branch = pull_request.fork_branch();
+        if branch_exists(&cache_repo_path, &local_branch)? {
+            // A branch left from an earlier checkout of the PR is moved to the
+            // head just fetched, unless a worktree still has it checked out,
+            // and moved back if the workspace is not created.
+            ensure_branch_not_checked_out(&cache_repo_path, &local_branch)?;
+            leftover_head = Some(run_git_in_dir(
+                &cache_repo_path,
+                &["rev-parse", &format!("refs/heads/{local_branch}")],
+            )?);
+            run_git_in_dir(
+                &cache_repo_path,
+                &["branch", "--force", &local_branch, &pull_ref],
+            )?;
+            (WorkspaceBranch::Existing(local_branch), base_ref.clone())
+        } else {
+            // Created along with the worktree, so the journal deletes it on
+            // rollback.
+            (WorkspaceBranch::New(Some(local_branch)), pull_ref)
+        }
+    };
+    let created = create_local_workspace(
+        &cache_repo_path,
+        workspace_name,
+        branch,
+        Some(start_ref),
+        sparse_paths,
+        suffix,
+        &workspaces_root,
+        monitor,
+    )
+    .and_then(|mut worktree| {
+        worktree.prepared.base_ref = base_ref;
+        if pull_request.is_cross_repository && pull_request.maintainer_can_modify {
+            match configure_fork_push(&cache_repo_path, &pull_request, &worktree.prepared.branch) {
+                Ok(push_remote) => pull_request.push_remote = push_remote,
+                Err(err) => {
+                    worktree.roll_back();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(worktree)
+    });
+    let worktree = match created {
+        Ok(worktree) => worktree,
+        Err(err) => {
+            if let Some(head) = leftover_head {
+                let _ = run_git_in_dir(
+                    &cache_repo_path,
+                    &["branch", "--force", &pull_request.fork_branch(), &head],
+                );
+            }
+            return Err(err);
+        }
+    };
+
+    Ok(CreatedPullRequestWorkspace {
+        worktree,
+        pull_request: Some(pull_request),
+        source_pull_request: None,
+    })
+}
+
+/// Points `local_branch` at the fork's head branch: adds a `fork-<owner>`
+/// remote, sets it as upstream and adds a push refspec so a plain `git push`
+/// updates the PR. Returns the remote name, or `None` if the fork is gone.
+fn configure_fork_push(
+    repo_path: &Path,
+    pull_request: &WorkspacePullRequest,
+    local_branch: &str,
+) -> Result<Option<String>, WorkspaceError> {
+    let (Some(repository), Some(repository_url)) = (
+        pull_request.head_repository.as_deref(),
+        pull_request.head_repository_url(),
+    ) else {
+        return Ok(None);
+    };
+    let owner = repository.split('/').next().unwrap_or(repository);
+    let remote = format!("fork-{owner}");
+
+    if run_git_in_dir(repo_path, &["remote", "get-url", &remote]).is_ok() {
+        run_git_in_dir(repo_path, &["remote", "set-url", &remote, &repository_url])?;
+    } else {
+        run_git_in_dir(repo_path, &["remote", "add", &remote, &repository_url])?;
+    }
+
+    let head_ref = format!("refs/heads/{}", pull_request.head_branch);
+    run_git_in_dir(
+        repo_path,
+        &["config", &format!("branch.{local_branch}.remote"), &remote],
+    )?;
+    run_git_in_dir(
+        repo_path,
+        &["config", &format!("branch.{local_branch}.merge"), &head_ref],
+    )?;
+
+    let push_key = format!("remote.{remote}.push");
+    let refspec = format!("refs/heads/{local_branch}:{head_ref}");
+    let existing =
+        run_git_in_dir(repo_path, &["config", "--get-all", &push_key]).unwrap_or_default();
+    if !existing.lines().any(|line| line.trim() == refspec) {
+        run_git_in_dir(repo_path, &["config", "--add", &push_key, &refspec])?;
+    }
+    Ok(Some(remote))
+}
+
+/// Path of the repo cache for `repo_url`. Shared mirrors are bare
+/// repositories, so they get a `.git` suffix and never collide with a regular
+/// clone of the same repository.
+pub fn cache_repo_path(
+    repo_url: &str,
+    cache: &RepoCacheOptions,
+    app_data_dir: &Path,
+) -> Result<PathBuf, WorkspaceError> {
     let (owner, repo) = parse_github_repo_url(repo_url)?;
     let clone_root = normalize_clone_root_path(
-        clone_root_path,
+        cache.clone_root_path.clone(),
         app_data_dir,
         app_data_dir.join("repo-cache").join("github"),
     );
-    let cache_repo_path = clone_root.join(format!("{owner}__{repo}"));
+    let name = match cache.clone_strategy {
+        CloneStrategy::SharedMirror => format!("{owner}__{repo}.git"),
+        _ => format!("{owner}__{repo}"),
+    };
+    Ok(clone_root.join(name))
+}
+
+/// Clones `repo_url` into the repo cache, or fetches it if already cached.
+/// Only `origin` is fetched: fork remotes added for pull request pushes may
+/// have gone away.
+fn ensure_cache_repo(
+    repo_url: &str,
+    cache: &RepoCacheOptions,
+    app_data_dir: &Path,
+    monitor: GitMonitor<'_>,
+) -> Result<PathBuf, WorkspaceError> {
+    let cache_repo_path = cache_repo_path(repo_url, cache, app_data_dir)?;
 
     if cache_repo_path.exists() {
-        run_git_in_dir(&cache_repo_path, &["fetch", "--all", "--prune"])?;
+        run_git_with_progress(
+            &cache_repo_path,
+            &["fetch", "--prune", "--progress", "origin"],
+            monitor,
+        )?;
     } else {
         if let Some(parent) = cache_repo_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        run_git_in_dir(
+        clone_cache_repo(
+            repo_url,
+            &cache_repo_path,
+            cache.clone_strategy,
+            app_data_dir,
+            monitor,
+        )?;
+    }
+    Ok(cache_repo_path)
+}
+
+fn clone_cache_repo(
+    repo_url: &str,
+    destination: &Path,
+    strategy: CloneStrategy,
+    app_data_dir: &Path,
+    monitor: GitMonitor<'_>,
+) -> Result<(), WorkspaceError> {
+    let operation = begin_operation(
+        &app_data_dir.join("workspaces"),
+        Operation::CloneCache {
+            repo_url: repo_url.to_string(),
+            cache_path: destination.to_string_lossy().to_string(),
+        },
+    )?;
+    let destination_arg = destination.to_string_lossy();
+    let cloned = match strategy {
+        CloneStrategy::Full => run_git_with_progress(
+            app_data_dir,
+            &["clone", "--progress", repo_url, &destination_arg],
+            monitor,
+        ),
+        CloneStrategy::Blobless => run_git_with_progress(
             app_data_dir,
             &[
                 "clone",
+                "--progress",
+                "--filter=blob:none",
                 repo_url,
-                cache_repo_path.to_string_lossy().as_ref(),
+                &destination_arg,
             ],
-        )?;
+            monitor,
+        ),
+        // Every branch is kept so existing branches can still be checked out.
+        CloneStrategy::Shallow { depth } => run_git_with_progress(
+            app_data_dir,
+            &[
+                "clone",
+                "--progress",
+                "--depth",
+                &depth.max(1).to_string(),
+                "--no-single-branch",
+                repo_url,
+                &destination_arg,
+            ],
+            monitor,
+        ),
+        // Unlike `clone --mirror`, branches stay under `origin/` as in the
+        // other strategies, and there are no local branches to go stale.
+        CloneStrategy::SharedMirror => {
+            run_git_in_dir(app_data_dir, &["init", "--bare", &destination_arg])
+                .and_then(|_| run_git_in_dir(destination, &["remote", "add", "origin", repo_url]))
+                .and_then(|_| {
+                    run_git_with_progress(destination, &["fetch", "--progress", "origin"], monitor)
+                })
+                .and_then(|_| {
+                    run_git_in_dir(destination, &["remote", "set-head", "origin", "--auto"])
+                })
+        }
+    };
+    // A half-made cache would only be fetched, never finished, next time.
+    match cloned {
+        Ok(_) => {
+            operation.finish();
+            Ok(())
+        }
+        Err(err) => {
+            operation.roll_back();
+            Err(err)
+        }
     }
-
-    let workspaces_root = app_data_dir.join("workspaces");
-    create_local_workspace(
-        &cache_repo_path,
-        workspace_name,
-        branch_name,
-        base_ref,
-        suffix,
-        &workspaces_root,
-    )
 }
 
-pub fn create_new_github_workspace(
+/// URL of the repository `create_new_github_workspace` creates for
+/// `repository_name`, owned by the authenticated user unless it names an owner.
+pub fn new_github_repo_url(
     repository_name: &str,
-    workspace_name: &str,
-    clone_root_path: Option<String>,
-    branch_name: Option<String>,
-    base_ref: Option<String>,
-    suffix: &str,
     app_data_dir: &Path,
-) -> Result<CreatedGitHubWorkspace, WorkspaceError> {
-    let clone_root = normalize_clone_root_path(
-        clone_root_path,
-        app_data_dir,
-        app_data_dir.join("repo-cache").join("github-created"),
-    );
-    fs::create_dir_all(&clone_root)?;
-
+) -> Result<String, WorkspaceError> {
+    fs::create_dir_all(app_data_dir)?;
     let (owner_override, repo) = split_repository_name(repository_name)?;
     let owner = match owner_override {
         Some(owner) => owner,
-        None => gh_authenticated_owner(&clone_root)?,
+        None => gh_authenticated_owner(app_data_dir)?,
     };
-    let repo_identifier = format!("{owner}/{repo}");
-    let repo_url = format!("https://github.com/{repo_identifier}");
+    Ok(format!("https://github.com/{owner}/{repo}"))
+}
 
-    let clone_destination = clone_root.join(&repo);
-    if clone_destination.exists() {
-        return Err(WorkspaceError::InvalidInput(format!(
-            "Clone destination already exists: {}",
-            clone_destination.display()
-        )));
+/// Creates a private repository on GitHub and a workspace in its repo cache.
+/// A repository or repo cache left by an earlier, failed attempt is reused, so
+/// the creation can be retried.
+#[allow(clippy::too_many_arguments)]
+pub fn create_new_github_workspace(
+    repo_url: &str,
+    workspace_name: &str,
+    cache: RepoCacheOptions,
+    branch: WorkspaceBranch,
+    base_ref: Option<String>,
+    suffix: &str,
+    app_data_dir: &Path,
+    monitor: GitMonitor<'_>,
+) -> Result<AddedWorktree, WorkspaceError> {
+    let repo_identifier = repo_id_from_url(repo_url)?;
+    let has_cache = cache_repo_path(repo_url, &cache, app_data_dir)?.exists();
+    if !has_cache && !github_repo_exists(&repo_identifier, app_data_dir) {
+        run_gh(
+            app_data_dir,
+            &[
+                "repo",
+                "create",
+                &repo_identifier,
+                "--private",
+                "--add-readme",
+            ],
+        )?;
     }
-
-    run_gh(
-        &clone_root,
-        &[
-            "repo",
-            "create",
-            &repo_identifier,
-            "--private",
-            "--clone",
-            "--add-readme",
-        ],
-    )?;
+    let cache_repo_path = ensure_cache_repo(repo_url, &cache, app_data_dir, monitor)?;
 
     let workspaces_root = app_data_dir.join("workspaces");
-    let prepared = create_local_workspace(
-        &clone_destination,
+    create_local_workspace(
+        &cache_repo_path,
         workspace_name,
-        branch_name,
+        branch,
         base_ref,
+        &[],
         suffix,
         &workspaces_root,
-    )?;
+        monitor,
+    )
+}
 
-    Ok(CreatedGitHubWorkspace { prepared, repo_url })
+fn github_repo_exists(repo_id: &str, cwd: &Path) -> bool {
+    run_gh(cwd, &["repo", "view", repo_id, "--json", "name"]).is_ok()
+}
+
+/// `owner/repo` of a github.com repository URL.
+pub fn repo_id_from_url(repo_url: &str) -> Result<String, WorkspaceError> {
+    let (owner, repo) = parse_github_repo_url(repo_url)?;
+    Ok(format!("{owner}/{repo}"))
 }
 
 pub fn repo_url_from_id(repo_id: &str) -> Result<String, WorkspaceError> {
@@ -246,10 +549,13 @@ pub fn list_repo_branches(
 ) -> Result<Vec<WorkspaceBranchOption>, WorkspaceError> {
     let repo_url = repo_url_from_id(repo_id)?;
     let default_name = repo_default_branch(repo_id)?;
-    let output = Command::new("git")
-        .args(["ls-remote", "--heads", &repo_url])
-        .output()
-        .map_err(WorkspaceError::Io)?;
+    let mut command = Command::new("git");
+    command.args(["ls-remote", "--heads", &repo_url]);
+    let output = run_command(
+        command,
+        "git ls-remote",
+        RunLimits::timeout(LONG_TIMEOUT),
+    )?;
     if !output.status.success() {
         return Err(WorkspaceError::GitFailed(format!(
             "git ls-remote failed: {}",
@@ -308,7 +614,10 @@ pub fn repo_default_branch(repo_id: &str) -> Result<String, WorkspaceError> {
     Ok(branch.to_string())
 }
 
-pub fn pull_request_head_branch(repo_id: &str, number: u32) -> Result<String, WorkspaceError> {
+pub fn pull_request_head(
+    repo_id: &str,
+    number: u32,
+) -> Result<WorkspacePullRequest, WorkspaceError> {
     let output = run_gh(
         Path::new("."),
         &[
@@ -318,18 +627,399 @@ pub fn pull_request_head_branch(repo_id: &str, number: u32) -> Result<String, Wo
             repo_id,
             &number.to_string(),
             "--json",
-            "headRefName",
-            "--jq",
-            ".headRefName",
+            "number,url,headRefName,baseRefName,headRepository,headRepositoryOwner,\
+             isCrossRepository,maintainerCanModify",
         ],
     )?;
-    let branch = output.trim();
-    if branch.is_empty() {
+    parse_pull_request_view(&output, number)
+}
+
+fn parse_pull_request_view(
+    output: &str,
+    number: u32,
+) -> Result<WorkspacePullRequest, WorkspaceError> {
+    #[derive(Debug, Deserialize)]
+    struct RepositoryRef {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct OwnerRef {
+        login: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PullRequestView {
+        number: u32,
+        url: String,
+        head_ref_name: String,
+        base_ref_name: String,
+        head_repository: Option<RepositoryRef>,
+        head_repository_owner: Option<OwnerRef>,
+        #[serde(default)]
+        is_cross_repository: bool,
+        #[serde(default)]
+        maintainer_can_modify: bool,
+    }
+
+    let view = serde_json::from_str::<PullRequestView>(output)
+        .map_err(|err| WorkspaceError::GitFailed(format!("Failed to parse pull request: {err}")))?;
+    if view.head_ref_name.trim().is_empty() {
         return Err(WorkspaceError::InvalidInput(format!(
             "Pull request not found: {number}"
         )));
     }
-    Ok(branch.to_string())
+
+    let head_repository = match (view.head_repository_owner, view.head_repository) {
+        (Some(owner), Some(repository)) => Some(format!("{}/{}", owner.login, repository.name)),
+        _ => None,
+    };
+    Ok(WorkspacePullRequest {
+        number: view.number,
+        url: view.url,
+        head_branch: view.head_ref_name,
+        base_branch: view.base_ref_name,
+        head_repository,
+        is_cross_repository: view.is_cross_repository,
+        maintainer_can_modify: view.maintainer_can_modify,
+        push_remote: None,
+        status: None,
+    })
+}
+
+pub fn read_pull_request_status(
+    repo_id: &str,
+    number: u32,
+) -> Result<PullRequestStatus, WorkspaceError> {
+    let output = run_gh(
+        Path::new("."),
+        &[
+            "pr",
+            "view",
+            "--repo",
+            repo_id,
+            &number.to_string(),
+            "--json",
+            "state,isDraft,reviewDecision,statusCheckRollup,mergedAt",
+        ],
+    )?;
+    parse_pull_request_status(&output)
+}
+
+fn parse_pull_request_status(output: &str) -> Result<PullRequestStatus, WorkspaceError> {
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct StatusView {
+        state: String,
+        #[serde(default)]
+        is_draft: bool,
+        review_decision: Option<String>,
+        #[serde(default)]
+        status_check_rollup: Option<Vec<StatusCheckRollupItem>>,
+        merged_at: Option<String>,
+    }
+
+    let view = serde_json::from_str::<StatusView>(output).map_err(|err| {
+        WorkspaceError::GitFailed(format!("Failed to parse pull request status: {err}"))
+    })?;
+    let state = match view.state.as_str() {
+        "OPEN" => PullRequestState::Open,
+        "MERGED" => PullRequestState::Merged,
+        "CLOSED" => PullRequestState::Closed,
+        other => {
+            return Err(WorkspaceError::GitFailed(format!(
+                "Unknown pull request state: {other}"
+            )))
+        }
+    };
+    let review_decision = match view.review_decision.as_deref() {
+        Some("APPROVED") => Some(ReviewDecision::Approved),
+        Some("CHANGES_REQUESTED") => Some(ReviewDecision::ChangesRequested),
+        Some("REVIEW_REQUIRED") => Some(ReviewDecision::ReviewRequired),
+        _ => None,
+    };
+
+    let checks = view
+        .status_check_rollup
+        .unwrap_or_default()
+        .into_iter()
+        .map(StatusCheckRollupItem::into_check_run)
+        .collect::<Vec<_>>();
+
+    Ok(PullRequestStatus {
+        state,
+        is_draft: view.is_draft,
+        review_decision,
+        checks: check_rollup(&checks),
+        merged_at: view.merged_at.filter(|value| !value.is_empty()),
+    })
+}
+
+/// Pushes the workspace branch and opens a pull request against the branch
+/// behind `base_ref`, or updates the one already open for that branch.
+pub fn open_pull_request(
+    workspace: &Workspace,
+    input: &OpenPullRequestInput,
+) -> Result<WorkspacePullRequest, WorkspaceError> {
+    let title = input.title.trim();
+    if title.is_empty() {
+        return Err(WorkspaceError::InvalidInput(
+            "Pull request title is required".to_string(),
+        ));
+    }
+    let worktree_path = Path::new(&workspace.worktree_path);
+    let repo_id = workspace_repo_id(workspace)?;
+    let (existing, head_branch) = pull_request_target(workspace);
+
+    push_pull_request_head(worktree_path, existing, head_branch)?;
+
+    let open_number = match existing {
+        Some(pr) if pr.is_cross_repository => Some(pr.number),
+        _ => find_open_pull_request(worktree_path, &repo_id, head_branch)?,
+    };
+    let number = match open_number {
+        Some(number) => {
+            update_pull_request(worktree_path, &repo_id, number, title, input)?;
+            number
+        }
+        None => {
+            let base_branch = match pull_request_base_branch(workspace.base_ref.as_deref()) {
+                Some(branch) => branch,
+                None => repo_default_branch(&repo_id)?,
+            };
+            let mut args = vec![
+                "pr",
+                "create",
+                "--repo",
+                &repo_id,
+                "--base",
+                &base_branch,
+                "--head",
+                head_branch,
+                "--title",
+                title,
+                "--body",
+                &input.body,
+            ];
+            if input.draft {
+                args.push("--draft");
+            }
+            push_list_args(&mut args, "--reviewer", &input.reviewers);
+            push_list_args(&mut args, "--label", &input.labels);
+            let output = run_gh(worktree_path, &args)?;
+            pull_request_number_from_url(&output).ok_or_else(|| {
+                WorkspaceError::GitFailed(format!(
+                    "Unable to read pull request number from gh output: {output}"
+                ))
+            })?
+        }
+    };
+
+    let mut pull_request = pull_request_head(&repo_id, number)?;
+    pull_request.push_remote = existing
+        .filter(|pr| pr.number == number)
+        .and_then(|pr| pr.push_remote.clone());
+    Ok(pull_request)
+}
+
+/// The pull request `open_pull_request` pushes to and edits, and its head
+/// branch. That is the recorded one while the workspace is on its head
+/// branch; otherwise a pull request for the workspace branch is opened.
+fn pull_request_target(workspace: &Workspace) -> (Option<&WorkspacePullRequest>, &str) {
+    match workspace.pull_request.as_ref() {
+        Some(pr) if pr.is_head_branch(&workspace.branch) => (Some(pr), pr.head_branch.as_str()),
+        _ => (None, workspace.branch.as_str()),
+    }
+}
+
+/// `owner/name` of the workspace repository, from its GitHub source URL or
+/// the `origin` remote.
+pub fn workspace_repo_id(workspace: &Workspace) -> Result<String, WorkspaceError> {
+    let repo_url = match workspace.source_type {
+        WorkspaceSourceType::Github => github_repo_url_from_remote(&workspace.source),
+        WorkspaceSourceType::Local => None,
+    }
+    .or_else(|| {
+        origin_remote_url(Path::new(&workspace.repo_root_path))
+            .and_then(|url| github_repo_url_from_remote(&url))
+    })
+    .ok_or_else(|| {
+        WorkspaceError::InvalidInput(
+            "Workspace repository has no github.com origin remote".to_string(),
+        )
+    })?;
+    repo_id_from_url(&repo_url)
+}
+
+/// Pushes `HEAD` to the PR head branch: the fork for checked-out fork PRs,
+/// `origin` (setting it as upstream) otherwise.
+fn push_pull_request_head(
+    worktree_path: &Path,
+    existing: Option<&WorkspacePullRequest>,
+    head_branch: &str,
+) -> Result<(), WorkspaceError> {
+    let refspec = format!("HEAD:refs/heads/{head_branch}");
+    match existing.filter(|pr| pr.is_cross_repository) {
+        Some(pr) => {
+            if !pr.maintainer_can_modify {
+                return Err(WorkspaceError::InvalidInput(format!(
+                    "Pull request #{} does not allow maintainers to push to its branch",
+                    pr.number
+                )));
+            }
+            let remote = pr
+                .push_remote
+                .clone()
+                .or_else(|| pr.head_repository_url())
+                .ok_or_else(|| {
+                    WorkspaceError::InvalidInput(format!(
+                        "The fork for pull request #{} no longer exists",
+                        pr.number
+                    ))
+                })?;
+            run_git_in_dir(worktree_path, &["push", &remote, &refspec])?;
+        }
+        None => {
+            run_git_in_dir(
+                worktree_path,
+                &["push", "--set-upstream", "origin", &refspec],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn find_open_pull_request(
+    cwd: &Path,
+    repo_id: &str,
+    head_branch: &str,
+) -> Result<Option<u32>, WorkspaceError> {
+    let output = run_gh(
+        cwd,
+        &[
+            "pr",
+            "list",
+            "--repo",
+            repo_id,
+            "--head",
+            head_branch,
+            "--state",
+            "open",
+            "--json",
+            "number",
+            "--jq",
+            ".[0].number // empty",
+        ],
+    )?;
+    Ok(output.trim().parse::<u32>().ok())
+}
+
+fn update_pull_request(
+    cwd: &Path,
+    repo_id: &str,
+    number: u32,
+    title: &str,
+    input: &OpenPullRequestInput,
+) -> Result<(), WorkspaceError> {
+    let number_arg = number.to_string();
+    let mut args = vec![
+        "pr",
+        "edit",
+        &number_arg,
+        "--repo",
+        repo_id,
+        "--title",
+        title,
+        "--body",
+        &input.body,
+    ];
+    push_list_args(&mut args, "--add-reviewer", &input.reviewers);
+    push_list_args(&mut args, "--add-label", &input.labels);
+    run_gh(cwd, &args)?;
+
+    let is_draft = run_gh(
+        cwd,
+        &[
+            "pr",
+            "view",
+            &number_arg,
+            "--repo",
+            repo_id,
+            "--json",
+            "isDraft",
+            "--jq",
+            ".isDraft",
+        ],
+    )?;
+    if (is_draft.trim() == "true") != input.draft {
+        let mut args = vec!["pr", "ready", &number_arg, "--repo", repo_id];
+        if input.draft {
+            args.push("--undo");
+        }
+        run_gh(cwd, &args)?;
+    }
+    Ok(())
+}
+
+fn push_list_args<'a>(args: &mut Vec<&'a str>, flag: &'a str, values: &'a [String]) {
+    for value in values.iter().map(|value| value.trim()) {
+        if !value.is_empty() {
+            args.push(flag);
+            args.push(value);
+        }
+    }
+}
+
+/// The branch a new PR should target: `base_ref` without its `origin/` or
+/// `refs/heads/` prefix. `None` when `base_ref` is unset or not a branch.
+fn pull_request_base_branch(base_ref: Option<&str>) -> Option<String> {
+    let base_ref = base_ref.map(str::trim).filter(|value| !value.is_empty())?;
+    let branch = if let Some(rest) = base_ref.strip_prefix("refs/remotes/origin/") {
+        rest
+    } else if let Some(rest) = base_ref.strip_prefix("refs/heads/") {
+        rest
+    } else if base_ref.starts_with("refs/") {
+        return None;
+    } else {
+        base_ref.strip_prefix("origin/").unwrap_or(base_ref)
+    };
+    (!branch.is_empty() && branch != "HEAD").then(|| branch.to_string())
+}
+
+fn pull_request_number_from_url(output: &str) -> Option<u32> {
+    output
+        .lines()
+        .rev()
+        .find_map(|line| line.trim().rsplit_once("/pull/"))
+        .and_then(|(_, number)| number.trim_end_matches('/').parse().ok())
+}
+
+/// Normalizes an `origin` remote URL (https, `git@github.com:` or `ssh://`)
+/// to `https://github.com/<owner>/<repo>`. Returns `None` for other hosts.
+pub fn github_repo_url_from_remote(remote_url: &str) -> Option<String> {
+    let trimmed = remote_url.trim();
+    let path = if let Some(rest) = trimmed.strip_prefix("git@github.com:") {
+        rest.to_string()
+    } else {
+        let parsed = Url::parse(trimmed).ok()?;
+        if parsed.host_str() != Some("github.com") {
+            return None;
+        }
+        parsed.path().to_string()
+    };
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    match segments.as_slice() {
+        [owner, repo] => Some(format!(
+            "https://github.com/{owner}/{}",
+            repo.trim_end_matches(".git")
+        )),
+        _ => None,
+    }
 }
 
 fn parse_github_repo_url(repo_url: &str) -> Result<(String, String), WorkspaceError> {
@@ -419,7 +1109,13 @@ fn detect_home_dir() -> Option<PathBuf> {
 }
 
 fn run_git_in_dir(cwd: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
-    let output = Command::new("git").current_dir(cwd).args(args).output()?;
+    let mut command = Command::new("git");
+    command.current_dir(cwd).args(args);
+    let output = run_command(
+        command,
+        &format!("git {}", args.join(" ")),
+        RunLimits::timeout(git_timeout(args)),
+    )?;
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     } else {
@@ -466,20 +1162,22 @@ fn split_repository_name(repository_name: &str) -> Result<(Option<String>, Strin
     }
 }
 
-fn run_gh(cwd: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
-    let output = Command::new("gh")
-        .current_dir(cwd)
-        .args(args)
-        .output()
-        .map_err(|error| {
-            if error.kind() == std::io::ErrorKind::NotFound {
-                WorkspaceError::InvalidInput(
-                    "GitHub CLI not found. Install gh and run `gh auth login`.".to_string(),
-                )
-            } else {
-                WorkspaceError::Io(error)
-            }
-        })?;
+pub fn run_gh(cwd: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
+    let mut command = Command::new("gh");
+    command.current_dir(cwd).args(args);
+    let output = run_command(
+        command,
+        &format!("gh {}", args.join(" ")),
+        RunLimits::timeout(GH_TIMEOUT),
+    )
+    .map_err(|error| match error {
+        WorkspaceError::Io(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            WorkspaceError::InvalidInput(
+                "GitHub CLI not found. Install gh and run `gh auth login`.".to_string(),
+            )
+        }
+        error => error,
+    })?;
 
     if output.status.success() {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
@@ -498,11 +1196,472 @@ fn run_gh(cwd: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
     use std::path::Path;
 
     use super::{
-        create_github_workspace, normalize_clone_root_path_with_home, split_repository_name,
+        clone_cache_repo, create_github_workspace, create_new_github_workspace,
+        create_pull_request_workspace, github_repo_url_from_remote,
+        normalize_clone_root_path_with_home, parse_pull_request_view, pull_request_base_branch,
+        pull_request_number_from_url, pull_request_target, push_pull_request_head,
+        split_repository_name,
+    };
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::{
+        build_workspace, next_workspace_id, CloneStrategy, RepoCacheOptions, WorkspaceBranch,
+        WorkspacePullRequest, WorkspaceSourceType,
     };
+    use crate::workspaces::test_support::git;
+
+    fn fork_pull_request() -> WorkspacePullRequest {
+        WorkspacePullRequest {
+            number: 7,
+            url: "https://github.com/acme/widgets/pull/7".to_string(),
+            head_branch: "main".to_string(),
+            base_branch: "main".to_string(),
+            head_repository: Some("contributor/widgets".to_string()),
+            is_cross_repository: true,
+            maintainer_can_modify: true,
+            push_remote: None,
+            status: None,
+        }
+    }
+
+    /// Sets up an `origin` with a fork PR head only reachable through
+    /// `refs/pull/7/head`, and a cache clone of it under `<tmp>/clones`.
+    fn pull_request_fixture(root: &Path) -> String {
+        let origin = root.join("origin");
+        fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init"]);
+        git(&origin, &["checkout", "-B", "main"]);
+        fs::write(origin.join("README.md"), "# fixture\n").unwrap();
+        git(&origin, &["add", "."]);
+        git(&origin, &["commit", "-m", "initial"]);
+        git(&origin, &["checkout", "--detach"]);
+        fs::write(origin.join("fork.txt"), "from a fork\n").unwrap();
+        git(&origin, &["add", "."]);
+        git(&origin, &["commit", "-m", "fork change"]);
+        let pull_head = git(&origin, &["rev-parse", "HEAD"]);
+        git(&origin, &["update-ref", "refs/pull/7/head", &pull_head]);
+        git(&origin, &["checkout", "main"]);
+
+        let clones = root.join("clones");
+        fs::create_dir_all(&clones).unwrap();
+        git(
+            &clones,
+            &["clone", origin.to_str().unwrap(), "acme__widgets"],
+        );
+        pull_head
+    }
+
+    fn clones_cache(root: &Path) -> RepoCacheOptions {
+        RepoCacheOptions {
+            clone_root_path: Some(root.join("clones").to_string_lossy().to_string()),
+            clone_strategy: CloneStrategy::Full,
+        }
+    }
+
+    #[test]
+    fn parses_pull_request_view_for_forks() {
+        let pull_request = parse_pull_request_view(
+            r#"{
+                "number": 7,
+                "url": "https://github.com/acme/widgets/pull/7",
+                "headRefName": "main",
+                "baseRefName": "main",
+                "headRepository": { "id": "R_1", "name": "widgets" },
+                "headRepositoryOwner": { "id": "U_1", "login": "contributor" },
+                "isCrossRepository": true,
+                "maintainerCanModify": true
+            }"#,
+            7,
+        )
+        .unwrap();
+        assert_eq!(pull_request, fork_pull_request());
+        assert_eq!(
+            pull_request.head_repository_url().as_deref(),
+            Some("https://github.com/contributor/widgets")
+        );
+    }
+
+    #[test]
+    fn checks_out_fork_pull_request_head_with_push_to_fork() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pull_head = pull_request_fixture(tmpdir.path());
+        let app_data_dir = tmpdir.path().join("app-data");
+
+        let created = create_pull_request_workspace(
+            "https://github.com/acme/widgets",
+            fork_pull_request(),
+            true,
+            "KAT-7",
+            clones_cache(tmpdir.path()),
+            &[],
+            "ab12",
+            &app_data_dir,
+            GitMonitor::default(),
+        )
+        .unwrap();
+
+        let worktree = Path::new(&created.worktree.prepared.worktree_path);
+        assert_eq!(created.worktree.prepared.branch, "pr/7/main");
+        assert_eq!(created.worktree.prepared.base_ref, "origin/main");
+        assert_eq!(git(worktree, &["rev-parse", "HEAD"]), pull_head);
+        let pull_request = created.pull_request.unwrap();
+        assert_eq!(
+            pull_request.push_remote.as_deref(),
+            Some("fork-contributor")
+        );
+        assert_eq!(
+            git(worktree, &["config", "branch.pr/7/main.remote"]),
+            "fork-contributor"
+        );
+        assert_eq!(
+            git(worktree, &["config", "remote.fork-contributor.push"]),
+            "refs/heads/pr/7/main:refs/heads/main"
+        );
+    }
+
+    #[test]
+    fn moves_leftover_pull_request_branch_to_the_fetched_head() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        pull_request_fixture(tmpdir.path());
+        let app_data_dir = tmpdir.path().join("app-data");
+        let create = |suffix: &str| {
+            create_pull_request_workspace(
+                "https://github.com/acme/widgets",
+                fork_pull_request(),
+                true,
+                "KAT-7",
+                clones_cache(tmpdir.path()),
+                &[],
+                suffix,
+                &app_data_dir,
+                GitMonitor::default(),
+            )
+        };
+        let first = create("ab12").unwrap().worktree.prepared;
+
+        let err = create("cd34").unwrap_err();
+        assert!(err.to_string().contains("already checked out"), "{err}");
+
+        let cache = Path::new(&first.repo_root_path);
+        git(
+            cache,
+            &["worktree", "remove", "--force", &first.worktree_path],
+        );
+        let origin = tmpdir.path().join("origin");
+        git(&origin, &["checkout", "--detach", "refs/pull/7/head"]);
+        git(&origin, &["commit", "--allow-empty", "-m", "force-pushed"]);
+        let new_head = git(&origin, &["rev-parse", "HEAD"]);
+        git(&origin, &["update-ref", "refs/pull/7/head", &new_head]);
+        git(&origin, &["checkout", "main"]);
+
+        let second = create("ef56").unwrap().worktree.prepared;
+        assert_eq!(
+            git(Path::new(&second.worktree_path), &["rev-parse", "HEAD"]),
+            new_head
+        );
+    }
+
+    #[test]
+    fn leaves_pull_request_branch_as_it_was_when_creation_fails() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pull_head = pull_request_fixture(tmpdir.path());
+        let app_data_dir = tmpdir.path().join("app-data");
+        // Taken by something else, so adding the worktree fails.
+        fs::create_dir_all(app_data_dir.join("workspaces").join("kat-7-ab12")).unwrap();
+        let create = || {
+            create_pull_request_workspace(
+                "https://github.com/acme/widgets",
+                fork_pull_request(),
+                true,
+                "KAT-7",
+                clones_cache(tmpdir.path()),
+                &[],
+                "ab12",
+                &app_data_dir,
+                GitMonitor::default(),
+            )
+        };
+        let cache = tmpdir.path().join("clones").join("acme__widgets");
+
+        create().unwrap_err();
+        assert!(git(&cache, &["branch", "--list", "pr/7/main"]).is_empty());
+
+        let leftover_head = git(&cache, &["rev-parse", "main"]);
+        git(&cache, &["branch", "pr/7/main", &leftover_head]);
+        create().unwrap_err();
+        assert_eq!(git(&cache, &["rev-parse", "pr/7/main"]), leftover_head);
+        assert_ne!(leftover_head, pull_head);
+    }
+
+    #[test]
+    fn pushes_same_repository_branch_to_origin_and_tracks_it() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let remote = tmpdir.path().join("remote.git");
+        git(tmpdir.path(), &["init", "--bare", remote.to_str().unwrap()]);
+        let clone = tmpdir.path().join("clone");
+        git(
+            tmpdir.path(),
+            &["clone", remote.to_str().unwrap(), clone.to_str().unwrap()],
+        );
+        git(&clone, &["checkout", "-b", "feature/x"]);
+        git(&clone, &["commit", "--allow-empty", "-m", "feature"]);
+
+        push_pull_request_head(&clone, None, "feature/x").unwrap();
+
+        assert_eq!(
+            git(&remote, &["rev-parse", "refs/heads/feature/x"]),
+            git(&clone, &["rev-parse", "HEAD"])
+        );
+        assert_eq!(
+            git(&clone, &["rev-parse", "--abbrev-ref", "@{upstream}"]),
+            "origin/feature/x"
+        );
+    }
+
+    #[test]
+    fn pushes_fork_pull_request_branch_to_the_fork_head() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        pull_request_fixture(tmpdir.path());
+        let created = create_pull_request_workspace(
+            "https://github.com/acme/widgets",
+            fork_pull_request(),
+            true,
+            "KAT-7",
+            clones_cache(tmpdir.path()),
+            &[],
+            "ab12",
+            &tmpdir.path().join("app-data"),
+            GitMonitor::default(),
+        )
+        .unwrap();
+        let pull_request = created.pull_request.unwrap();
+        let worktree = Path::new(&created.worktree.prepared.worktree_path);
+        // Stand-in for the contributor's fork on GitHub.
+        let fork = tmpdir.path().join("fork.git");
+        git(tmpdir.path(), &["init", "--bare", fork.to_str().unwrap()]);
+        git(
+            worktree,
+            &[
+                "remote",
+                "set-url",
+                "fork-contributor",
+                fork.to_str().unwrap(),
+            ],
+        );
+        git(
+            worktree,
+            &["commit", "--allow-empty", "-m", "maintainer fix"],
+        );
+
+        push_pull_request_head(worktree, Some(&pull_request), &pull_request.head_branch).unwrap();
+        assert_eq!(
+            git(&fork, &["rev-parse", "refs/heads/main"]),
+            git(worktree, &["rev-parse", "HEAD"])
+        );
+
+        // A plain push follows the refspec set up for the branch.
+        git(worktree, &["commit", "--allow-empty", "-m", "second fix"]);
+        git(worktree, &["push"]);
+        assert_eq!(
+            git(&fork, &["rev-parse", "refs/heads/main"]),
+            git(worktree, &["rev-parse", "HEAD"])
+        );
+
+        let read_only = WorkspacePullRequest {
+            maintainer_can_modify: false,
+            ..pull_request
+        };
+        let err = push_pull_request_head(worktree, Some(&read_only), "main").unwrap_err();
+        assert!(err.to_string().contains("does not allow maintainers"));
+    }
+
+    #[test]
+    fn starts_new_branch_from_fork_pull_request_head() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pull_head = pull_request_fixture(tmpdir.path());
+
+        let created = create_pull_request_workspace(
+            "https://github.com/acme/widgets",
+            fork_pull_request(),
+            false,
+            "KAT-7",
+            clones_cache(tmpdir.path()),
+            &[],
+            "ab12",
+            &tmpdir.path().join("app-data"),
+            GitMonitor::default(),
+        )
+        .unwrap();
+
+        assert!(created.pull_request.is_none());
+        let source = created.source_pull_request.unwrap();
+        assert_eq!(source.number, 7);
+        assert_eq!(
+            source.head_repository.as_deref(),
+            Some("contributor/widgets")
+        );
+        assert_eq!(created.worktree.prepared.base_ref, "refs/pull/7/head");
+        assert_eq!(
+            git(
+                Path::new(&created.worktree.prepared.worktree_path),
+                &["rev-parse", "HEAD"]
+            ),
+            pull_head
+        );
+    }
+
+    #[test]
+    fn new_branch_from_pull_request_opens_its_own_pull_request() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pull_head = pull_request_fixture(tmpdir.path());
+        let origin = tmpdir.path().join("origin");
+        git(&origin, &["branch", "feature/x", &pull_head]);
+        let same_repository = WorkspacePullRequest {
+            head_branch: "feature/x".to_string(),
+            head_repository: Some("acme/widgets".to_string()),
+            is_cross_repository: false,
+            maintainer_can_modify: false,
+            ..fork_pull_request()
+        };
+
+        let created = create_pull_request_workspace(
+            "https://github.com/acme/widgets",
+            same_repository,
+            false,
+            "KAT-7",
+            clones_cache(tmpdir.path()),
+            &[],
+            "ab12",
+            &tmpdir.path().join("app-data"),
+            GitMonitor::default(),
+        )
+        .unwrap();
+        assert!(created.pull_request.is_none());
+        let mut workspace = build_workspace(
+            next_workspace_id(),
+            "KAT-7".to_string(),
+            WorkspaceSourceType::Github,
+            "https://github.com/acme/widgets".to_string(),
+            created.worktree.prepared,
+        );
+        // Even a pull request recorded as its own is left alone while the
+        // workspace is on another branch.
+        workspace.pull_request = created.source_pull_request;
+
+        let (existing, head_branch) = pull_request_target(&workspace);
+        assert!(existing.is_none());
+        assert_eq!(head_branch, workspace.branch);
+        let worktree = Path::new(&workspace.worktree_path);
+        git(worktree, &["commit", "--allow-empty", "-m", "follow-up"]);
+        push_pull_request_head(worktree, existing, head_branch).unwrap();
+        assert_eq!(
+            git(
+                &origin,
+                &["rev-parse", &format!("refs/heads/{head_branch}")]
+            ),
+            git(worktree, &["rev-parse", "HEAD"])
+        );
+        assert_eq!(git(&origin, &["rev-parse", "feature/x"]), pull_head);
+    }
+
+    #[test]
+    fn new_repository_workspace_reuses_the_cache_of_a_failed_attempt() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        pull_request_fixture(tmpdir.path());
+
+        let worktree = create_new_github_workspace(
+            "https://github.com/acme/widgets",
+            "KAT-154",
+            clones_cache(tmpdir.path()),
+            WorkspaceBranch::New(None),
+            None,
+            "ab12",
+            &tmpdir.path().join("app-data"),
+            GitMonitor::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            worktree.prepared.repo_root_path,
+            tmpdir
+                .path()
+                .join("clones")
+                .join("acme__widgets")
+                .canonicalize()
+                .unwrap()
+                .to_string_lossy()
+        );
+        let worktree_path = Path::new(&worktree.prepared.worktree_path);
+        assert!(worktree_path.join("README.md").exists());
+    }
+
+    #[test]
+    fn adds_pull_request_worktree_off_shared_mirror() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let pull_head = pull_request_fixture(tmpdir.path());
+        let mirror = tmpdir.path().join("clones").join("acme__widgets.git");
+        clone_cache_repo(
+            tmpdir.path().join("origin").to_str().unwrap(),
+            &mirror,
+            CloneStrategy::SharedMirror,
+            tmpdir.path(),
+            GitMonitor::default(),
+        )
+        .unwrap();
+        assert_eq!(git(&mirror, &["rev-parse", "--is-bare-repository"]), "true");
+        assert_eq!(git(&mirror, &["for-each-ref", "refs/heads"]), "");
+
+        let created = create_pull_request_workspace(
+            "https://github.com/acme/widgets",
+            fork_pull_request(),
+            false,
+            "KAT-7",
+            RepoCacheOptions {
+                clone_strategy: CloneStrategy::SharedMirror,
+                ..clones_cache(tmpdir.path())
+            },
+            &[],
+            "ab12",
+            &tmpdir.path().join("app-data"),
+            GitMonitor::default(),
+        )
+        .unwrap();
+
+        let worktree = Path::new(&created.worktree.prepared.worktree_path);
+        assert_eq!(git(worktree, &["rev-parse", "HEAD"]), pull_head);
+        assert_eq!(
+            Path::new(&created.worktree.prepared.repo_root_path),
+            mirror.canonicalize().unwrap()
+        );
+    }
+
+    #[test]
+    fn shallow_cache_keeps_every_branch() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        pull_request_fixture(tmpdir.path());
+        let origin = tmpdir.path().join("origin");
+        git(&origin, &["branch", "feature/x", "main"]);
+        let cache = tmpdir.path().join("shallow");
+
+        clone_cache_repo(
+            &format!("file://{}", origin.display()),
+            &cache,
+            CloneStrategy::Shallow { depth: 1 },
+            tmpdir.path(),
+            GitMonitor::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            git(&cache, &["rev-parse", "--is-shallow-repository"]),
+            "true"
+        );
+        assert_eq!(git(&cache, &["rev-list", "--count", "origin/main"]), "1");
+        assert!(git(&cache, &["branch", "-r"]).contains("origin/feature/x"));
+    }
 
     #[test]
     fn rejects_non_github_remote_urls() {
@@ -510,11 +1669,13 @@ mod tests {
         let err = create_github_workspace(
             "https://gitlab.com/org/repo",
             "KAT-154",
+            RepoCacheOptions::default(),
+            WorkspaceBranch::New(None),
             None,
-            None,
-            None,
+            &[],
             "ab12",
             app_data_dir.path(),
+            GitMonitor::default(),
         )
         .unwrap_err();
         assert!(err.to_string().contains("github.com"));
@@ -569,9 +1730,54 @@ mod tests {
         assert_eq!(defaulted, Path::new("/tmp/default"));
     }
 
+    #[test]
+    fn normalizes_github_remote_urls() {
+        for remote in [
+            "https://github.com/kata-sh/kata-cloud-agents.git",
+            "git@github.com:kata-sh/kata-cloud-agents.git",
+            "ssh://git@github.com/kata-sh/kata-cloud-agents",
+        ] {
+            assert_eq!(
+                github_repo_url_from_remote(remote),
+                Some("https://github.com/kata-sh/kata-cloud-agents".to_string())
+            );
+        }
+        assert_eq!(
+            github_repo_url_from_remote("https://gitlab.com/org/repo"),
+            None
+        );
+        assert_eq!(github_repo_url_from_remote("/tmp/local/repo"), None);
+    }
+
     #[test]
     fn rejects_invalid_repository_name_shapes() {
         let err = split_repository_name("owner/repo/extra").unwrap_err();
         assert!(err.to_string().contains("<owner>/<name>"));
     }
+
+    #[test]
+    fn resolves_pull_request_base_branch_from_base_ref() {
+        assert_eq!(
+            pull_request_base_branch(Some("origin/release/1.2")),
+            Some("release/1.2".to_string())
+        );
+        assert_eq!(
+            pull_request_base_branch(Some("refs/heads/main")),
+            Some("main".to_string())
+        );
+        assert_eq!(
+            pull_request_base_branch(Some("develop")),
+            Some("develop".to_string())
+        );
+        assert_eq!(pull_request_base_branch(Some("refs/pull/42/head")), None);
+        assert_eq!(pull_request_base_branch(Some("origin/HEAD")), None);
+        assert_eq!(pull_request_base_branch(None), None);
+    }
+
+    #[test]
+    fn reads_pull_request_number_from_created_url() {
+        let output = "Warning: 1 uncommitted change\nhttps://github.com/kata-sh/kata/pull/128\n";
+        assert_eq!(pull_request_number_from_url(output), Some(128));
+        assert_eq!(pull_request_number_from_url("no url here"), None);
+    }
 }
@@ -1,18 +1,44 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use super::model::{derive_workspace_branch_name, slugify_name, PreparedWorkspace};
+use super::git_progress::{run_git_with_progress, GitMonitor};
+use super::model::{
+    derive_workspace_branch_name, slugify_name, PreparedWorkspace, WorkspaceBranch,
+};
+use super::operations::{begin_operation, Operation, PendingOperation};
+use super::process::{git_timeout, run_command, RunLimits, LOCAL_TIMEOUT};
 use super::WorkspaceError;
 
+/// A worktree added by `create_local_workspace`. Its journal entry stays
+/// open until the caller has recorded the workspace, so that quitting before
+/// then rolls the worktree back at startup instead of leaving it behind.
+#[must_use]
+#[derive(Debug)]
+pub struct AddedWorktree {
+    pub prepared: PreparedWorkspace,
+    pub operation: PendingOperation,
+}
+
+impl AddedWorktree {
+    /// Removes the worktree, and its branch if it was created, after a later
+    /// step failed.
+    pub fn roll_back(self) {
+        self.operation.roll_back();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_local_workspace(
     repo_path: &Path,
     workspace_name: &str,
-    branch_name: Option<String>,
+    branch: WorkspaceBranch,
     base_ref: Option<String>,
+    sparse_paths: &[String],
     suffix: &str,
     workspaces_root: &Path,
-) -> Result<PreparedWorkspace, WorkspaceError> {
+    monitor: GitMonitor<'_>,
+) -> Result<AddedWorktree, WorkspaceError> {
     if workspace_name.trim().is_empty() {
         return Err(WorkspaceError::InvalidInput(
             "Workspace name must not be empty".to_string(),
@@ -27,21 +53,40 @@ pub fn create_local_workspace(
 
     let repo_root_path = canonicalize_path(repo_path)?;
     verify_git_repo(repo_path)?;
+    let sparse_paths = normalize_sparse_paths(sparse_paths)?;
 
-    let branch = branch_name
-        .filter(|value| !value.trim().is_empty())
-        .unwrap_or_else(|| derive_workspace_branch_name(workspace_name, suffix));
+    let (branch, checkout_existing) = match branch {
+        WorkspaceBranch::New(branch_name) => (
+            branch_name
+                .filter(|value| !value.trim().is_empty())
+                .unwrap_or_else(|| derive_workspace_branch_name(workspace_name, suffix)),
+            false,
+        ),
+        WorkspaceBranch::Existing(branch_name) => {
+            let normalized = branch_name.trim().trim_start_matches("origin/").to_string();
+            if normalized.is_empty() {
+                return Err(WorkspaceError::InvalidInput(
+                    "Branch name is required to check out an existing branch".to_string(),
+                ));
+            }
+            (normalized, true)
+        }
+    };
     if branch == "main" || branch == "master" {
         return Err(WorkspaceError::InvalidInput(
             "Workspace branch cannot be main/master".to_string(),
         ));
     }
 
-    if branch_exists(repo_path, &branch)? {
+    let has_local_branch = branch_exists(repo_path, &branch)?;
+    if !checkout_existing && has_local_branch {
         return Err(WorkspaceError::InvalidInput(format!(
             "Branch already exists: {branch}"
         )));
     }
+    if checkout_existing {
+        ensure_branch_not_checked_out(repo_path, &branch)?;
+    }
 
     let resolved_base_ref = match base_ref.filter(|value| !value.trim().is_empty()) {
         Some(explicit) => explicit,
@@ -57,39 +102,290 @@ pub fn create_local_workspace(
     }
     fs::create_dir_all(workspaces_root)?;
 
-    run_git(
-        repo_path,
-        &[
-            "worktree",
-            "add",
-            worktree_path.to_string_lossy().as_ref(),
-            "-b",
-            &branch,
-            &resolved_base_ref,
-        ],
+    let operation = begin_operation(
+        workspaces_root,
+        Operation::AddWorktree {
+            repo_path: repo_root_path.clone(),
+            worktree_path: worktree_path.to_string_lossy().to_string(),
+            branch: branch.clone(),
+            creates_branch: !has_local_branch,
+        },
     )?;
+    let added = add_worktree(
+        repo_path,
+        &worktree_path,
+        &branch,
+        checkout_existing,
+        has_local_branch,
+        &resolved_base_ref,
+        &sparse_paths,
+        monitor,
+    );
+    let worktree_path = added.and_then(|_| canonicalize_path(&worktree_path));
+    let worktree_path = match worktree_path {
+        Ok(worktree_path) => worktree_path,
+        Err(err) => {
+            operation.roll_back();
+            return Err(err);
+        }
+    };
 
-    Ok(PreparedWorkspace {
-        repo_root_path,
-        worktree_path: canonicalize_path(&worktree_path)?,
-        branch,
-        base_ref: resolved_base_ref,
+    Ok(AddedWorktree {
+        prepared: PreparedWorkspace {
+            repo_root_path,
+            worktree_path,
+            branch,
+            base_ref: resolved_base_ref,
+            sparse_paths,
+        },
+        operation,
     })
 }
 
+#[allow(clippy::too_many_arguments)]
+fn add_worktree(
+    repo_path: &Path,
+    worktree_path: &Path,
+    branch: &str,
+    checkout_existing: bool,
+    has_local_branch: bool,
+    base_ref: &str,
+    sparse_paths: &[String],
+    monitor: GitMonitor<'_>,
+) -> Result<(), WorkspaceError> {
+    // The worktree is added without files and checked out separately, since
+    // `worktree add` has no `--progress` and any sparse checkout paths have
+    // to be set before files are written.
+    if checkout_existing {
+        checkout_existing_branch(repo_path, worktree_path, branch, has_local_branch)?;
+    } else {
+        let worktree = worktree_path.to_string_lossy();
+        run_git(
+            repo_path,
+            &[
+                "worktree",
+                "add",
+                "--no-checkout",
+                worktree.as_ref(),
+                "-b",
+                branch,
+                base_ref,
+            ],
+        )?;
+    }
+    if !sparse_paths.is_empty() {
+        set_sparse_paths(worktree_path, sparse_paths)?;
+    }
+    run_git_with_progress(
+        worktree_path,
+        &["checkout", "--progress", "--force"],
+        monitor,
+    )?;
+    Ok(())
+}
+
+/// Adds a worktree on an existing branch so pushes update that branch. A
+/// branch that only exists on `origin` is created locally and tracks it; a
+/// local branch without an upstream is pointed at `origin/<branch>` if present.
+/// A local branch behind `origin/<branch>` is fast-forwarded first, and one
+/// that has diverged from it is refused rather than checked out stale.
+fn checkout_existing_branch(
+    repo_path: &Path,
+    worktree_path: &Path,
+    branch: &str,
+    has_local_branch: bool,
+) -> Result<(), WorkspaceError> {
+    let remote_branch = format!("origin/{branch}");
+    let has_remote_branch = ref_exists(repo_path, &format!("refs/remotes/{remote_branch}"))?;
+    let worktree = worktree_path.to_string_lossy();
+    let mut add_args = vec!["worktree", "add", "--no-checkout"];
+
+    if has_local_branch {
+        if has_remote_branch {
+            fast_forward_to_remote(repo_path, branch, &remote_branch)?;
+        }
+        add_args.extend([worktree.as_ref(), branch]);
+        run_git(repo_path, &add_args)?;
+        let upstream_spec = format!("{branch}@{{upstream}}");
+        let has_upstream =
+            run_git(repo_path, &["rev-parse", "--abbrev-ref", &upstream_spec]).is_ok();
+        if !has_upstream && has_remote_branch {
+            run_git(
+                repo_path,
+                &["branch", "--set-upstream-to", &remote_branch, branch],
+            )?;
+        }
+        return Ok(());
+    }
+
+    if !has_remote_branch {
+        return Err(WorkspaceError::NotFound(format!(
+            "Branch not found locally or on origin: {branch}"
+        )));
+    }
+    add_args.extend(["--track", "-b", branch, worktree.as_ref(), &remote_branch]);
+    run_git(repo_path, &add_args).map(|_| ())
+}
+
+/// Moves `branch`, which is not checked out anywhere, up to `remote_branch`
+/// when it is strictly behind it.
+fn fast_forward_to_remote(
+    repo_path: &Path,
+    branch: &str,
+    remote_branch: &str,
+) -> Result<(), WorkspaceError> {
+    let range = format!("{branch}...{remote_branch}");
+    let counts = run_git(repo_path, &["rev-list", "--left-right", "--count", &range])?;
+    let (ahead, behind) = counts.split_once('\t').ok_or_else(|| {
+        WorkspaceError::GitFailed(format!("Unexpected rev-list output: {counts}"))
+    })?;
+    match (ahead.trim() != "0", behind.trim() != "0") {
+        (false, true) => {
+            let full_ref = format!("refs/heads/{branch}");
+            run_git(repo_path, &["update-ref", &full_ref, remote_branch]).map(|_| ())
+        }
+        (true, true) => Err(WorkspaceError::InvalidInput(format!(
+            "Branch {branch} has diverged from {remote_branch} ({ahead} local and {behind} \
+             remote commits); reconcile it before checking it out"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Trims and dedupes cone-mode include paths, which are directories relative
+/// to the repository root.
+pub fn normalize_sparse_paths(paths: &[String]) -> Result<Vec<String>, WorkspaceError> {
+    let mut normalized = Vec::with_capacity(paths.len());
+    for path in paths {
+        let trimmed = path
+            .trim()
+            .trim_start_matches("./")
+            .trim_matches('/')
+            .to_string();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed
+            .split('/')
+            .any(|segment| segment == ".." || segment == ".")
+        {
+            return Err(WorkspaceError::InvalidInput(format!(
+                "Sparse checkout paths must be directories inside the repository: {path}"
+            )));
+        }
+        normalized.push(trimmed);
+    }
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+/// Limits the worktree to `paths` (cone mode), or checks out everything again
+/// when `paths` is empty. Only this worktree's configuration is changed.
+pub fn set_sparse_paths(worktree_path: &Path, paths: &[String]) -> Result<(), WorkspaceError> {
+    if paths.is_empty() {
+        return run_git(worktree_path, &["sparse-checkout", "disable"]).map(|_| ());
+    }
+    let mut args = vec!["sparse-checkout", "set", "--cone", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    run_git(worktree_path, &args).map(|_| ())
+}
+
+/// Sparse checkout directories of an existing worktree; empty when the
+/// worktree is not sparse.
+pub fn read_sparse_paths(worktree_path: &Path) -> Vec<String> {
+    run_git(worktree_path, &["sparse-checkout", "list"])
+        .map(|output| output.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Fails when `branch` is already checked out by any worktree of the repo,
+/// since git allows a branch in only one worktree at a time.
+pub fn ensure_branch_not_checked_out(repo_path: &Path, branch: &str) -> Result<(), WorkspaceError> {
+    let checked_out = list_worktrees(repo_path)?
+        .into_iter()
+        .find(|entry| entry.branch.as_deref() == Some(branch));
+    match checked_out {
+        Some(entry) => Err(WorkspaceError::InvalidInput(format!(
+            "Branch {branch} is already checked out in {}",
+            entry.path.display()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// One entry of `git worktree list --porcelain`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitWorktreeEntry {
+    pub path: PathBuf,
+    pub head: Option<String>,
+    /// Short branch name, without the `refs/heads/` prefix.
+    pub branch: Option<String>,
+    pub bare: bool,
+    pub detached: bool,
+    pub prunable: bool,
+}
+
+/// Lists the worktrees of the repository containing `repo_path`. The main
+/// worktree is always the first entry.
+pub fn list_worktrees(repo_path: &Path) -> Result<Vec<GitWorktreeEntry>, WorkspaceError> {
+    let output = run_git(repo_path, &["worktree", "list", "--porcelain"])?;
+    Ok(parse_worktree_list(&output))
+}
+
+fn parse_worktree_list(output: &str) -> Vec<GitWorktreeEntry> {
+    let mut entries = Vec::new();
+    let mut current: Option<GitWorktreeEntry> = None;
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            entries.extend(current.take());
+            current = Some(GitWorktreeEntry {
+                path: PathBuf::from(path),
+                ..GitWorktreeEntry::default()
+            });
+            continue;
+        }
+        let Some(entry) = current.as_mut() else {
+            continue;
+        };
+        if let Some(head) = line.strip_prefix("HEAD ") {
+            entry.head = Some(head.to_string());
+        } else if let Some(branch) = line.strip_prefix("branch ") {
+            entry.branch = Some(branch.trim_start_matches("refs/heads/").to_string());
+        } else if line == "bare" {
+            entry.bare = true;
+        } else if line == "detached" {
+            entry.detached = true;
+        } else if line == "prunable" || line.starts_with("prunable ") {
+            entry.prunable = true;
+        }
+    }
+    entries.extend(current);
+    entries
+}
+
+/// Returns the URL of the `origin` remote, if the repository has one.
+pub fn origin_remote_url(repo_path: &Path) -> Option<String> {
+    run_git(repo_path, &["remote", "get-url", "origin"])
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
 fn verify_git_repo(repo_path: &Path) -> Result<(), WorkspaceError> {
     run_git(repo_path, &["rev-parse", "--is-inside-work-tree"]).map(|_| ())
 }
 
-fn branch_exists(repo_path: &Path, branch: &str) -> Result<bool, WorkspaceError> {
-    let output = Command::new("git")
+pub fn branch_exists(repo_path: &Path, branch: &str) -> Result<bool, WorkspaceError> {
+    ref_exists(repo_path, &format!("refs/heads/{branch}"))
+}
+
+fn ref_exists(repo_path: &Path, full_ref: &str) -> Result<bool, WorkspaceError> {
+    let mut command = Command::new("git");
+    command
         .arg("-C")
         .arg(repo_path)
-        .arg("show-ref")
-        .arg("--verify")
-        .arg("--quiet")
-        .arg(format!("refs/heads/{branch}"))
-        .output()?;
+        .args(["show-ref", "--verify", "--quiet", full_ref]);
+    let output = run_command(command, "git show-ref", RunLimits::timeout(LOCAL_TIMEOUT))?;
     if output.status.success() {
         return Ok(true);
     }
@@ -97,12 +393,31 @@ fn branch_exists(repo_path: &Path, branch: &str) -> Result<bool, WorkspaceError>
         return Ok(false);
     }
     Err(WorkspaceError::GitFailed(format!(
-        "Failed to check branch existence: {}",
+        "Failed to check ref existence: {}",
         String::from_utf8_lossy(&output.stderr)
     )))
 }
 
-fn detect_default_base_ref(repo_path: &Path) -> Result<String, WorkspaceError> {
+/// Best guess at the ref a branch was started from. A tracked upstream counts
+/// when it is a different branch (e.g. `origin/main` after
+/// `git worktree add -b feature ../wt origin/main`); otherwise the repository
+/// default is used.
+pub fn guess_base_ref(repo_path: &Path, branch: Option<&str>) -> Result<String, WorkspaceError> {
+    if let Some(branch) = branch {
+        let upstream_spec = format!("{branch}@{{upstream}}");
+        if let Ok(upstream) = run_git(repo_path, &["rev-parse", "--abbrev-ref", &upstream_spec]) {
+            let upstream_branch = upstream
+                .split_once('/')
+                .map_or(upstream.as_str(), |(_, rest)| rest);
+            if !upstream.is_empty() && upstream_branch != branch {
+                return Ok(upstream);
+            }
+        }
+    }
+    detect_default_base_ref(repo_path)
+}
+
+pub fn detect_default_base_ref(repo_path: &Path) -> Result<String, WorkspaceError> {
     if let Ok(remote_head) = run_git(repo_path, &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"]) {
         if !remote_head.trim().is_empty() {
             return Ok(remote_head);
@@ -117,15 +432,23 @@ fn detect_default_base_ref(repo_path: &Path) -> Result<String, WorkspaceError> {
     Ok("HEAD".to_string())
 }
 
-fn run_git(repo_path: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(repo_path)
-        .args(args)
-        .output()?;
+pub fn run_git(repo_path: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
+    run_git_untrimmed(repo_path, args).map(|output| output.trim().to_string())
+}
+
+/// Like `run_git`, but keeps stdout as-is. Needed for output where leading or
+/// trailing whitespace is significant, such as `-z` listings and patches.
+pub fn run_git_untrimmed(repo_path: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo_path).args(args);
+    let output = run_command(
+        command,
+        &format!("git {}", args.join(" ")),
+        RunLimits::timeout(git_timeout(args)),
+    )?;
 
     if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
     } else {
         Err(WorkspaceError::GitFailed(format!(
             "git {} failed: {}",
@@ -135,7 +458,7 @@ fn run_git(repo_path: &Path, args: &[&str]) -> Result<String, WorkspaceError> {
     }
 }
 
-fn canonicalize_path(path: &Path) -> Result<String, WorkspaceError> {
+pub fn canonicalize_path(path: &Path) -> Result<String, WorkspaceError> {
     Ok(path.canonicalize()?.to_string_lossy().to_string())
 }
 
@@ -143,11 +466,18 @@ fn canonicalize_path(path: &Path) -> Result<String, WorkspaceError> {
 mod tests {
     use std::fs;
     use std::path::Path;
-    use std::process::Command;
 
     use tempfile::TempDir;
 
-    use super::create_local_workspace;
+    use super::{
+        create_local_workspace, list_worktrees, parse_worktree_list, read_sparse_paths,
+        set_sparse_paths,
+    };
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::WorkspaceBranch;
+    use crate::workspaces::operations::recover_interrupted_operations;
+    use crate::workspaces::WorkspaceStore;
+    use crate::workspaces::test_support::git;
 
     struct LocalRepoFixture {
         _tmpdir: TempDir,
@@ -160,14 +490,11 @@ mod tests {
             let repo_path = tmpdir.path().join("repo");
             fs::create_dir_all(&repo_path).unwrap();
 
-            run_git_raw(
-                &repo_path,
-                &["init"],
-            );
-            run_git_raw(&repo_path, &["checkout", "-B", "main"]);
+            git(&repo_path, &["init"]);
+            git(&repo_path, &["checkout", "-B", "main"]);
             fs::write(repo_path.join("README.md"), "# fixture\n").unwrap();
-            run_git_raw(&repo_path, &["add", "."]);
-            run_git_with_identity(&repo_path, &["commit", "-m", "initial"]);
+            git(&repo_path, &["add", "."]);
+            git(&repo_path, &["commit", "-m", "initial"]);
 
             Self {
                 _tmpdir: tmpdir,
@@ -176,46 +503,342 @@ mod tests {
         }
     }
 
-    fn run_git_raw(repo_path: &Path, args: &[&str]) {
-        let status = Command::new("git")
-            .arg("-C")
-            .arg(repo_path)
-            .args(args)
-            .status()
-            .unwrap();
-        assert!(status.success(), "git {:?} failed", args);
+    #[test]
+    fn creates_local_workspace_in_separate_worktree_path() {
+        let fixture = LocalRepoFixture::new();
+        let workspaces_root = fixture.repo_path.join("workspaces");
+        let created = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(None),
+            None,
+            &[],
+            "ab12",
+            &workspaces_root,
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        assert!(created.worktree_path.contains("workspaces"));
+        assert_ne!(created.worktree_path, created.repo_root_path);
     }
 
-    fn run_git_with_identity(repo_path: &Path, args: &[&str]) {
-        let status = Command::new("git")
-            .arg("-C")
-            .arg(repo_path)
-            .arg("-c")
-            .arg("user.name=Kata Test")
-            .arg("-c")
-            .arg("user.email=kata@example.com")
-            .args(args)
-            .status()
-            .unwrap();
-        assert!(status.success(), "git {:?} failed", args);
+    #[test]
+    fn worktree_added_but_never_recorded_is_rolled_back_at_startup() {
+        let fixture = LocalRepoFixture::new();
+        let created = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(None),
+            None,
+            &[],
+            "ab12",
+            &fixture.repo_path.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        // Quit before the workspace made it into the registry.
+        let store = WorkspaceStore::new(&fixture.repo_path);
+        assert!(recover_interrupted_operations(&fixture.repo_path, &store).is_some());
+
+        assert!(!Path::new(&created.worktree_path).exists());
+        assert_eq!(list_worktrees(&fixture.repo_path).unwrap().len(), 1);
     }
 
     #[test]
-    fn creates_local_workspace_in_separate_worktree_path() {
+    fn checks_out_only_sparse_paths_and_widens_later() {
+        let fixture = LocalRepoFixture::new();
+        for dir in ["apps/web", "apps/api", "libs/ui"] {
+            fs::create_dir_all(fixture.repo_path.join(dir)).unwrap();
+            fs::write(fixture.repo_path.join(dir).join("index.ts"), "x\n").unwrap();
+        }
+        git(&fixture.repo_path, &["add", "."]);
+        git(&fixture.repo_path, &["commit", "-m", "monorepo"]);
+
+        let created = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(None),
+            None,
+            &["./apps/web/".to_string(), "apps/web".to_string()],
+            "ab12",
+            &fixture.repo_path.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        let worktree = Path::new(&created.worktree_path);
+        assert_eq!(created.sparse_paths, vec!["apps/web".to_string()]);
+        assert!(worktree.join("README.md").exists());
+        assert!(worktree.join("apps/web/index.ts").exists());
+        assert!(!worktree.join("apps/api").exists());
+        assert!(!worktree.join("libs").exists());
+        assert_eq!(git(worktree, &["status", "--porcelain"]), "");
+        assert_eq!(read_sparse_paths(worktree), vec!["apps/web".to_string()]);
+
+        set_sparse_paths(worktree, &["apps/web".to_string(), "libs/ui".to_string()]).unwrap();
+        assert!(worktree.join("libs/ui/index.ts").exists());
+        assert!(!worktree.join("apps/api").exists());
+
+        set_sparse_paths(worktree, &[]).unwrap();
+        assert!(worktree.join("apps/api/index.ts").exists());
+        assert!(read_sparse_paths(worktree).is_empty());
+    }
+
+    #[test]
+    fn rejects_sparse_paths_outside_the_repository() {
+        let fixture = LocalRepoFixture::new();
+        let err = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(None),
+            None,
+            &["../elsewhere".to_string()],
+            "ab12",
+            &fixture.repo_path.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Sparse checkout"));
+    }
+
+    #[test]
+    fn parses_porcelain_worktree_list() {
+        let output = "worktree /repo\nHEAD 1111\nbranch refs/heads/main\n\n\
+                      worktree /ws/a\nHEAD 2222\ndetached\nprunable gitdir file points to non-existent location\n\n\
+                      worktree /ws/b\nHEAD 3333\nbranch refs/heads/workspace/b-1234\n";
+        let entries = parse_worktree_list(output);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].branch.as_deref(), Some("main"));
+        assert!(entries[1].detached && entries[1].prunable);
+        assert_eq!(entries[1].branch, None);
+        assert_eq!(entries[2].branch.as_deref(), Some("workspace/b-1234"));
+        assert_eq!(entries[2].head.as_deref(), Some("3333"));
+    }
+
+    #[test]
+    fn lists_created_worktree_after_main_worktree() {
         let fixture = LocalRepoFixture::new();
         let workspaces_root = fixture.repo_path.join("workspaces");
         let created = create_local_workspace(
             &fixture.repo_path,
             "KAT-154",
+            WorkspaceBranch::New(None),
             None,
+            &[],
+            "ab12",
+            &workspaces_root,
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        let entries = list_worktrees(&fixture.repo_path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].branch.as_deref(), Some("main"));
+        assert_eq!(
+            entries[1].path.canonicalize().unwrap(),
+            Path::new(&created.worktree_path)
+        );
+        assert_eq!(entries[1].branch.as_deref(), Some(created.branch.as_str()));
+    }
+
+    #[test]
+    fn rejects_existing_branch_unless_checking_it_out() {
+        let fixture = LocalRepoFixture::new();
+        git(&fixture.repo_path, &["branch", "feature/x"]);
+        let workspaces_root = fixture.repo_path.join("workspaces");
+
+        let err = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(Some("feature/x".into())),
             None,
+            &[],
             "ab12",
             &workspaces_root,
+            GitMonitor::default(),
         )
-        .unwrap();
+        .unwrap_err();
+        assert!(err.to_string().contains("already exists"));
 
-        assert!(created.worktree_path.contains("workspaces"));
-        assert_ne!(created.worktree_path, created.repo_root_path);
+        let created = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::Existing("feature/x".into()),
+            None,
+            &[],
+            "ab12",
+            &workspaces_root,
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+        assert_eq!(created.branch, "feature/x");
+        assert_eq!(
+            git(
+                Path::new(&created.worktree_path),
+                &["branch", "--show-current"]
+            ),
+            "feature/x"
+        );
+    }
+
+    #[test]
+    fn refuses_to_check_out_branch_used_by_another_worktree() {
+        let fixture = LocalRepoFixture::new();
+        let workspaces_root = fixture.repo_path.join("workspaces");
+        let first = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(None),
+            None,
+            &[],
+            "ab12",
+            &workspaces_root,
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        let err = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154 again",
+            WorkspaceBranch::Existing(first.branch.clone()),
+            None,
+            &[],
+            "cd34",
+            &workspaces_root,
+            GitMonitor::default(),
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("already checked out"));
+    }
+
+    #[test]
+    fn checks_out_remote_only_branch_tracking_origin() {
+        let fixture = LocalRepoFixture::new();
+        git(&fixture.repo_path, &["branch", "feature/remote"]);
+        let clone_path = fixture.repo_path.parent().unwrap().join("clone");
+        git(
+            fixture.repo_path.parent().unwrap(),
+            &[
+                "clone",
+                fixture.repo_path.to_str().unwrap(),
+                clone_path.to_str().unwrap(),
+            ],
+        );
+
+        let created = create_local_workspace(
+            &clone_path,
+            "KAT-154",
+            WorkspaceBranch::Existing("origin/feature/remote".into()),
+            None,
+            &[],
+            "ab12",
+            &clone_path.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+
+        assert_eq!(created.branch, "feature/remote");
+        assert_eq!(
+            git(
+                Path::new(&created.worktree_path),
+                &["rev-parse", "--abbrev-ref", "@{upstream}"]
+            ),
+            "origin/feature/remote"
+        );
+
+        let err = create_local_workspace(
+            &clone_path,
+            "KAT-155",
+            WorkspaceBranch::Existing("feature/missing".into()),
+            None,
+            &[],
+            "cd34",
+            &clone_path.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn fast_forwards_local_branch_behind_origin_and_refuses_diverged_one() {
+        let fixture = LocalRepoFixture::new();
+        git(&fixture.repo_path, &["branch", "feature/behind"]);
+        git(&fixture.repo_path, &["branch", "feature/diverged"]);
+        let clone_path = fixture.repo_path.parent().unwrap().join("clone");
+        git(
+            fixture.repo_path.parent().unwrap(),
+            &[
+                "clone",
+                fixture.repo_path.to_str().unwrap(),
+                clone_path.to_str().unwrap(),
+            ],
+        );
+        git(
+            &clone_path,
+            &["branch", "feature/behind", "origin/feature/behind"],
+        );
+        git(
+            &clone_path,
+            &["branch", "feature/diverged", "origin/feature/diverged"],
+        );
+        for branch in ["feature/behind", "feature/diverged"] {
+            git(&fixture.repo_path, &["checkout", branch]);
+            git(
+                &fixture.repo_path,
+                &["commit", "--allow-empty", "-m", "upstream work"],
+            );
+        }
+        git(&fixture.repo_path, &["checkout", "main"]);
+        git(&clone_path, &["fetch", "origin"]);
+        git(&clone_path, &["checkout", "feature/diverged"]);
+        git(
+            &clone_path,
+            &["commit", "--allow-empty", "-m", "local work"],
+        );
+        git(&clone_path, &["checkout", "main"]);
+
+        let created = create_local_workspace(
+            &clone_path,
+            "KAT-154",
+            WorkspaceBranch::Existing("feature/behind".into()),
+            None,
+            &[],
+            "ab12",
+            &clone_path.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+        assert_eq!(
+            git(Path::new(&created.worktree_path), &["rev-parse", "HEAD"]),
+            git(&clone_path, &["rev-parse", "origin/feature/behind"])
+        );
+
+        let err = create_local_workspace(
+            &clone_path,
+            "KAT-155",
+            WorkspaceBranch::Existing("feature/diverged".into()),
+            None,
+            &[],
+            "cd34",
+            &clone_path.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("diverged"), "{err}");
+        assert!(!clone_path.join("workspaces").join("kat-155-cd34").exists());
     }
 
     #[test]
@@ -225,10 +848,12 @@ mod tests {
         let err = create_local_workspace(
             &fixture.repo_path,
             "KAT-154",
-            Some("main".into()),
+            WorkspaceBranch::New(Some("main".into())),
             None,
+            &[],
             "ab12",
             &workspaces_root,
+            GitMonitor::default(),
         )
         .unwrap_err();
 
@@ -0,0 +1,254 @@
+use std::path::Path;
+use std::process::Command;
+
+use serde::Serialize;
+
+use super::process::{git_timeout, run_command_with, CancelToken, RunLimits};
+use super::WorkspaceError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitProgressPhase {
+    Enumerating,
+    Counting,
+    Compressing,
+    Receiving,
+    Resolving,
+    CheckingOut,
+    /// Another operation holds the repository; see `RepoLocks`.
+    WaitingForRepository,
+    Other,
+}
+
+/// One progress line of `git clone/fetch/checkout --progress`, such as
+/// `Receiving objects:  42% (420/1000), 1.20 MiB | 2.40 MiB/s`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitProgress {
+    pub phase: GitProgressPhase,
+    /// The phase as printed by git, e.g. `Receiving objects`.
+    pub title: String,
+    pub percent: Option<u8>,
+    pub current: u64,
+    /// Missing for phases git cannot size up front, like enumerating.
+    pub total: Option<u64>,
+    pub transferred_bytes: Option<u64>,
+    pub bytes_per_second: Option<u64>,
+    pub done: bool,
+}
+
+/// Payload of the channel passed to `workspace_watch_progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitProgressEvent {
+    pub workspace_id: String,
+    pub job_id: String,
+    pub progress: GitProgress,
+}
+
+/// Parses a progress line, with or without the `remote: ` prefix git puts
+/// on the server side phases. Returns `None` for any other output.
+pub fn parse_progress_line(line: &str) -> Option<GitProgress> {
+    let line = line.trim();
+    let line = line.strip_prefix("remote:").unwrap_or(line).trim();
+    let (title, rest) = line.split_once(": ")?;
+    let (rest, done) = match rest.trim().strip_suffix(", done.") {
+        Some(rest) => (rest, true),
+        None => (rest.trim(), false),
+    };
+    let (counts, transfer) = match rest.split_once(", ") {
+        Some((counts, transfer)) => (counts, Some(transfer)),
+        None => (rest, None),
+    };
+
+    let (percent, current, total) = match counts.split_once('%') {
+        Some((percent, fraction)) => {
+            let (current, total) = fraction
+                .trim()
+                .strip_prefix('(')?
+                .strip_suffix(')')?
+                .split_once('/')?;
+            (
+                Some(percent.trim().parse().ok()?),
+                current.parse().ok()?,
+                Some(total.parse().ok()?),
+            )
+        }
+        None => (None, counts.trim().parse().ok()?, None),
+    };
+    let (transferred_bytes, bytes_per_second) = match transfer.and_then(|t| t.split_once(" | ")) {
+        Some((transferred, rate)) => (
+            parse_size(transferred),
+            rate.strip_suffix("/s").and_then(parse_size),
+        ),
+        None => (None, None),
+    };
+
+    Some(GitProgress {
+        phase: phase_from_title(title),
+        title: title.to_string(),
+        percent,
+        current,
+        total,
+        transferred_bytes,
+        bytes_per_second,
+        done,
+    })
+}
+
+fn phase_from_title(title: &str) -> GitProgressPhase {
+    match title {
+        "Enumerating objects" => GitProgressPhase::Enumerating,
+        "Counting objects" => GitProgressPhase::Counting,
+        "Compressing objects" => GitProgressPhase::Compressing,
+        "Receiving objects" => GitProgressPhase::Receiving,
+        "Resolving deltas" => GitProgressPhase::Resolving,
+        "Updating files" | "Checking out files" => GitProgressPhase::CheckingOut,
+        _ => GitProgressPhase::Other,
+    }
+}
+
+/// Parses git's humanised sizes: `512 bytes`, `1.20 MiB`, and so on.
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, unit) = value.trim().split_once(' ')?;
+    let scale = match unit {
+        "byte" | "bytes" => 1u64,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+    let number = number.parse::<f64>().ok()?;
+    Some((number * scale as f64).round() as u64)
+}
+
+/// Where a long-running git command reports progress, and what cancels it.
+#[derive(Clone, Copy, Default)]
+pub struct GitMonitor<'a> {
+    pub on_progress: Option<&'a dyn Fn(GitProgress)>,
+    pub cancel: Option<&'a CancelToken>,
+}
+
+/// Runs git in `cwd`, reporting its progress lines as they arrive. Callers
+/// pass `--progress`, since git only reports progress to a terminal
+/// otherwise. The rest of stderr makes up the error message on failure.
+pub fn run_git_with_progress(
+    cwd: &Path,
+    args: &[&str],
+    monitor: GitMonitor<'_>,
+) -> Result<String, WorkspaceError> {
+    let mut command = Command::new("git");
+    command.current_dir(cwd).args(args);
+    let description = format!("git {}", args.join(" "));
+    let limits = RunLimits {
+        cancel: monitor.cancel,
+        ..RunLimits::timeout(git_timeout(args))
+    };
+
+    let mut messages = Vec::new();
+    let mut on_line = |line: &str| match parse_progress_line(line) {
+        Some(progress) => {
+            if let Some(on_progress) = monitor.on_progress {
+                on_progress(progress);
+            }
+        }
+        None if !line.trim().is_empty() => messages.push(line.trim().to_string()),
+        None => {}
+    };
+    let output = run_command_with(command, &description, limits, None, &mut on_line)?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(WorkspaceError::GitFailed(format!(
+            "{description} failed: {}",
+            messages.join("\n")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use tempfile::tempdir;
+
+    use super::{parse_progress_line, run_git_with_progress, GitMonitor, GitProgressPhase};
+    use crate::workspaces::test_support::init_repo;
+    use crate::workspaces::WorkspaceError;
+
+    #[test]
+    fn parses_remote_local_and_checkout_progress_lines() {
+        let counting = parse_progress_line("remote: Counting objects:  45% (9/20)").unwrap();
+        assert_eq!(counting.phase, GitProgressPhase::Counting);
+        assert_eq!(
+            (counting.percent, counting.current, counting.total),
+            (Some(45), 9, Some(20))
+        );
+        assert!(!counting.done);
+
+        let enumerating = parse_progress_line("remote: Enumerating objects: 123, done.").unwrap();
+        assert_eq!(enumerating.phase, GitProgressPhase::Enumerating);
+        assert_eq!((enumerating.current, enumerating.total), (123, None));
+        assert!(enumerating.done);
+
+        let receiving = parse_progress_line(
+            "Receiving objects: 100% (1000/1000), 1.50 MiB | 512.00 KiB/s, done.",
+        )
+        .unwrap();
+        assert_eq!(receiving.phase, GitProgressPhase::Receiving);
+        assert_eq!(receiving.transferred_bytes, Some(1_572_864));
+        assert_eq!(receiving.bytes_per_second, Some(524_288));
+        assert!(receiving.done);
+
+        let checkout = parse_progress_line("Updating files:  50% (150/300)").unwrap();
+        assert_eq!(checkout.phase, GitProgressPhase::CheckingOut);
+        assert_eq!(checkout.percent, Some(50));
+    }
+
+    #[test]
+    fn ignores_output_that_is_not_progress() {
+        for line in [
+            "Cloning into 'widgets'...",
+            "remote: Total 5 (delta 0), reused 0 (delta 0), pack-reused 0",
+            "fatal: repository 'https://github.com/acme/missing/' not found",
+            "",
+        ] {
+            assert_eq!(parse_progress_line(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn reports_clone_progress_and_keeps_other_output_for_errors() {
+        let dir = tempdir().unwrap();
+        let origin = dir.path().join("origin");
+        init_repo(&origin);
+        let url = format!("file://{}", origin.display());
+        let updates = RefCell::new(Vec::new());
+
+        run_git_with_progress(
+            dir.path(),
+            &["clone", "--progress", &url, "clone"],
+            GitMonitor {
+                on_progress: Some(&|progress| updates.borrow_mut().push(progress)),
+                cancel: None,
+            },
+        )
+        .unwrap();
+        let err = run_git_with_progress(
+            dir.path(),
+            &["clone", "--progress", "file:///nonexistent/repo", "missing"],
+            GitMonitor::default(),
+        )
+        .unwrap_err();
+
+        let updates = updates.into_inner();
+        assert!(updates
+            .iter()
+            .any(|progress| progress.phase == GitProgressPhase::Receiving && progress.done));
+        assert!(dir.path().join("clone").join(".git").exists());
+        assert!(matches!(err, WorkspaceError::GitFailed(message)
+            if message.contains("does not appear to be a git repository")));
+    }
+}
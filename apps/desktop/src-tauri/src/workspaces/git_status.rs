@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use super::git_local::{run_git, run_git_untrimmed};
+use super::model::{now_iso8601, Workspace};
+use super::WorkspaceError;
+
+/// How long a computed status is served from the cache before git is asked
+/// again.
+pub const STATUS_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Upper bound on worktrees inspected in parallel by `read_workspace_statuses`.
+const STATUS_PARALLELISM: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+    Unmerged,
+    Untracked,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileStatus {
+    pub path: String,
+    /// Previous path of a rename or copy.
+    pub original_path: Option<String>,
+    pub kind: FileChangeKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AheadBehind {
+    pub ahead: u32,
+    pub behind: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitSummary {
+    pub sha: String,
+    pub short_sha: String,
+    pub subject: String,
+    pub author_name: String,
+    pub authored_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InProgressOperation {
+    Rebase,
+    Merge,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorktreeStatus {
+    /// Checked-out branch; `None` when HEAD is detached and no rebase names
+    /// the branch being rebuilt.
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    pub staged: Vec<FileStatus>,
+    pub unstaged: Vec<FileStatus>,
+    pub untracked: Vec<FileStatus>,
+    pub conflicted: Vec<FileStatus>,
+    /// Commits ahead of / behind the upstream branch, if one is configured.
+    pub upstream_divergence: Option<AheadBehind>,
+    /// Commits ahead of / behind the workspace's `base_ref`, if it resolves.
+    pub base_divergence: Option<AheadBehind>,
+    /// `None` on an unborn branch.
+    pub head: Option<CommitSummary>,
+    pub in_progress: Option<InProgressOperation>,
+    pub computed_at: String,
+}
+
+/// Status of one workspace in a batch. Exactly one of `status` and `error`
+/// is set, so a single broken worktree does not fail the whole batch.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceStatusEntry {
+    pub workspace_id: String,
+    pub status: Option<WorktreeStatus>,
+    pub error: Option<String>,
+}
+
+impl WorkspaceStatusEntry {
+    pub fn from_result(
+        workspace_id: String,
+        result: Result<WorktreeStatus, WorkspaceError>,
+    ) -> Self {
+        match result {
+            Ok(status) => Self {
+                workspace_id,
+                status: Some(status),
+                error: None,
+            },
+            Err(err) => Self {
+                workspace_id,
+                status: None,
+                error: Some(err.to_string()),
+            },
+        }
+    }
+}
+
+/// Worktree statuses keyed by workspace id. Entries expire after the TTL and
+/// are dropped explicitly whenever the app itself changes a worktree.
+#[derive(Debug)]
+pub struct StatusCache {
+    ttl: Duration,
+    entries: HashMap<String, (Instant, WorktreeStatus)>,
+}
+
+impl StatusCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, workspace_id: &str) -> Option<WorktreeStatus> {
+        self.entries
+            .get(workspace_id)
+            .filter(|(computed, _)| computed.elapsed() < self.ttl)
+            .map(|(_, status)| status.clone())
+    }
+
+    pub fn insert(&mut self, workspace_id: String, status: WorktreeStatus) {
+        self.entries.insert(workspace_id, (Instant::now(), status));
+    }
+
+    pub fn invalidate(&mut self, workspace_id: &str) {
+        self.entries.remove(workspace_id);
+    }
+}
+
+impl Default for StatusCache {
+    fn default() -> Self {
+        Self::new(STATUS_CACHE_TTL)
+    }
+}
+
+/// Reads the status of several workspaces, a bounded number at a time.
+/// Results are returned in the order of `workspaces`.
+pub fn read_workspace_statuses(
+    workspaces: &[Workspace],
+) -> Vec<Result<WorktreeStatus, WorkspaceError>> {
+    let mut results = Vec::with_capacity(workspaces.len());
+    for chunk in workspaces.chunks(STATUS_PARALLELISM) {
+        thread::scope(|scope| {
+            let handles = chunk
+                .iter()
+                .map(|workspace| {
+                    scope.spawn(|| {
+                        read_worktree_status(
+                            Path::new(&workspace.worktree_path),
+                            workspace.base_ref.as_deref(),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                results.push(handle.join().unwrap_or_else(|_| {
+                    Err(WorkspaceError::GitFailed(
+                        "Status task panicked".to_string(),
+                    ))
+                }));
+            }
+        });
+    }
+    results
+}
+
+pub fn read_worktree_status(
+    worktree_path: &Path,
+    base_ref: Option<&str>,
+) -> Result<WorktreeStatus, WorkspaceError> {
+    if !worktree_path.exists() {
+        return Err(WorkspaceError::NotFound(format!(
+            "Worktree path does not exist: {}",
+            worktree_path.display()
+        )));
+    }
+
+    let porcelain = run_git_untrimmed(
+        worktree_path,
+        &["status", "--porcelain=v2", "--branch", "-z"],
+    )?;
+    let parsed = parse_porcelain_v2(&porcelain);
+
+    let head = if parsed.head_oid.is_some() {
+        read_head_summary(worktree_path)?
+    } else {
+        None
+    };
+    let base_divergence = match base_ref.filter(|value| !value.trim().is_empty()) {
+        Some(base_ref) if parsed.head_oid.is_some() => {
+            count_ahead_behind(worktree_path, "HEAD", base_ref)
+        }
+        _ => None,
+    };
+
+    let git_dir = run_git(worktree_path, &["rev-parse", "--absolute-git-dir"])?;
+    let git_dir = Path::new(&git_dir);
+    let in_progress = detect_in_progress(git_dir);
+    let branch = parsed.branch.or_else(|| {
+        (in_progress == Some(InProgressOperation::Rebase))
+            .then(|| rebasing_branch(git_dir))
+            .flatten()
+    });
+
+    Ok(WorktreeStatus {
+        branch,
+        upstream: parsed.upstream,
+        staged: parsed.staged,
+        unstaged: parsed.unstaged,
+        untracked: parsed.untracked,
+        conflicted: parsed.conflicted,
+        upstream_divergence: parsed.upstream_divergence,
+        base_divergence,
+        head,
+        in_progress,
+        computed_at: now_iso8601(),
+    })
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PorcelainStatus {
+    head_oid: Option<String>,
+    branch: Option<String>,
+    upstream: Option<String>,
+    upstream_divergence: Option<AheadBehind>,
+    staged: Vec<FileStatus>,
+    unstaged: Vec<FileStatus>,
+    untracked: Vec<FileStatus>,
+    conflicted: Vec<FileStatus>,
+}
+
+/// Parses `git status --porcelain=v2 --branch -z`. Renames and copies carry
+/// their original path in the following NUL-separated field.
+fn parse_porcelain_v2(output: &str) -> PorcelainStatus {
+    let mut status = PorcelainStatus::default();
+    let mut records = output.split('\0').filter(|record| !record.is_empty());
+
+    while let Some(record) = records.next() {
+        if let Some(header) = record.strip_prefix("# ") {
+            parse_branch_header(header, &mut status);
+            continue;
+        }
+
+        match record.as_bytes()[0] {
+            b'1' => {
+                let fields = record.splitn(9, ' ').collect::<Vec<_>>();
+                if let [_, xy, _, _, _, _, _, _, path] = fields.as_slice() {
+                    push_changes(&mut status, xy, path, None);
+                }
+            }
+            b'2' => {
+                let fields = record.splitn(10, ' ').collect::<Vec<_>>();
+                let original_path = records.next();
+                if let [_, xy, _, _, _, _, _, _, _, path] = fields.as_slice() {
+                    push_changes(&mut status, xy, path, original_path);
+                }
+            }
+            b'u' => {
+                if let Some(path) = record.splitn(11, ' ').nth(10) {
+                    status.conflicted.push(FileStatus {
+                        path: path.to_string(),
+                        original_path: None,
+                        kind: FileChangeKind::Unmerged,
+                    });
+                }
+            }
+            b'?' => status.untracked.push(FileStatus {
+                path: record[2..].to_string(),
+                original_path: None,
+                kind: FileChangeKind::Untracked,
+            }),
+            _ => {}
+        }
+    }
+    status
+}
+
+fn parse_branch_header(header: &str, status: &mut PorcelainStatus) {
+    let Some((key, value)) = header.split_once(' ') else {
+        return;
+    };
+    match key {
+        "branch.oid" if value != "(initial)" => status.head_oid = Some(value.to_string()),
+        "branch.head" if value != "(detached)" => status.branch = Some(value.to_string()),
+        "branch.upstream" => status.upstream = Some(value.to_string()),
+        "branch.ab" => {
+            let mut counts = value
+                .split(' ')
+                .map(|count| count.trim_start_matches(['+', '-']).parse::<u32>());
+            if let (Some(Ok(ahead)), Some(Ok(behind))) = (counts.next(), counts.next()) {
+                status.upstream_divergence = Some(AheadBehind { ahead, behind });
+            }
+        }
+        _ => {}
+    }
+}
+
+fn push_changes(status: &mut PorcelainStatus, xy: &str, path: &str, original_path: Option<&str>) {
+    let mut codes = xy.chars();
+    let (Some(index_code), Some(worktree_code)) = (codes.next(), codes.next()) else {
+        return;
+    };
+    let file = |kind| FileStatus {
+        path: path.to_string(),
+        original_path: original_path.map(str::to_string),
+        kind,
+    };
+    if let Some(kind) = change_kind(index_code) {
+        status.staged.push(file(kind));
+    }
+    if let Some(kind) = change_kind(worktree_code) {
+        status.unstaged.push(file(kind));
+    }
+}
+
+fn change_kind(code: char) -> Option<FileChangeKind> {
+    match code {
+        'A' => Some(FileChangeKind::Added),
+        'M' => Some(FileChangeKind::Modified),
+        'D' => Some(FileChangeKind::Deleted),
+        'R' => Some(FileChangeKind::Renamed),
+        'C' => Some(FileChangeKind::Copied),
+        'T' => Some(FileChangeKind::TypeChanged),
+        _ => None,
+    }
+}
+
+pub fn read_head_summary(worktree_path: &Path) -> Result<Option<CommitSummary>, WorkspaceError> {
+    let output = run_git(
+        worktree_path,
+        &["log", "-1", "--format=%H%x1f%h%x1f%s%x1f%an%x1f%aI"],
+    )?;
+    let fields = output.split('\u{1f}').collect::<Vec<_>>();
+    Ok(match fields.as_slice() {
+        [sha, short_sha, subject, author_name, authored_at] => Some(CommitSummary {
+            sha: sha.to_string(),
+            short_sha: short_sha.to_string(),
+            subject: subject.to_string(),
+            author_name: author_name.to_string(),
+            authored_at: authored_at.to_string(),
+        }),
+        _ => None,
+    })
+}
+
+/// Counts commits reachable only from `left` (ahead) and only from `right`
+/// (behind). Returns `None` if either ref does not resolve.
+pub fn count_ahead_behind(repo_path: &Path, left: &str, right: &str) -> Option<AheadBehind> {
+    let range = format!("{left}...{right}");
+    let output = run_git(
+        repo_path,
+        &["rev-list", "--left-right", "--count", &range, "--"],
+    )
+    .ok()?;
+    let (ahead, behind) = output.split_once(char::is_whitespace)?;
+    Some(AheadBehind {
+        ahead: ahead.trim().parse().ok()?,
+        behind: behind.trim().parse().ok()?,
+    })
+}
+
+/// Detects an interrupted operation from the marker files git leaves in the
+/// worktree's git dir.
+pub fn detect_in_progress(git_dir: &Path) -> Option<InProgressOperation> {
+    if git_dir.join("rebase-merge").is_dir() || git_dir.join("rebase-apply").is_dir() {
+        Some(InProgressOperation::Rebase)
+    } else if git_dir.join("MERGE_HEAD").is_file() {
+        Some(InProgressOperation::Merge)
+    } else if git_dir.join("CHERRY_PICK_HEAD").is_file() {
+        Some(InProgressOperation::CherryPick)
+    } else if git_dir.join("REVERT_HEAD").is_file() {
+        Some(InProgressOperation::Revert)
+    } else if git_dir.join("BISECT_LOG").is_file() {
+        Some(InProgressOperation::Bisect)
+    } else {
+        None
+    }
+}
+
+fn rebasing_branch(git_dir: &Path) -> Option<String> {
+    ["rebase-merge", "rebase-apply"].iter().find_map(|dir| {
+        let head_name = std::fs::read_to_string(git_dir.join(dir).join("head-name")).ok()?;
+        head_name
+            .trim()
+            .strip_prefix("refs/heads/")
+            .map(str::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::{
+        parse_porcelain_v2, read_worktree_status, AheadBehind, FileChangeKind, InProgressOperation,
+    };
+    use crate::workspaces::test_support::{git, git_succeeds, init_repo};
+
+    /// A repository on `main` with `notes.txt` committed.
+    fn notes_repo(repo_path: &Path) {
+        init_repo(repo_path);
+        fs::write(repo_path.join("notes.txt"), "one\n").unwrap();
+        git(repo_path, &["add", "."]);
+        git(repo_path, &["commit", "-m", "add notes"]);
+    }
+
+    #[test]
+    fn parses_porcelain_v2_records() {
+        let output = [
+            "# branch.oid 1111111111111111111111111111111111111111",
+            "# branch.head feature/x",
+            "# branch.upstream origin/feature/x",
+            "# branch.ab +2 -1",
+            "1 M. N... 100644 100644 100644 aaaa bbbb src/with space.rs",
+            "1 .D N... 100644 100644 000000 aaaa aaaa gone.txt",
+            "2 R. N... 100644 100644 100644 aaaa aaaa R100 new.rs",
+            "old.rs",
+            "u UU N... 100644 100644 100644 100644 aaaa bbbb cccc both.rs",
+            "? scratch.txt",
+            "",
+        ]
+        .join("\0");
+
+        let status = parse_porcelain_v2(&output);
+
+        assert_eq!(status.branch.as_deref(), Some("feature/x"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/feature/x"));
+        assert_eq!(
+            status.upstream_divergence,
+            Some(AheadBehind {
+                ahead: 2,
+                behind: 1
+            })
+        );
+        assert_eq!(status.staged.len(), 2);
+        assert_eq!(status.staged[0].path, "src/with space.rs");
+        assert_eq!(status.staged[1].kind, FileChangeKind::Renamed);
+        assert_eq!(status.staged[1].original_path.as_deref(), Some("old.rs"));
+        assert_eq!(status.unstaged[0].kind, FileChangeKind::Deleted);
+        assert_eq!(status.conflicted[0].path, "both.rs");
+        assert_eq!(status.untracked[0].path, "scratch.txt");
+    }
+
+    #[test]
+    fn reports_changes_divergence_and_head() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        notes_repo(&repo_path);
+        git(&repo_path, &["checkout", "-b", "feature/x"]);
+        fs::write(repo_path.join("feature.txt"), "feature\n").unwrap();
+        git(&repo_path, &["add", "."]);
+        git(&repo_path, &["commit", "-m", "add feature"]);
+        fs::write(repo_path.join("notes.txt"), "two\n").unwrap();
+        fs::write(repo_path.join("staged.txt"), "staged\n").unwrap();
+        git(&repo_path, &["add", "staged.txt"]);
+        fs::write(repo_path.join("scratch.txt"), "scratch\n").unwrap();
+
+        let status = read_worktree_status(&repo_path, Some("main")).unwrap();
+
+        assert_eq!(status.branch.as_deref(), Some("feature/x"));
+        assert_eq!(status.staged[0].path, "staged.txt");
+        assert_eq!(status.staged[0].kind, FileChangeKind::Added);
+        assert_eq!(status.unstaged[0].path, "notes.txt");
+        assert_eq!(status.untracked[0].path, "scratch.txt");
+        assert_eq!(
+            status.base_divergence,
+            Some(AheadBehind {
+                ahead: 1,
+                behind: 0
+            })
+        );
+        assert_eq!(status.upstream_divergence, None);
+        assert_eq!(status.head.unwrap().subject, "add feature");
+        assert_eq!(status.in_progress, None);
+    }
+
+    #[test]
+    fn detects_merge_in_progress() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        notes_repo(&repo_path);
+        git(&repo_path, &["checkout", "-b", "feature/x"]);
+        fs::write(repo_path.join("notes.txt"), "feature\n").unwrap();
+        git(&repo_path, &["commit", "-am", "feature edit"]);
+        git(&repo_path, &["checkout", "main"]);
+        fs::write(repo_path.join("notes.txt"), "main\n").unwrap();
+        git(&repo_path, &["commit", "-am", "main edit"]);
+        assert!(!git_succeeds(&repo_path, &["merge", "feature/x"]));
+
+        let status = read_worktree_status(&repo_path, None).unwrap();
+
+        assert_eq!(status.in_progress, Some(InProgressOperation::Merge));
+        assert_eq!(status.conflicted[0].path, "notes.txt");
+        assert_eq!(status.conflicted[0].kind, FileChangeKind::Unmerged);
+    }
+}
@@ -0,0 +1,421 @@
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use super::git_local::{detect_default_base_ref, run_git, run_git_untrimmed};
+use super::git_status::{
+    detect_in_progress, read_head_summary, CommitSummary, InProgressOperation,
+};
+use super::process::{git_timeout, run_command, RunLimits, LOCAL_TIMEOUT};
+use super::WorkspaceError;
+
+/// Conflict sides larger than this are reported without their contents.
+pub const CONFLICT_BLOB_MAX_BYTES: usize = 512 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStrategy {
+    Rebase,
+    Merge,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceSyncInput {
+    pub workspace_id: String,
+    pub strategy: SyncStrategy,
+}
+
+/// One conflicted path. `ours` is always the workspace side and `theirs` the
+/// incoming `base_ref` side, whichever strategy produced the conflict. A side
+/// is `None` when the file does not exist there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictedFile {
+    pub path: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+    /// Contents were left out because a side is binary or larger than
+    /// `CONFLICT_BLOB_MAX_BYTES`.
+    pub content_omitted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncOutcome {
+    /// `base_ref` was already contained in the workspace branch.
+    UpToDate,
+    Synced {
+        head: CommitSummary,
+    },
+    /// The operation stopped; resolve and stage the files, then continue or
+    /// abort.
+    #[serde(rename_all = "camelCase")]
+    Conflicts {
+        operation: InProgressOperation,
+        files: Vec<ConflictedFile>,
+    },
+}
+
+/// Fetches the remote of `base_ref`, then rebases or merges the worktree's
+/// branch onto it. Local changes must be committed or discarded first.
+pub fn sync_worktree(
+    worktree_path: &Path,
+    base_ref: Option<&str>,
+    strategy: SyncStrategy,
+) -> Result<SyncOutcome, WorkspaceError> {
+    if current_operation(worktree_path)?.is_some() {
+        return Err(WorkspaceError::InvalidInput(
+            "A rebase or merge is already in progress. Continue or abort it first.".to_string(),
+        ));
+    }
+    let local_changes = run_git(
+        worktree_path,
+        &["status", "--porcelain", "--untracked-files=no"],
+    )?;
+    if !local_changes.is_empty() {
+        return Err(WorkspaceError::InvalidInput(
+            "Commit or discard local changes before syncing".to_string(),
+        ));
+    }
+
+    let base_ref = match base_ref.filter(|value| !value.trim().is_empty()) {
+        Some(base_ref) => base_ref.to_string(),
+        None => detect_default_base_ref(worktree_path)?,
+    };
+    if let Some(remote) = base_ref_remote(worktree_path, &base_ref)? {
+        run_git(worktree_path, &["fetch", "--prune", &remote])?;
+    }
+    if git_succeeds(
+        worktree_path,
+        &["merge-base", "--is-ancestor", &base_ref, "HEAD"],
+    )? {
+        return Ok(SyncOutcome::UpToDate);
+    }
+
+    let args = match strategy {
+        SyncStrategy::Rebase => vec!["rebase", base_ref.as_str()],
+        SyncStrategy::Merge => vec!["merge", "--no-edit", base_ref.as_str()],
+    };
+    finish_step(worktree_path, &args)
+}
+
+/// The remote `base_ref` is a remote-tracking branch of, if any. Only that
+/// remote is fetched, so an unreachable one, such as a deleted fork, does not
+/// stop every sync.
+fn base_ref_remote(worktree_path: &Path, base_ref: &str) -> Result<Option<String>, WorkspaceError> {
+    let short_ref = base_ref.strip_prefix("refs/remotes/").unwrap_or(base_ref);
+    Ok(run_git(worktree_path, &["remote"])?
+        .lines()
+        .filter(|remote| short_ref.starts_with(&format!("{remote}/")))
+        .max_by_key(|remote| remote.len())
+        .map(str::to_string))
+}
+
+/// Continues a stopped sync once conflicts are resolved and staged. A rebase
+/// may stop again on a later commit, which is reported as new conflicts.
+pub fn continue_sync(worktree_path: &Path) -> Result<SyncOutcome, WorkspaceError> {
+    let operation = current_operation(worktree_path)?.ok_or_else(|| {
+        WorkspaceError::InvalidInput("No rebase or merge is in progress".to_string())
+    })?;
+    let files = read_conflicts(worktree_path, operation)?;
+    if !files.is_empty() {
+        return Ok(SyncOutcome::Conflicts { operation, files });
+    }
+
+    let args = match operation {
+        InProgressOperation::Rebase => ["-c", "core.editor=true", "rebase", "--continue"],
+        _ => ["-c", "core.editor=true", "merge", "--continue"],
+    };
+    finish_step(worktree_path, &args)
+}
+
+pub fn abort_sync(worktree_path: &Path) -> Result<(), WorkspaceError> {
+    match current_operation(worktree_path)? {
+        Some(InProgressOperation::Rebase) => run_git(worktree_path, &["rebase", "--abort"])?,
+        Some(InProgressOperation::Merge) => run_git(worktree_path, &["merge", "--abort"])?,
+        _ => {
+            return Err(WorkspaceError::InvalidInput(
+                "No rebase or merge is in progress".to_string(),
+            ))
+        }
+    };
+    Ok(())
+}
+
+/// Runs a rebase or merge step and reports where it ended up.
+fn finish_step(worktree_path: &Path, args: &[&str]) -> Result<SyncOutcome, WorkspaceError> {
+    let step = run_git(worktree_path, args);
+    if let Some(operation) = current_operation(worktree_path)? {
+        let files = read_conflicts(worktree_path, operation)?;
+        if !files.is_empty() {
+            return Ok(SyncOutcome::Conflicts { operation, files });
+        }
+    }
+    step?;
+
+    let head = read_head_summary(worktree_path)?
+        .ok_or_else(|| WorkspaceError::GitFailed("HEAD could not be read".to_string()))?;
+    Ok(SyncOutcome::Synced { head })
+}
+
+/// Rebases and merges are the only operations sync starts or resumes.
+fn current_operation(worktree_path: &Path) -> Result<Option<InProgressOperation>, WorkspaceError> {
+    let git_dir = run_git(worktree_path, &["rev-parse", "--absolute-git-dir"])?;
+    Ok(detect_in_progress(Path::new(&git_dir)).filter(|operation| {
+        matches!(
+            operation,
+            InProgressOperation::Rebase | InProgressOperation::Merge
+        )
+    }))
+}
+
+fn read_conflicts(
+    worktree_path: &Path,
+    operation: InProgressOperation,
+) -> Result<Vec<ConflictedFile>, WorkspaceError> {
+    let unmerged = run_git_untrimmed(
+        worktree_path,
+        &["diff", "--name-only", "--diff-filter=U", "-z"],
+    )?;
+    let mut paths = unmerged
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .collect::<Vec<_>>();
+    paths.dedup();
+
+    // During a rebase stage 2 holds the branch being rebased onto and stage 3
+    // the workspace commit being replayed, the reverse of a merge.
+    let (ours_stage, theirs_stage) = match operation {
+        InProgressOperation::Rebase => (3, 2),
+        _ => (2, 3),
+    };
+    paths
+        .into_iter()
+        .map(|path| {
+            let base = read_stage_blob(worktree_path, 1, path)?;
+            let ours = read_stage_blob(worktree_path, ours_stage, path)?;
+            let theirs = read_stage_blob(worktree_path, theirs_stage, path)?;
+            let content_omitted = [&base, &ours, &theirs]
+                .iter()
+                .any(|side| matches!(side, Some(BlobContent::Omitted)));
+            Ok(ConflictedFile {
+                path: path.to_string(),
+                base: base.and_then(BlobContent::into_text),
+                ours: ours.and_then(BlobContent::into_text),
+                theirs: theirs.and_then(BlobContent::into_text),
+                content_omitted,
+            })
+        })
+        .collect()
+}
+
+enum BlobContent {
+    Text(String),
+    Omitted,
+}
+
+impl BlobContent {
+    fn into_text(self) -> Option<String> {
+        match self {
+            BlobContent::Text(text) => Some(text),
+            BlobContent::Omitted => None,
+        }
+    }
+}
+
+/// Reads the index entry of `path` at a merge stage, or `None` when that
+/// side has no such file.
+fn read_stage_blob(
+    worktree_path: &Path,
+    stage: u8,
+    path: &str,
+) -> Result<Option<BlobContent>, WorkspaceError> {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(worktree_path)
+        .args(["cat-file", "blob", &format!(":{stage}:{path}")]);
+    let output = run_command(command, "git cat-file", RunLimits::timeout(LOCAL_TIMEOUT))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let bytes = output.stdout;
+    if bytes.len() > CONFLICT_BLOB_MAX_BYTES || bytes.contains(&0) {
+        return Ok(Some(BlobContent::Omitted));
+    }
+    Ok(Some(match String::from_utf8(bytes) {
+        Ok(text) => BlobContent::Text(text),
+        Err(_) => BlobContent::Omitted,
+    }))
+}
+
+fn git_succeeds(repo_path: &Path, args: &[&str]) -> Result<bool, WorkspaceError> {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(repo_path).args(args);
+    let output = run_command(
+        command,
+        &format!("git {}", args.join(" ")),
+        RunLimits::timeout(git_timeout(args)),
+    )?;
+    Ok(output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::{abort_sync, continue_sync, sync_worktree, SyncOutcome, SyncStrategy};
+    use crate::workspaces::git_status::InProgressOperation;
+    use crate::workspaces::test_support::git;
+
+    fn commit_file(repo_path: &Path, name: &str, contents: &str, message: &str) {
+        fs::write(repo_path.join(name), contents).unwrap();
+        git(repo_path, &["add", name]);
+        git(repo_path, &["commit", "-m", message]);
+    }
+
+    /// `feature/x` and `main` both edit `notes.txt` after branching; with
+    /// `conflicting` unset they touch different files instead.
+    fn sync_fixture(repo_path: &Path, conflicting: bool) {
+        fs::create_dir_all(repo_path).unwrap();
+        git(repo_path, &["init"]);
+        git(repo_path, &["config", "user.name", "Kata Test"]);
+        git(repo_path, &["config", "user.email", "kata@example.com"]);
+        git(repo_path, &["checkout", "-B", "main"]);
+        commit_file(repo_path, "notes.txt", "base\n", "initial");
+        git(repo_path, &["checkout", "-b", "feature/x"]);
+        commit_file(repo_path, "notes.txt", "feature\n", "feature edit");
+        git(repo_path, &["checkout", "main"]);
+        let main_file = if conflicting {
+            "notes.txt"
+        } else {
+            "other.txt"
+        };
+        commit_file(repo_path, main_file, "main\n", "main edit");
+        git(repo_path, &["checkout", "feature/x"]);
+    }
+
+    #[test]
+    fn rebases_cleanly_and_reports_up_to_date_afterwards() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        sync_fixture(&repo_path, false);
+
+        let outcome = sync_worktree(&repo_path, Some("main"), SyncStrategy::Rebase).unwrap();
+
+        match outcome {
+            SyncOutcome::Synced { head } => assert_eq!(head.subject, "feature edit"),
+            other => panic!("expected Synced, got {other:?}"),
+        }
+        assert_eq!(git(&repo_path, &["rev-list", "--count", "HEAD"]), "3");
+        assert_eq!(
+            sync_worktree(&repo_path, Some("main"), SyncStrategy::Merge).unwrap(),
+            SyncOutcome::UpToDate
+        );
+    }
+
+    #[test]
+    fn reports_rebase_conflicts_from_the_workspace_point_of_view() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        sync_fixture(&repo_path, true);
+
+        let outcome = sync_worktree(&repo_path, Some("main"), SyncStrategy::Rebase).unwrap();
+
+        let SyncOutcome::Conflicts { operation, files } = outcome else {
+            panic!("expected conflicts");
+        };
+        assert_eq!(operation, InProgressOperation::Rebase);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "notes.txt");
+        assert_eq!(files[0].base.as_deref(), Some("base\n"));
+        assert_eq!(files[0].ours.as_deref(), Some("feature\n"));
+        assert_eq!(files[0].theirs.as_deref(), Some("main\n"));
+
+        abort_sync(&repo_path).unwrap();
+        assert_eq!(
+            git(&repo_path, &["branch", "--show-current"]),
+            "feature/x"
+        );
+        assert_eq!(
+            fs::read_to_string(repo_path.join("notes.txt")).unwrap(),
+            "feature\n"
+        );
+    }
+
+    #[test]
+    fn continues_merge_after_conflicts_are_resolved() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        sync_fixture(&repo_path, true);
+
+        let outcome = sync_worktree(&repo_path, Some("main"), SyncStrategy::Merge).unwrap();
+        let SyncOutcome::Conflicts { operation, files } = outcome else {
+            panic!("expected conflicts");
+        };
+        assert_eq!(operation, InProgressOperation::Merge);
+        assert_eq!(files[0].ours.as_deref(), Some("feature\n"));
+        assert_eq!(files[0].theirs.as_deref(), Some("main\n"));
+
+        assert!(matches!(
+            continue_sync(&repo_path).unwrap(),
+            SyncOutcome::Conflicts { .. }
+        ));
+        fs::write(repo_path.join("notes.txt"), "feature and main\n").unwrap();
+        git(&repo_path, &["add", "notes.txt"]);
+
+        let outcome = continue_sync(&repo_path).unwrap();
+        assert!(matches!(outcome, SyncOutcome::Synced { .. }));
+        assert_eq!(
+            git(&repo_path, &["rev-list", "--count", "--merges", "HEAD"]),
+            "1"
+        );
+    }
+
+    #[test]
+    fn fetches_only_the_base_remote() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let origin = tmpdir.path().join("origin");
+        sync_fixture(&origin, false);
+        let clone = tmpdir.path().join("clone");
+        git(
+            tmpdir.path(),
+            &[
+                "clone",
+                "--branch",
+                "feature/x",
+                origin.to_str().unwrap(),
+                "clone",
+            ],
+        );
+        git(&clone, &["config", "user.name", "Kata Test"]);
+        git(&clone, &["config", "user.email", "kata@example.com"]);
+        let gone = tmpdir.path().join("gone");
+        git(
+            &clone,
+            &["remote", "add", "fork-gone", gone.to_str().unwrap()],
+        );
+        git(&origin, &["checkout", "main"]);
+        commit_file(&origin, "later.txt", "later\n", "later main edit");
+
+        let outcome = sync_worktree(&clone, Some("origin/main"), SyncStrategy::Rebase).unwrap();
+
+        assert!(matches!(outcome, SyncOutcome::Synced { .. }));
+        assert_eq!(git(&clone, &["rev-list", "--count", "HEAD"]), "4");
+    }
+
+    #[test]
+    fn refuses_to_sync_with_local_changes() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let repo_path = tmpdir.path().join("repo");
+        sync_fixture(&repo_path, false);
+        fs::write(repo_path.join("notes.txt"), "uncommitted\n").unwrap();
+
+        let err = sync_worktree(&repo_path, Some("main"), SyncStrategy::Rebase).unwrap_err();
+        assert!(err.to_string().contains("local changes"));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use super::git_local::{canonicalize_path, list_worktrees};
+use super::model::{ImportableWorktree, Workspace};
+use super::recovery::adopt_worktree;
+use super::WorkspaceError;
+
+/// Lists the linked worktrees of the repository at `repo_path` together with
+/// the record each would be imported as. The main worktree, bare entries,
+/// worktrees whose folder is gone and detached worktrees, which have no branch
+/// to record, are skipped.
+pub fn list_importable_worktrees(
+    repo_path: &Path,
+    registered: &[Workspace],
+) -> Result<Vec<ImportableWorktree>, WorkspaceError> {
+    let registered_paths = registered
+        .iter()
+        .map(|workspace| workspace.worktree_path.clone())
+        .collect::<BTreeSet<_>>();
+
+    let mut importable = Vec::new();
+    for entry in list_worktrees(repo_path)?.into_iter().skip(1) {
+        if entry.bare || entry.prunable || entry.branch.is_none() || !entry.path.exists() {
+            continue;
+        }
+        let Some(candidate) = adopt_worktree(&entry.path, None)? else {
+            continue;
+        };
+        importable.push(ImportableWorktree {
+            already_registered: registered_paths.contains(&candidate.worktree_path),
+            worktree_path: candidate.worktree_path,
+            repo_root_path: candidate.repo_root_path,
+            branch: entry.branch,
+            head: entry.head,
+            base_ref: candidate.base_ref.unwrap_or_default(),
+            source_type: candidate.source_type,
+            source: candidate.source,
+        });
+    }
+    Ok(importable)
+}
+
+/// Builds workspace records for the selected worktrees of `repo_path`. Only
+/// the registry is affected; nothing on disk is touched. A worktree selected
+/// more than once is imported once.
+pub fn import_worktrees(
+    repo_path: &Path,
+    worktree_paths: &[String],
+    registered: &[Workspace],
+) -> Result<Vec<Workspace>, WorkspaceError> {
+    if worktree_paths.is_empty() {
+        return Err(WorkspaceError::InvalidInput(
+            "Select at least one worktree to import".to_string(),
+        ));
+    }
+    let importable = list_importable_worktrees(repo_path, registered)?;
+
+    let mut seen = BTreeSet::new();
+    let mut imported = Vec::new();
+    for requested in worktree_paths {
+        let canonical = canonicalize_path(Path::new(requested)).map_err(|_| {
+            WorkspaceError::InvalidInput(format!("Worktree path does not exist: {requested}"))
+        })?;
+        if !seen.insert(canonical.clone()) {
+            continue;
+        }
+        let candidate = importable
+            .iter()
+            .find(|candidate| candidate.worktree_path == canonical)
+            .ok_or_else(|| {
+                WorkspaceError::InvalidInput(format!(
+                    "Not a linked worktree of {} with a branch checked out: {requested}",
+                    repo_path.display()
+                ))
+            })?;
+        if candidate.already_registered {
+            return Err(WorkspaceError::InvalidInput(format!(
+                "Worktree is already registered as a workspace: {requested}"
+            )));
+        }
+        let workspace = adopt_worktree(Path::new(&canonical), None)?.ok_or_else(|| {
+            WorkspaceError::InvalidInput(format!("Not a linked git worktree: {requested}"))
+        })?;
+        imported.push(workspace);
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
+    use super::{import_worktrees, list_importable_worktrees};
+    use crate::workspaces::git_local::create_local_workspace;
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::{
+        build_workspace, next_workspace_id, WorkspaceBranch, WorkspaceSourceType, WorkspaceStatus,
+    };
+    use crate::workspaces::test_support::git;
+
+    struct ImportFixture {
+        tmpdir: TempDir,
+        repo_path: PathBuf,
+    }
+
+    impl ImportFixture {
+        fn new() -> Self {
+            let tmpdir = tempfile::tempdir().unwrap();
+            let repo_path = tmpdir.path().join("repo");
+            fs::create_dir_all(&repo_path).unwrap();
+            git(&repo_path, &["init"]);
+            git(&repo_path, &["checkout", "-B", "main"]);
+            fs::write(repo_path.join("README.md"), "# fixture\n").unwrap();
+            git(&repo_path, &["add", "."]);
+            git(&repo_path, &["commit", "-m", "initial"]);
+
+            Self { tmpdir, repo_path }
+        }
+
+        fn add_worktree_by_hand(&self, dir_name: &str, branch: &str) -> PathBuf {
+            let path = self.tmpdir.path().join(dir_name);
+            git(
+                &self.repo_path,
+                &[
+                    "worktree",
+                    "add",
+                    "-b",
+                    branch,
+                    path.to_str().unwrap(),
+                    "main",
+                ],
+            );
+            path.canonicalize().unwrap()
+        }
+    }
+
+    #[test]
+    fn lists_linked_worktrees_and_flags_registered_ones() {
+        let fixture = ImportFixture::new();
+        let by_hand = fixture.add_worktree_by_hand("feature-x", "feature/x");
+        let prepared = create_local_workspace(
+            &fixture.repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(None),
+            None,
+            &[],
+            "ab12",
+            &fixture.tmpdir.path().join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+        let registered = build_workspace(
+            next_workspace_id(),
+            "KAT-154".to_string(),
+            WorkspaceSourceType::Local,
+            fixture.repo_path.to_string_lossy().to_string(),
+            prepared,
+        );
+
+        let importable = list_importable_worktrees(&fixture.repo_path, &[registered]).unwrap();
+
+        assert_eq!(importable.len(), 2);
+        let hand_made = importable
+            .iter()
+            .find(|candidate| candidate.worktree_path == by_hand.to_string_lossy())
+            .unwrap();
+        assert!(!hand_made.already_registered);
+        assert_eq!(hand_made.branch.as_deref(), Some("feature/x"));
+        assert_eq!(hand_made.base_ref, "main");
+        assert_eq!(hand_made.source_type, WorkspaceSourceType::Local);
+        assert!(importable
+            .iter()
+            .any(|candidate| candidate.already_registered));
+    }
+
+    #[test]
+    fn imports_selected_worktree_without_touching_disk() {
+        let fixture = ImportFixture::new();
+        let by_hand = fixture.add_worktree_by_hand("feature-x", "feature/x");
+        let before = fs::read_dir(&by_hand).unwrap().count();
+
+        let imported = import_worktrees(
+            &fixture.repo_path,
+            &[by_hand.to_string_lossy().to_string()],
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].branch, "feature/x");
+        assert_eq!(imported[0].status, WorkspaceStatus::Ready);
+        assert_eq!(
+            imported[0].repo_root_path,
+            fixture.repo_path.canonicalize().unwrap().to_string_lossy()
+        );
+        assert_eq!(fs::read_dir(&by_hand).unwrap().count(), before);
+    }
+
+    #[test]
+    fn rejects_paths_that_are_not_linked_worktrees_of_the_repo() {
+        let fixture = ImportFixture::new();
+        let err = import_worktrees(
+            &fixture.repo_path,
+            &[fixture.repo_path.to_string_lossy().to_string()],
+            &[],
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("Not a linked worktree"));
+    }
+
+    #[test]
+    fn skips_detached_worktrees() {
+        let fixture = ImportFixture::new();
+        let detached = fixture.tmpdir.path().join("detached");
+        git(
+            &fixture.repo_path,
+            &[
+                "worktree",
+                "add",
+                "--detach",
+                detached.to_str().unwrap(),
+                "main",
+            ],
+        );
+
+        assert!(list_importable_worktrees(&fixture.repo_path, &[])
+            .unwrap()
+            .is_empty());
+        let err = import_worktrees(
+            &fixture.repo_path,
+            &[detached.to_string_lossy().to_string()],
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("with a branch checked out"));
+    }
+
+    #[test]
+    fn imports_a_worktree_selected_twice_once() {
+        let fixture = ImportFixture::new();
+        let by_hand = fixture.add_worktree_by_hand("feature-x", "feature/x");
+        let path = by_hand.to_string_lossy().to_string();
+
+        let imported =
+            import_worktrees(&fixture.repo_path, &[path.clone(), format!("{path}/")], &[]).unwrap();
+
+        assert_eq!(imported.len(), 1);
+    }
+}
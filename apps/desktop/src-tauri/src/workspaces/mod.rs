@@ -1,14 +1,51 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
+use tauri::ipc::Channel;
+
+pub mod checkpoints;
+pub mod ci_checks;
 pub mod commands;
+pub mod create_jobs;
+pub mod doctor;
+pub mod fork;
+pub mod git_changes;
+pub mod git_diff;
 pub mod git_github;
 pub mod git_local;
+pub mod git_progress;
+pub mod git_status;
+pub mod git_sync;
+pub mod import;
 pub mod model;
+pub mod operations;
+pub mod pr_watch;
+pub mod process;
+pub mod recovery;
+pub mod rename;
+pub mod repo_cache;
+pub mod repo_locks;
 pub mod store;
+#[cfg(test)]
+mod test_support;
 
+use create_jobs::CreateJobHandle;
+use git_progress::GitProgressEvent;
+use git_status::StatusCache;
+use model::WorkspaceStartupNotice;
+use repo_locks::RepoLocks;
 pub use store::WorkspaceStore;
 
+/// Event emitted once per notice recorded while loading workspace state.
+pub const STARTUP_NOTICE_EVENT: &str = "workspace:startup-notice";
+
+/// Event emitted when the state of a workspace pull request changes.
+pub const PULL_REQUEST_EVENT: &str = "workspace:pull-request";
+
+/// Event emitted when a background creation job starts and when it ends.
+pub const CREATE_PROGRESS_EVENT: &str = "workspace:create-progress";
+
 #[derive(Debug, thiserror::Error)]
 pub enum WorkspaceError {
     #[error("IO error: {0}")]
@@ -21,19 +58,62 @@ pub enum WorkspaceError {
     NotFound(String),
     #[error("{0}")]
     GitFailed(String),
+    #[error(
+        "Workspace registry uses schema version {found}, but this version of the app only \
+         supports up to {supported}. Update the app instead of downgrading the registry."
+    )]
+    UnsupportedSchemaVersion { found: u32, supported: u32 },
+    #[error("The {hook} hook failed: {output}")]
+    HookFailed {
+        hook: String,
+        exit_code: Option<i32>,
+        output: String,
+    },
+    #[error("{branch} is a protected branch. Make changes on a workspace branch instead.")]
+    ProtectedBranch { branch: String },
+    #[error("{command} did not finish within {timeout_secs} seconds and was stopped")]
+    TimedOut { command: String, timeout_secs: u64 },
+    #[error("{command} was cancelled")]
+    Cancelled { command: String },
 }
 
 pub struct WorkspaceState {
     pub app_data_dir: PathBuf,
     pub store: Mutex<WorkspaceStore>,
+    pub startup_notices: Mutex<Vec<WorkspaceStartupNotice>>,
+    pub status_cache: Mutex<StatusCache>,
+    /// Running creation jobs by workspace ID.
+    pub create_jobs: Mutex<HashMap<String, CreateJobHandle>>,
+    /// Channels watching the git progress of running creation jobs.
+    pub progress_channels: Mutex<HashMap<String, Channel<GitProgressEvent>>>,
+    /// Repositories and repo caches that an operation is working on.
+    pub repo_locks: RepoLocks,
 }
 
 impl WorkspaceState {
     pub fn new(app_data_dir: PathBuf) -> Result<Self, WorkspaceError> {
-        let store = WorkspaceStore::load(&app_data_dir)?;
+        let (mut store, notice) = recovery::load_store_with_recovery(&app_data_dir)?;
+        let recovered = operations::recover_interrupted_operations(&app_data_dir, &store);
+        if store.fail_interrupted_creates() > 0 {
+            if let Err(err) = store.save() {
+                eprintln!("Warning: failed to record interrupted workspace creations: {err}");
+            }
+        }
         Ok(Self {
             app_data_dir,
             store: Mutex::new(store),
+            startup_notices: Mutex::new(notice.into_iter().chain(recovered).collect()),
+            status_cache: Mutex::new(StatusCache::default()),
+            create_jobs: Mutex::new(HashMap::new()),
+            progress_channels: Mutex::new(HashMap::new()),
+            repo_locks: RepoLocks::default(),
         })
     }
+
+    pub fn pending_startup_notices(&self) -> Vec<WorkspaceStartupNotice> {
+        self.startup_notices
+            .lock()
+            .map(|notices| notices.clone())
+            .unwrap_or_default()
+    }
 }
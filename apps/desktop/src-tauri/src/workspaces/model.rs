@@ -1,5 +1,9 @@
 use chrono::{SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::fork::WorkspaceForkInput;
+use super::WorkspaceError;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -32,18 +36,208 @@ pub struct Workspace {
     pub created_at: String,
     pub updated_at: String,
     pub last_opened_at: Option<String>,
+    /// Why the workspace is in `WorkspaceStatus::Error`.
+    #[serde(default)]
+    pub error_message: Option<String>,
+    /// Pull request whose head branch is the workspace branch, if any.
+    #[serde(default)]
+    pub pull_request: Option<WorkspacePullRequest>,
+    /// Pull request whose head the workspace branch was started from, when
+    /// it was created as a new branch off a pull request.
+    #[serde(default)]
+    pub source_pull_request: Option<WorkspacePullRequest>,
+    /// Workspace this one was forked from with `workspace_fork`.
+    #[serde(default)]
+    pub parent_workspace_id: Option<String>,
+    /// Directories checked out in cone-mode sparse checkout. Empty means the
+    /// whole repository is checked out.
+    #[serde(default)]
+    pub sparse_paths: Vec<String>,
+    /// Background job creating the workspace. Kept after a failure so the
+    /// creation can be retried; cleared once the workspace is ready.
+    #[serde(default)]
+    pub create_job: Option<CreateJob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateJob {
+    pub id: String,
+    pub request: CreateWorkspaceRequest,
+}
+
+/// The create command a workspace came from, with its original input.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CreateWorkspaceRequest {
+    Local(CreateLocalWorkspaceInput),
+    Github(CreateGitHubWorkspaceInput),
+    NewGithub(CreateNewGitHubWorkspaceInput),
+    FromSource(CreateWorkspaceFromSourceInput),
+    Fork(WorkspaceForkInput),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspacePullRequest {
+    pub number: u32,
+    pub url: String,
+    pub head_branch: String,
+    pub base_branch: String,
+    /// `owner/name` of the repository holding the head branch. `None` when
+    /// the fork has been deleted.
+    pub head_repository: Option<String>,
+    pub is_cross_repository: bool,
+    pub maintainer_can_modify: bool,
+    /// Remote that pushes of the head branch go to. Only set for fork pull
+    /// requests that accept pushes from maintainers.
+    #[serde(default)]
+    pub push_remote: Option<String>,
+    /// Last state read from GitHub by the pull request watcher.
+    #[serde(default)]
+    pub status: Option<PullRequestStatus>,
+}
+
+impl WorkspacePullRequest {
+    pub fn head_repository_url(&self) -> Option<String> {
+        self.head_repository
+            .as_ref()
+            .map(|repository| format!("https://github.com/{repository}"))
+    }
+
+    /// Local branch a fork pull request is checked out on, `pr/<number>/<head>`.
+    pub fn fork_branch(&self) -> String {
+        format!("pr/{}/{}", self.number, self.head_branch)
+    }
+
+    /// Whether `branch` is the head branch, or the local branch a fork pull
+    /// request is checked out on.
+    pub fn is_head_branch(&self, branch: &str) -> bool {
+        branch == self.head_branch || (self.is_cross_repository && branch == self.fork_branch())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PullRequestState {
+    Open,
+    Merged,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewDecision {
+    Approved,
+    ChangesRequested,
+    ReviewRequired,
+}
+
+/// Combined result of the checks and commit statuses on the PR head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckRollup {
+    None,
+    Pending,
+    Passing,
+    Failing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestStatus {
+    pub state: PullRequestState,
+    pub is_draft: bool,
+    pub review_decision: Option<ReviewDecision>,
+    pub checks: CheckRollup,
+    pub merged_at: Option<String>,
+}
+
+/// What to do with a workspace once its pull request is merged. Stored in
+/// the registry; everything is off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoArchivePolicy {
+    pub archive_merged: bool,
+    /// Also remove the worktree directory. Only applies with `archive_merged`.
+    #[serde(default)]
+    pub remove_worktree: bool,
+    /// Also delete the local workspace branch. Only applies with `archive_merged`.
+    #[serde(default)]
+    pub delete_branch: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenPullRequestInput {
+    pub workspace_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub draft: bool,
+    /// GitHub logins or `org/team` slugs.
+    #[serde(default)]
+    pub reviewers: Vec<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+/// How the GitHub repository cache that workspaces are created from is
+/// cloned. Only applies when the cache is first cloned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CloneStrategy {
+    #[default]
+    Full,
+    /// `--filter=blob:none`: file contents are fetched when first checked out.
+    Blobless,
+    /// History truncated to `depth` commits on every branch.
+    Shallow { depth: u32 },
+    /// A bare repository with no checkout of its own; worktrees are added
+    /// straight off it.
+    SharedMirror,
+}
+
+/// Where and how the GitHub repository cache is cloned.
+#[derive(Debug, Clone, Default)]
+pub struct RepoCacheOptions {
+    pub clone_root_path: Option<String>,
+    pub clone_strategy: CloneStrategy,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct SetCloneStrategyInput {
+    /// `owner/repo`.
+    pub repo_id: String,
+    pub strategy: CloneStrategy,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetSparsePathsInput {
+    pub workspace_id: String,
+    /// Directories to keep checked out. Empty checks out everything again.
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateLocalWorkspaceInput {
     pub repo_path: String,
     pub workspace_name: String,
     pub branch_name: Option<String>,
     pub base_ref: Option<String>,
+    /// Check out `branch_name` as-is instead of forking a new branch from it.
+    #[serde(default)]
+    pub checkout_existing: bool,
+    /// Only check out these directories (sparse checkout, cone mode).
+    #[serde(default)]
+    pub sparse_paths: Vec<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateGitHubWorkspaceInput {
     pub repo_url: String,
@@ -51,9 +245,16 @@ pub struct CreateGitHubWorkspaceInput {
     pub clone_root_path: Option<String>,
     pub branch_name: Option<String>,
     pub base_ref: Option<String>,
+    #[serde(default)]
+    pub checkout_existing: bool,
+    #[serde(default)]
+    pub sparse_paths: Vec<String>,
+    /// Overrides the repository's default clone strategy.
+    #[serde(default)]
+    pub clone_strategy: Option<CloneStrategy>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateNewGitHubWorkspaceInput {
     pub repository_name: String,
@@ -97,30 +298,172 @@ pub struct WorkspaceIssueOption {
     pub updated_at: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryBackup {
+    pub index: usize,
+    pub path: String,
+    pub modified_at: Option<String>,
+    pub size_bytes: u64,
+    pub workspace_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportableWorktree {
+    pub worktree_path: String,
+    pub repo_root_path: String,
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub base_ref: String,
+    pub source_type: WorkspaceSourceType,
+    pub source: String,
+    pub already_registered: bool,
+}
+
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportExistingWorktreesInput {
+    pub repo_path: String,
+    pub worktree_paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceStartupNoticeKind {
+    RegistryRestoredFromBackup,
+    RegistryRebuilt,
+    InterruptedOperationsRecovered,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceStartupNotice {
+    pub kind: WorkspaceStartupNoticeKind,
+    pub message: String,
+    pub details: Vec<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum WorkspaceCreateFromSource {
     Default,
-    PullRequest { value: u32 },
-    Branch { value: String },
-    Issue { value: u32 },
+    #[serde(rename_all = "camelCase")]
+    PullRequest {
+        value: u32,
+        #[serde(default)]
+        checkout_existing: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    Branch {
+        value: String,
+        #[serde(default)]
+        checkout_existing: bool,
+    },
+    Issue {
+        value: u32,
+    },
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// Which branch a new workspace's worktree is created on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkspaceBranch {
+    /// Fork a new branch off the base ref, named explicitly or derived from
+    /// the workspace name.
+    New(Option<String>),
+    /// Check out a branch that already exists locally or on `origin`.
+    Existing(String),
+}
+
+impl WorkspaceBranch {
+    pub fn from_input(
+        branch_name: Option<String>,
+        checkout_existing: bool,
+    ) -> Result<Self, WorkspaceError> {
+        if !checkout_existing {
+            return Ok(Self::New(branch_name));
+        }
+        branch_name
+            .filter(|value| !value.trim().is_empty())
+            .map(Self::Existing)
+            .ok_or_else(|| {
+                WorkspaceError::InvalidInput(
+                    "Branch name is required to check out an existing branch".to_string(),
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateWorkspaceFromSourceInput {
     pub repo_id: String,
     pub workspace_name: Option<String>,
     pub clone_root_path: Option<String>,
     pub source: WorkspaceCreateFromSource,
+    #[serde(default)]
+    pub sparse_paths: Vec<String>,
+    #[serde(default)]
+    pub clone_strategy: Option<CloneStrategy>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct PreparedWorkspace {
     pub repo_root_path: String,
     pub worktree_path: String,
     pub branch: String,
     pub base_ref: String,
+    pub sparse_paths: Vec<String>,
+}
+
+pub fn next_workspace_id() -> String {
+    format!("ws_{}", Uuid::new_v4().simple())
+}
+
+pub fn next_job_id() -> String {
+    format!("job_{}", Uuid::new_v4().simple())
+}
+
+pub fn next_operation_id() -> String {
+    format!("op_{}", Uuid::new_v4().simple())
+}
+
+pub fn workspace_suffix(workspace_id: &str) -> &str {
+    workspace_id
+        .strip_prefix("ws_")
+        .map(|value| &value[..4.min(value.len())])
+        .unwrap_or("ws")
+}
+
+pub fn build_workspace(
+    id: String,
+    name: String,
+    source_type: WorkspaceSourceType,
+    source: String,
+    prepared: PreparedWorkspace,
+) -> Workspace {
+    let timestamp = now_iso8601();
+    Workspace {
+        id,
+        name,
+        source_type,
+        source,
+        repo_root_path: prepared.repo_root_path,
+        worktree_path: prepared.worktree_path,
+        branch: prepared.branch,
+        base_ref: Some(prepared.base_ref),
+        status: WorkspaceStatus::Ready,
+        created_at: timestamp.clone(),
+        updated_at: timestamp,
+        last_opened_at: None,
+        error_message: None,
+        pull_request: None,
+        source_pull_request: None,
+        parent_workspace_id: None,
+        sparse_paths: prepared.sparse_paths,
+        create_job: None,
+    }
 }
 
 pub fn now_iso8601() -> String {
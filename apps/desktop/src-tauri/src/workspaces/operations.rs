@@ -0,0 +1,420 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use super::git_local::{branch_exists, canonicalize_path, list_worktrees, run_git};
+use super::model::{
+    next_operation_id, now_iso8601, WorkspaceStartupNotice, WorkspaceStartupNoticeKind,
+};
+use super::recovery::startup_notice;
+use super::store::write_atomically;
+use super::{WorkspaceError, WorkspaceStore};
+
+/// Serializes updates of the journal, which background creation jobs write
+/// concurrently.
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// A multi-step git operation that leaves debris behind if interrupted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+    /// `git worktree add`, followed by the sparse checkout when paths are set.
+    #[serde(rename_all = "camelCase")]
+    AddWorktree {
+        repo_path: String,
+        worktree_path: String,
+        branch: String,
+        /// The branch did not exist before, so undoing the operation deletes it.
+        creates_branch: bool,
+    },
+    /// Clone of a repo cache, or the init and fetch of a shared mirror.
+    #[serde(rename_all = "camelCase")]
+    CloneCache {
+        repo_url: String,
+        cache_path: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub id: String,
+    pub started_at: String,
+    pub operation: Operation,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
+    #[serde(default)]
+    operations: Vec<JournalEntry>,
+}
+
+/// An operation recorded in the journal before its first step. It must end
+/// with `finish` or `roll_back`; an entry still in the journal at startup
+/// means the app quit midway.
+#[must_use]
+#[derive(Debug)]
+pub struct PendingOperation {
+    journal_path: PathBuf,
+    entry: JournalEntry,
+}
+
+impl PendingOperation {
+    /// Drops the entry once the operation completed.
+    pub fn finish(self) {
+        let id = self.entry.id;
+        if let Err(err) = update_journal(&self.journal_path, |journal| {
+            journal.operations.retain(|entry| entry.id != id)
+        }) {
+            eprintln!(
+                "Warning: failed to update operation journal {}: {err}",
+                self.journal_path.display()
+            );
+        }
+    }
+
+    /// Undoes whatever the failed operation left behind, then drops the entry.
+    pub fn roll_back(self) {
+        let mut details = Vec::new();
+        if let Err(err) = clean_up(&self.entry.operation, &mut details) {
+            eprintln!(
+                "Warning: failed to roll back {:?}: {err}",
+                self.entry.operation
+            );
+        }
+        self.finish();
+    }
+}
+
+/// The journal lives next to the registry in `<app_data>/workspaces`.
+pub fn journal_path(workspaces_root: &Path) -> PathBuf {
+    workspaces_root.join("operations.json")
+}
+
+pub fn begin_operation(
+    workspaces_root: &Path,
+    operation: Operation,
+) -> Result<PendingOperation, WorkspaceError> {
+    let journal_path = journal_path(workspaces_root);
+    let entry = JournalEntry {
+        id: next_operation_id(),
+        started_at: now_iso8601(),
+        operation,
+    };
+    update_journal(&journal_path, |journal| {
+        journal.operations.push(entry.clone())
+    })?;
+    Ok(PendingOperation {
+        journal_path,
+        entry,
+    })
+}
+
+fn read_journal(path: &Path) -> Result<Journal, WorkspaceError> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Journal::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Applies `change` to the journal. An empty journal is removed rather than
+/// written, so the file only exists while something is in flight.
+fn update_journal(path: &Path, change: impl FnOnce(&mut Journal)) -> Result<(), WorkspaceError> {
+    let _guard = JOURNAL_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut journal = read_journal(path)?;
+    change(&mut journal);
+    if journal.operations.is_empty() {
+        return match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        };
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomically(path, &serde_json::to_vec_pretty(&journal)?)
+}
+
+/// Resolves the operations left in the journal by the previous run. Work
+/// that made it into the registry, or a clone that got as far as its last
+/// step, is rolled forward; everything else is cleaned up. Returns a notice
+/// describing what was done, or `None` if nothing was interrupted.
+pub fn recover_interrupted_operations(
+    app_data_dir: &Path,
+    store: &WorkspaceStore,
+) -> Option<WorkspaceStartupNotice> {
+    let path = journal_path(&app_data_dir.join("workspaces"));
+    let _guard = JOURNAL_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut details = Vec::new();
+    let entries = match read_journal(&path) {
+        Ok(journal) => journal.operations,
+        Err(err) => {
+            details.push(format!("Operation journal could not be read: {err}"));
+            Vec::new()
+        }
+    };
+    if entries.is_empty() && details.is_empty() {
+        return None;
+    }
+
+    for entry in &entries {
+        let recovered = if is_complete(&entry.operation, store) {
+            roll_forward(&entry.operation, &mut details)
+        } else {
+            clean_up(&entry.operation, &mut details)
+        };
+        if let Err(err) = recovered {
+            details.push(format!(
+                "Could not recover operation started at {}: {err}",
+                entry.started_at
+            ));
+        }
+    }
+    if let Err(err) = fs::remove_file(&path) {
+        if err.kind() != io::ErrorKind::NotFound {
+            details.push(format!("Could not clear {}: {err}", path.display()));
+        }
+    }
+
+    Some(startup_notice(
+        WorkspaceStartupNoticeKind::InterruptedOperationsRecovered,
+        format!(
+            "{} workspace operation(s) were interrupted when the app last quit and have been \
+             recovered.",
+            entries.len()
+        ),
+        details,
+    ))
+}
+
+fn is_complete(operation: &Operation, store: &WorkspaceStore) -> bool {
+    match operation {
+        Operation::AddWorktree { worktree_path, .. } => {
+            let Ok(canonical) = canonicalize_path(Path::new(worktree_path)) else {
+                return false;
+            };
+            store
+                .list()
+                .iter()
+                .any(|workspace| workspace.worktree_path == canonical)
+        }
+        // `origin/HEAD` is written once the objects are fetched: by `clone`
+        // before its checkout, and by the mirror's final `remote set-head`.
+        Operation::CloneCache { cache_path, .. } => {
+            let path = Path::new(cache_path);
+            is_repository_root(path)
+                && run_git(
+                    path,
+                    &[
+                        "rev-parse",
+                        "--verify",
+                        "--quiet",
+                        "refs/remotes/origin/HEAD",
+                    ],
+                )
+                .is_ok()
+        }
+    }
+}
+
+fn roll_forward(operation: &Operation, details: &mut Vec<String>) -> Result<(), WorkspaceError> {
+    match operation {
+        Operation::AddWorktree { worktree_path, .. } => {
+            details.push(format!(
+                "Kept worktree {worktree_path}, which is registered"
+            ));
+        }
+        Operation::CloneCache { cache_path, .. } => {
+            let path = Path::new(cache_path);
+            if run_git(path, &["rev-parse", "--is-bare-repository"])? != "true" {
+                run_git(path, &["reset", "--hard", "--quiet"])?;
+            }
+            details.push(format!("Finished repo cache {cache_path}"));
+        }
+    }
+    Ok(())
+}
+
+fn clean_up(operation: &Operation, details: &mut Vec<String>) -> Result<(), WorkspaceError> {
+    match operation {
+        Operation::AddWorktree {
+            repo_path,
+            worktree_path,
+            branch,
+            creates_branch,
+        } => {
+            let repo = Path::new(repo_path);
+            let worktree = Path::new(worktree_path);
+            if worktree.exists() {
+                if run_git(repo, &["worktree", "remove", "--force", worktree_path]).is_err() {
+                    fs::remove_dir_all(worktree)?;
+                }
+                details.push(format!("Removed half-created worktree {worktree_path}"));
+            }
+            run_git(repo, &["worktree", "prune"])?;
+            let checked_out = list_worktrees(repo)?
+                .iter()
+                .any(|entry| entry.branch.as_deref() == Some(branch.as_str()));
+            if *creates_branch && !checked_out && branch_exists(repo, branch)? {
+                run_git(repo, &["branch", "-D", branch])?;
+                details.push(format!("Deleted branch {branch} in {repo_path}"));
+            }
+        }
+        Operation::CloneCache {
+            repo_url,
+            cache_path,
+        } => {
+            let path = Path::new(cache_path);
+            let in_use = is_repository_root(path)
+                && list_worktrees(path).is_ok_and(|worktrees| worktrees.len() > 1);
+            if in_use {
+                details.push(format!("Kept repo cache {cache_path}, which has worktrees"));
+            } else if path.exists() {
+                fs::remove_dir_all(path)?;
+                details.push(format!(
+                    "Removed incomplete clone of {repo_url} at {cache_path}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` holds a repository of its own. Git commands run in a
+/// half-made clone would otherwise act on any repository enclosing it.
+fn is_repository_root(path: &Path) -> bool {
+    let (Ok(root), Ok(git_dir)) = (
+        canonicalize_path(path),
+        run_git(path, &["rev-parse", "--absolute-git-dir"]),
+    ) else {
+        return false;
+    };
+    Path::new(&git_dir).starts_with(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::{begin_operation, journal_path, recover_interrupted_operations, Operation};
+    use crate::workspaces::model::WorkspaceStartupNoticeKind;
+    use crate::workspaces::test_support::{git, init_repo};
+    use crate::workspaces::WorkspaceStore;
+
+    #[test]
+    fn finished_operations_leave_no_journal() {
+        let dir = tempdir().unwrap();
+        let workspaces_root = dir.path().join("workspaces");
+        let operation = Operation::CloneCache {
+            repo_url: "https://github.com/acme/widgets".to_string(),
+            cache_path: dir.path().join("cache").to_string_lossy().to_string(),
+        };
+
+        let first = begin_operation(&workspaces_root, operation.clone()).unwrap();
+        let second = begin_operation(&workspaces_root, operation).unwrap();
+        first.finish();
+        assert!(journal_path(&workspaces_root).exists());
+        second.finish();
+
+        assert!(!journal_path(&workspaces_root).exists());
+        let store = WorkspaceStore::new(dir.path());
+        assert!(recover_interrupted_operations(dir.path(), &store).is_none());
+    }
+
+    #[test]
+    fn cleans_up_interrupted_worktree_add_and_partial_clone() {
+        let dir = tempdir().unwrap();
+        let workspaces_root = dir.path().join("workspaces");
+        let repo = dir.path().join("repo");
+        init_repo(&repo);
+        let worktree = workspaces_root.join("kat-1-ab12");
+        let partial_clone = dir.path().join("repo-cache").join("acme__widgets");
+        fs::create_dir_all(&partial_clone).unwrap();
+        git(&partial_clone, &["init"]);
+
+        // Quit after `worktree add --no-checkout`, before the sparse checkout.
+        let _add = begin_operation(
+            &workspaces_root,
+            Operation::AddWorktree {
+                repo_path: repo.to_string_lossy().to_string(),
+                worktree_path: worktree.to_string_lossy().to_string(),
+                branch: "kat-1".to_string(),
+                creates_branch: true,
+            },
+        )
+        .unwrap();
+        git(
+            &repo,
+            &[
+                "worktree",
+                "add",
+                "--no-checkout",
+                worktree.to_str().unwrap(),
+                "-b",
+                "kat-1",
+            ],
+        );
+        let _clone = begin_operation(
+            &workspaces_root,
+            Operation::CloneCache {
+                repo_url: "https://github.com/acme/widgets".to_string(),
+                cache_path: partial_clone.to_string_lossy().to_string(),
+            },
+        )
+        .unwrap();
+
+        let store = WorkspaceStore::new(dir.path());
+        let notice = recover_interrupted_operations(dir.path(), &store).unwrap();
+
+        assert_eq!(
+            notice.kind,
+            WorkspaceStartupNoticeKind::InterruptedOperationsRecovered
+        );
+        assert_eq!(notice.details.len(), 3, "{:?}", notice.details);
+        assert!(!worktree.exists());
+        assert!(!partial_clone.exists());
+        assert_eq!(git(&repo, &["branch", "--list", "kat-1"]), "");
+        assert_eq!(git(&repo, &["worktree", "list"]).lines().count(), 1);
+        assert!(!journal_path(&workspaces_root).exists());
+    }
+
+    #[test]
+    fn rolls_forward_clone_interrupted_during_checkout() {
+        let dir = tempdir().unwrap();
+        let origin = dir.path().join("origin");
+        init_repo(&origin);
+        fs::write(origin.join("README.md"), "# fixture\n").unwrap();
+        git(&origin, &["add", "."]);
+        git(&origin, &["commit", "-m", "readme"]);
+        let cache = dir.path().join("repo-cache").join("acme__widgets");
+        git(
+            dir.path(),
+            &["clone", origin.to_str().unwrap(), cache.to_str().unwrap()],
+        );
+        fs::remove_file(cache.join("README.md")).unwrap();
+        let _clone = begin_operation(
+            &dir.path().join("workspaces"),
+            Operation::CloneCache {
+                repo_url: "https://github.com/acme/widgets".to_string(),
+                cache_path: cache.to_string_lossy().to_string(),
+            },
+        )
+        .unwrap();
+
+        let store = WorkspaceStore::new(dir.path());
+        let notice = recover_interrupted_operations(dir.path(), &store).unwrap();
+
+        assert_eq!(notice.details.len(), 1, "{:?}", notice.details);
+        assert!(cache.join("README.md").exists());
+    }
+}
@@ -0,0 +1,371 @@
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::git_changes::is_protected_branch;
+use super::git_github::{read_pull_request_status, workspace_repo_id};
+use super::git_local::{branch_exists, run_git};
+use super::git_progress::GitMonitor;
+use super::model::{
+    AutoArchivePolicy, PullRequestState, PullRequestStatus, Workspace, WorkspaceStatus,
+};
+use super::{WorkspaceError, WorkspaceState, PULL_REQUEST_EVENT};
+
+/// Delay between two polls of every tracked pull request.
+pub const PULL_REQUEST_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Payload of `PULL_REQUEST_EVENT`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequestStatusChange {
+    pub workspace_id: String,
+    pub number: u32,
+    pub previous: Option<PullRequestStatus>,
+    pub status: PullRequestStatus,
+    /// Whether the auto-archive policy archived the workspace on this change.
+    pub archived: bool,
+    /// Why removing the worktree or branch failed. The workspace stays
+    /// archived either way.
+    pub cleanup_error: Option<String>,
+}
+
+/// Polls tracked pull requests on a background thread for the lifetime of
+/// the app, emitting `PULL_REQUEST_EVENT` for every change.
+pub fn spawn_pull_request_watcher(app: AppHandle) {
+    thread::spawn(move || loop {
+        let changes = poll_pull_requests(app.state::<WorkspaceState>().inner());
+        emit_pull_request_changes(&app, &changes);
+        thread::sleep(PULL_REQUEST_POLL_INTERVAL);
+    });
+}
+
+pub fn emit_pull_request_changes(app: &AppHandle, changes: &[PullRequestStatusChange]) {
+    for change in changes {
+        if let Err(err) = app.emit(PULL_REQUEST_EVENT, change.clone()) {
+            eprintln!(
+                "Warning: failed to emit pull request change for {}: {err}",
+                change.workspace_id
+            );
+        }
+    }
+}
+
+/// Reads the state of every non-archived workspace pull request that has not
+/// been merged yet, records it, and applies the auto-archive policy to newly
+/// merged ones. Returns the pull requests whose state changed.
+pub fn poll_pull_requests(state: &WorkspaceState) -> Vec<PullRequestStatusChange> {
+    let tracked = match state.store.lock() {
+        Ok(store) => store
+            .list()
+            .into_iter()
+            .filter(is_tracked)
+            .collect::<Vec<_>>(),
+        Err(_) => return Vec::new(),
+    };
+
+    let mut changes = Vec::new();
+    for workspace in tracked {
+        let Some(number) = workspace.pull_request.as_ref().map(|pr| pr.number) else {
+            continue;
+        };
+        let result = workspace_repo_id(&workspace)
+            .and_then(|repo_id| read_pull_request_status(&repo_id, number))
+            .and_then(|status| record_status(state, &workspace.id, status));
+        match result {
+            Ok(Some(change)) => changes.push(change),
+            Ok(None) => {}
+            Err(err) => eprintln!(
+                "Warning: failed to refresh pull request #{number} for workspace {}: {err}",
+                workspace.id
+            ),
+        }
+    }
+    changes
+}
+
+fn is_tracked(workspace: &Workspace) -> bool {
+    workspace.status != WorkspaceStatus::Archived
+        && workspace.pull_request.as_ref().is_some_and(|pr| {
+            pr.status
+                .as_ref()
+                .is_none_or(|status| status.state != PullRequestState::Merged)
+        })
+}
+
+fn record_status(
+    state: &WorkspaceState,
+    workspace_id: &str,
+    status: PullRequestStatus,
+) -> Result<Option<PullRequestStatusChange>, WorkspaceError> {
+    let mut store = state.store.lock().map_err(|_| {
+        WorkspaceError::InvalidInput(
+            "Workspace state is unavailable. Please restart the application.".to_string(),
+        )
+    })?;
+    let current = store.get(workspace_id)?;
+    let Some(pull_request) = current.pull_request else {
+        return Ok(None);
+    };
+    if pull_request.status.as_ref() == Some(&status) {
+        return Ok(None);
+    }
+
+    let policy = store.auto_archive_policy();
+    let archive = policy.archive_merged
+        && status.state == PullRequestState::Merged
+        && current.status != WorkspaceStatus::Archived
+        && pull_request.is_head_branch(&current.branch);
+    let updated = store.update(workspace_id, |workspace| {
+        if let Some(pull_request) = workspace.pull_request.as_mut() {
+            pull_request.status = Some(status.clone());
+        }
+    })?;
+    if archive {
+        store.archive(workspace_id)?;
+    }
+    store.save()?;
+    drop(store);
+
+    let cleanup_error = if archive {
+        let result = state
+            .repo_locks
+            .lock(Path::new(&updated.repo_root_path), GitMonitor::default())
+            .and_then(|_repo_lock| clean_up_merged_workspace(&updated, policy));
+        if let Ok(mut cache) = state.status_cache.lock() {
+            cache.invalidate(workspace_id);
+        }
+        result.err().map(|err| err.to_string())
+    } else {
+        None
+    };
+    Ok(Some(PullRequestStatusChange {
+        workspace_id: workspace_id.to_string(),
+        number: pull_request.number,
+        previous: pull_request.status,
+        status,
+        archived: archive,
+        cleanup_error,
+    }))
+}
+
+/// Removes the worktree and local branch of an archived workspace as far as
+/// `policy` asks. A worktree with uncommitted changes is left in place.
+fn clean_up_merged_workspace(
+    workspace: &Workspace,
+    policy: AutoArchivePolicy,
+) -> Result<(), WorkspaceError> {
+    let repo_root = Path::new(&workspace.repo_root_path);
+    let worktree_path = Path::new(&workspace.worktree_path);
+    if policy.remove_worktree && worktree_path != repo_root && worktree_path.exists() {
+        run_git(repo_root, &["worktree", "remove", &workspace.worktree_path])?;
+    }
+    if policy.delete_branch
+        && !workspace.branch.is_empty()
+        && branch_exists(repo_root, &workspace.branch)?
+    {
+        if is_protected_branch(repo_root, &workspace.branch, workspace.base_ref.as_deref()) {
+            return Err(WorkspaceError::ProtectedBranch {
+                branch: workspace.branch.clone(),
+            });
+        }
+        // Squash and rebase merges leave the branch unmerged locally.
+        run_git(repo_root, &["branch", "-D", &workspace.branch])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use std::sync::Mutex;
+
+    use tempfile::tempdir;
+
+    use super::{poll_pull_requests, record_status};
+    use crate::workspaces::git_status::StatusCache;
+    use crate::workspaces::model::{
+        build_workspace, AutoArchivePolicy, CheckRollup, PreparedWorkspace, PullRequestState,
+        PullRequestStatus, WorkspacePullRequest, WorkspaceSourceType, WorkspaceStatus,
+    };
+    use crate::workspaces::repo_locks::RepoLocks;
+    use crate::workspaces::{WorkspaceState, WorkspaceStore};
+    use crate::workspaces::test_support::{git, init_repo};
+
+    fn status(state: PullRequestState) -> PullRequestStatus {
+        PullRequestStatus {
+            state,
+            is_draft: false,
+            review_decision: None,
+            checks: CheckRollup::Passing,
+            merged_at: None,
+        }
+    }
+
+    /// A workspace on branch `feature` in a linked worktree, with PR #12.
+    fn workspace_state(root: &Path, policy: AutoArchivePolicy) -> WorkspaceState {
+        let repo = root.join("repo");
+        let worktree = root.join("worktree");
+        init_repo(&repo);
+        git(
+            &repo,
+            &[
+                "worktree",
+                "add",
+                "-b",
+                "feature",
+                worktree.to_str().unwrap(),
+            ],
+        );
+
+        let mut workspace = build_workspace(
+            "ws_1".to_string(),
+            "Feature".to_string(),
+            WorkspaceSourceType::Local,
+            repo.to_string_lossy().to_string(),
+            PreparedWorkspace {
+                repo_root_path: repo.to_string_lossy().to_string(),
+                worktree_path: worktree.to_string_lossy().to_string(),
+                branch: "feature".to_string(),
+                base_ref: "main".to_string(),
+                sparse_paths: Vec::new(),
+            },
+        );
+        workspace.pull_request = Some(WorkspacePullRequest {
+            number: 12,
+            url: "https://github.com/acme/widgets/pull/12".to_string(),
+            head_branch: "feature".to_string(),
+            base_branch: "main".to_string(),
+            head_repository: Some("acme/widgets".to_string()),
+            is_cross_repository: false,
+            maintainer_can_modify: false,
+            push_remote: None,
+            status: Some(status(PullRequestState::Open)),
+        });
+        let mut store = WorkspaceStore::new(root.join("app-data"));
+        store.insert(workspace);
+        store.set_auto_archive_policy(policy);
+        WorkspaceState {
+            app_data_dir: root.join("app-data"),
+            store: Mutex::new(store),
+            startup_notices: Mutex::new(Vec::new()),
+            status_cache: Mutex::new(StatusCache::default()),
+            create_jobs: Mutex::new(HashMap::new()),
+            progress_channels: Mutex::new(HashMap::new()),
+            repo_locks: RepoLocks::default(),
+        }
+    }
+
+    #[test]
+    fn records_changes_once_and_skips_merged_pull_requests() {
+        let dir = tempdir().unwrap();
+        let state = workspace_state(dir.path(), AutoArchivePolicy::default());
+
+        assert!(
+            record_status(&state, "ws_1", status(PullRequestState::Open))
+                .unwrap()
+                .is_none()
+        );
+        let change = record_status(&state, "ws_1", status(PullRequestState::Merged))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(change.previous, Some(status(PullRequestState::Open)));
+        assert!(!change.archived);
+        let workspace = state.store.lock().unwrap().get("ws_1").unwrap();
+        assert_eq!(workspace.status, WorkspaceStatus::Ready);
+        assert_eq!(
+            workspace.pull_request.unwrap().status,
+            Some(status(PullRequestState::Merged))
+        );
+        // Merged pull requests are no longer polled, so nothing reaches `gh`.
+        assert!(poll_pull_requests(&state).is_empty());
+    }
+
+    #[test]
+    fn archives_merged_workspace_and_removes_worktree_and_branch() {
+        let dir = tempdir().unwrap();
+        let state = workspace_state(
+            dir.path(),
+            AutoArchivePolicy {
+                archive_merged: true,
+                remove_worktree: true,
+                delete_branch: true,
+            },
+        );
+
+        let change = record_status(&state, "ws_1", status(PullRequestState::Merged))
+            .unwrap()
+            .unwrap();
+
+        assert!(change.archived);
+        assert_eq!(change.cleanup_error, None);
+        let workspace = state.store.lock().unwrap().get("ws_1").unwrap();
+        assert_eq!(workspace.status, WorkspaceStatus::Archived);
+        assert!(!Path::new(&workspace.worktree_path).exists());
+        let branches = Command::new("git")
+            .arg("-C")
+            .arg(&workspace.repo_root_path)
+            .args(["branch", "--list", "feature"])
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&branches.stdout).trim().is_empty());
+    }
+
+    #[test]
+    fn leaves_workspace_started_from_the_pull_request_head_alone() {
+        let dir = tempdir().unwrap();
+        let state = workspace_state(
+            dir.path(),
+            AutoArchivePolicy {
+                archive_merged: true,
+                remove_worktree: true,
+                delete_branch: true,
+            },
+        );
+        state
+            .store
+            .lock()
+            .unwrap()
+            .update("ws_1", |workspace| {
+                workspace.branch = "feature-follow-up".to_string()
+            })
+            .unwrap();
+
+        let change = record_status(&state, "ws_1", status(PullRequestState::Merged))
+            .unwrap()
+            .unwrap();
+
+        assert!(!change.archived);
+        let workspace = state.store.lock().unwrap().get("ws_1").unwrap();
+        assert_eq!(workspace.status, WorkspaceStatus::Ready);
+        assert!(Path::new(&workspace.worktree_path).exists());
+    }
+
+    #[test]
+    fn keeps_dirty_worktree_but_still_archives() {
+        let dir = tempdir().unwrap();
+        let state = workspace_state(
+            dir.path(),
+            AutoArchivePolicy {
+                archive_merged: true,
+                remove_worktree: true,
+                delete_branch: false,
+            },
+        );
+        fs::write(dir.path().join("worktree").join("notes.txt"), "wip").unwrap();
+
+        let change = record_status(&state, "ws_1", status(PullRequestState::Merged))
+            .unwrap()
+            .unwrap();
+
+        assert!(change.archived);
+        assert!(change.cleanup_error.is_some());
+        assert!(dir.path().join("worktree").join("notes.txt").exists());
+    }
+}
@@ -0,0 +1,428 @@
+use std::io::{Read, Write};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::WorkspaceError;
+
+/// A local git command running longer than this is stuck, usually on a lock.
+pub const LOCAL_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+/// Transfers, and commands that write the whole worktree or object store,
+/// legitimately take a long time on large repositories. On a blobless clone
+/// a checkout fetches its blobs as well.
+pub const LONG_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// `gh` calls are API requests, apart from downloading CI logs.
+pub const GH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Commit hooks may run linters or tests.
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long output is still collected after the command has exited.
+const OUTPUT_DRAIN_GRACE: Duration = Duration::from_millis(500);
+
+/// Stops the subprocesses it was passed to. Clones share one flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// How long a subprocess may run, and what can stop it earlier.
+#[derive(Debug, Clone, Copy)]
+pub struct RunLimits<'a> {
+    pub timeout: Duration,
+    pub cancel: Option<&'a CancelToken>,
+    /// Stops the process once it has written more than this to stdout. The
+    /// returned stdout then holds one byte over the cap, which tells callers
+    /// it was cut.
+    pub max_stdout_bytes: Option<usize>,
+}
+
+impl RunLimits<'_> {
+    pub fn timeout(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            cancel: None,
+            max_stdout_bytes: None,
+        }
+    }
+}
+
+const LONG_RUNNING_GIT_COMMANDS: &[&str] = &[
+    "add",
+    "clone",
+    "fetch",
+    "pull",
+    "push",
+    "ls-remote",
+    "checkout",
+    "switch",
+    "reset",
+    "merge",
+    "rebase",
+    "read-tree",
+    "sparse-checkout",
+    "maintenance",
+    "gc",
+    "repack",
+];
+
+/// `LONG_TIMEOUT` for transfers and for commands that may populate a whole
+/// worktree or repack a repository; `LOCAL_TIMEOUT` for everything else.
+pub fn git_timeout(args: &[&str]) -> Duration {
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        if arg == "-C" || arg == "-c" {
+            args.next();
+        } else if !arg.starts_with('-') {
+            words.push(arg);
+        }
+    }
+    match words.as_slice() {
+        ["worktree", "add" | "move" | "remove", ..] | ["stash", "apply" | "pop", ..] => {
+            LONG_TIMEOUT
+        }
+        [subcommand, ..] if LONG_RUNNING_GIT_COMMANDS.contains(subcommand) => LONG_TIMEOUT,
+        _ => LOCAL_TIMEOUT,
+    }
+}
+
+/// Keeps git and gh from waiting on a prompt nobody can answer, and puts the
+/// process in its own group so that killing it takes its children along,
+/// such as the `git-remote-https` doing a fetch.
+fn configure_command(command: &mut Command) {
+    command
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GH_PROMPT_DISABLED", "1")
+        .stdin(Stdio::null());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+}
+
+/// Runs `command` to completion within `limits`. A non-zero exit is not an
+/// error here; callers read it from the returned output.
+pub fn run_command(
+    command: Command,
+    description: &str,
+    limits: RunLimits<'_>,
+) -> Result<Output, WorkspaceError> {
+    run_command_with(command, description, limits, None, &mut |_| {})
+}
+
+/// `run_command` that also writes `input` to stdin and passes each stderr
+/// line to `on_stderr_line` as it arrives. Lines end with `\n` or with the
+/// `\r` git uses to redraw progress.
+pub fn run_command_with(
+    mut command: Command,
+    description: &str,
+    limits: RunLimits<'_>,
+    input: Option<&[u8]>,
+    on_stderr_line: &mut dyn FnMut(&str),
+) -> Result<Output, WorkspaceError> {
+    configure_command(&mut command);
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    if input.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command.spawn()?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        let input = input.to_vec();
+        // A child that exits without reading its input closes the pipe; the
+        // exit status says why, so the write error is not interesting.
+        thread::spawn(move || stdin.write_all(&input));
+    }
+    let (sender, receiver) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_output(stdout, OutputChunk::Stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_output(stderr, OutputChunk::Stderr, sender.clone());
+    }
+    drop(sender);
+
+    let deadline = Instant::now() + limits.timeout;
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut lines = LineSplitter::default();
+    // Returns how much stdout has been collected so far.
+    let mut collect = |chunk| {
+        match chunk {
+            OutputChunk::Stdout(bytes) => stdout.extend_from_slice(&bytes),
+            OutputChunk::Stderr(bytes) => {
+                lines.push(&bytes, on_stderr_line);
+                stderr.extend_from_slice(&bytes);
+            }
+        }
+        stdout.len()
+    };
+    // The exit status is checked while the pipes are still open: a helper
+    // that git or ssh leaves running in the background keeps them open long
+    // after the command itself is done.
+    let mut pipes_open = true;
+    let mut stdout_len = 0;
+    let status = loop {
+        if pipes_open {
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(chunk) => stdout_len = collect(chunk),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => pipes_open = false,
+            }
+        } else {
+            thread::sleep(POLL_INTERVAL);
+        }
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if limits
+            .max_stdout_bytes
+            .is_some_and(|max_bytes| stdout_len > max_bytes)
+        {
+            kill_process_group(&mut child);
+            break child.wait()?;
+        }
+
+        if limits.cancel.is_some_and(CancelToken::is_cancelled) {
+            kill_process_group(&mut child);
+            return Err(WorkspaceError::Cancelled {
+                command: description.to_string(),
+            });
+        }
+        if Instant::now() >= deadline {
+            kill_process_group(&mut child);
+            return Err(WorkspaceError::TimedOut {
+                command: description.to_string(),
+                timeout_secs: limits.timeout.as_secs(),
+            });
+        }
+    };
+
+    // Output the command wrote just before exiting may still be in the pipes.
+    let drain_deadline = Instant::now() + OUTPUT_DRAIN_GRACE;
+    while let Ok(chunk) =
+        receiver.recv_timeout(drain_deadline.saturating_duration_since(Instant::now()))
+    {
+        collect(chunk);
+    }
+    lines.finish(on_stderr_line);
+    if let Some(max_bytes) = limits.max_stdout_bytes {
+        stdout.truncate(max_bytes + 1);
+    }
+    Ok(Output {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+enum OutputChunk {
+    Stdout(Vec<u8>),
+    Stderr(Vec<u8>),
+}
+
+fn forward_output(
+    mut pipe: impl Read + Send + 'static,
+    chunk: fn(Vec<u8>) -> OutputChunk,
+    sender: mpsc::Sender<OutputChunk>,
+) {
+    thread::spawn(move || {
+        let mut buffer = [0u8; 8192];
+        while let Ok(read) = pipe.read(&mut buffer) {
+            if read == 0 || sender.send(chunk(buffer[..read].to_vec())).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn kill_process_group(child: &mut Child) {
+    // `configure_command` made the child a group leader, so its group ID is
+    // its PID.
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[derive(Default)]
+struct LineSplitter {
+    pending: Vec<u8>,
+}
+
+impl LineSplitter {
+    fn push(&mut self, bytes: &[u8], on_line: &mut dyn FnMut(&str)) {
+        for &byte in bytes {
+            if byte == b'\r' || byte == b'\n' {
+                on_line(&String::from_utf8_lossy(&self.pending));
+                self.pending.clear();
+            } else {
+                self.pending.push(byte);
+            }
+        }
+    }
+
+    fn finish(&mut self, on_line: &mut dyn FnMut(&str)) {
+        if !self.pending.is_empty() {
+            on_line(&String::from_utf8_lossy(&self.pending));
+            self.pending.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{git_timeout, run_command, run_command_with, CancelToken, RunLimits};
+    use super::{LineSplitter, LOCAL_TIMEOUT, LONG_TIMEOUT};
+    use crate::workspaces::WorkspaceError;
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[test]
+    fn collects_output_stdin_and_stderr_lines() {
+        let mut lines = Vec::new();
+        let output = run_command_with(
+            shell("cat; printf 'one\\rtwo\\n' >&2; echo \"$GIT_TERMINAL_PROMPT\"; exit 3"),
+            "sh",
+            RunLimits::timeout(LOCAL_TIMEOUT),
+            Some(b"input\n"),
+            &mut |line| lines.push(line.to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "input\n0\n");
+        assert_eq!(lines, vec!["one".to_string(), "two".to_string()]);
+    }
+
+    #[test]
+    fn times_out_and_kills_the_process_group() {
+        let started = Instant::now();
+        // The background sleep holds stdout open; it only goes away if the
+        // whole group is killed.
+        let err = run_command(
+            shell("sleep 30 & sleep 30"),
+            "sleep",
+            RunLimits::timeout(Duration::from_millis(200)),
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, WorkspaceError::TimedOut { .. }));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn returns_once_the_command_exits_even_if_a_helper_keeps_its_pipes() {
+        let started = Instant::now();
+        let output = run_command(
+            shell("sleep 5 & echo done"),
+            "sh",
+            RunLimits::timeout(Duration::from_secs(3)),
+        )
+        .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn stops_a_command_once_stdout_exceeds_the_cap() {
+        let output = run_command(
+            shell("yes"),
+            "yes",
+            RunLimits {
+                max_stdout_bytes: Some(1000),
+                ..RunLimits::timeout(Duration::from_secs(10))
+            },
+        )
+        .unwrap();
+
+        assert!(!output.status.success());
+        assert_eq!(output.stdout.len(), 1001);
+    }
+
+    #[test]
+    fn cancels_from_another_thread() {
+        let token = CancelToken::default();
+        let canceller = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+
+        let err = run_command(
+            shell("sleep 30"),
+            "sleep",
+            RunLimits {
+                cancel: Some(&token),
+                ..RunLimits::timeout(LOCAL_TIMEOUT)
+            },
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, WorkspaceError::Cancelled { command } if command == "sleep"));
+    }
+
+    #[test]
+    fn picks_timeout_by_git_subcommand() {
+        assert_eq!(git_timeout(&["fetch", "--all"]), LONG_TIMEOUT);
+        assert_eq!(git_timeout(&["--no-pager", "push", "origin"]), LONG_TIMEOUT);
+        assert_eq!(
+            git_timeout(&["checkout", "--progress", "--force", "main"]),
+            LONG_TIMEOUT
+        );
+        assert_eq!(
+            git_timeout(&["maintenance", "run", "--task=gc"]),
+            LONG_TIMEOUT
+        );
+        assert_eq!(git_timeout(&["worktree", "add", "../wt"]), LONG_TIMEOUT);
+        assert_eq!(git_timeout(&["stash", "apply", "stash@{0}"]), LONG_TIMEOUT);
+        assert_eq!(
+            git_timeout(&["-c", "core.editor=true", "rebase", "--continue"]),
+            LONG_TIMEOUT
+        );
+        assert_eq!(git_timeout(&["worktree", "list"]), LOCAL_TIMEOUT);
+        assert_eq!(git_timeout(&["status", "--porcelain"]), LOCAL_TIMEOUT);
+        assert_eq!(git_timeout(&["branch", "fetch"]), LOCAL_TIMEOUT);
+    }
+
+    #[test]
+    fn splits_redrawn_progress_lines_across_chunks() {
+        let mut lines = Vec::new();
+        let mut splitter = LineSplitter::default();
+        let mut on_line = |line: &str| lines.push(line.to_string());
+
+        splitter.push(b"Receiving objects:  50% (1/2)\rReceiving ", &mut on_line);
+        splitter.push(b"objects: 100% (2/2), done.\nok", &mut on_line);
+        splitter.finish(&mut on_line);
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "Receiving objects: 100% (2/2), done.");
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::path::Path;
+
+use super::git_github::github_repo_url_from_remote;
+use super::git_local::{
+    canonicalize_path, guess_base_ref, list_worktrees, origin_remote_url, read_sparse_paths,
+};
+use super::model::{
+    build_workspace, next_workspace_id, now_iso8601, PreparedWorkspace, Workspace,
+    WorkspaceSourceType, WorkspaceStartupNotice, WorkspaceStartupNoticeKind,
+};
+use super::store::REGISTRY_BACKUP_COUNT;
+use super::{WorkspaceError, WorkspaceStore};
+
+/// Loads the workspace registry, recovering from a malformed `workspaces.json`
+/// instead of failing. The corrupt file is moved aside, then the newest
+/// readable backup is restored; if none is usable the registry is rebuilt from
+/// the worktrees found under `<app_data>/workspaces`. Only deserialization
+/// errors trigger recovery, IO errors are returned as-is.
+pub fn load_store_with_recovery(
+    app_data_dir: &Path,
+) -> Result<(WorkspaceStore, Option<WorkspaceStartupNotice>), WorkspaceError> {
+    let parse_error = match WorkspaceStore::load(app_data_dir) {
+        Ok(store) => return Ok((store, None)),
+        Err(WorkspaceError::Serde(err)) => err,
+        Err(err) => return Err(err),
+    };
+
+    let mut store = WorkspaceStore::new(app_data_dir);
+    let quarantined = store.quarantine_registry()?;
+    let mut details = vec![
+        format!("Registry could not be parsed: {parse_error}"),
+        format!("Corrupt registry moved to {}", quarantined.display()),
+    ];
+
+    for index in 1..=REGISTRY_BACKUP_COUNT {
+        match store.restore_backup(index) {
+            Ok(()) => {
+                details.push(format!(
+                    "Restored {} workspace(s) from {}",
+                    store.list().len(),
+                    store.backup_path(index).display()
+                ));
+                let notice = startup_notice(
+                    WorkspaceStartupNoticeKind::RegistryRestoredFromBackup,
+                    format!(
+                        "The workspace registry was corrupt and has been restored from backup #{index}."
+                    ),
+                    details,
+                );
+                return Ok((store, Some(notice)));
+            }
+            Err(WorkspaceError::NotFound(_)) => {}
+            Err(err) => details.push(format!("Backup #{index} is unusable: {err}")),
+        }
+    }
+
+    let rebuilt = rebuild_from_worktrees(&app_data_dir.join("workspaces"));
+    let recovered_count = rebuilt.len();
+    for workspace in rebuilt {
+        details.push(format!(
+            "Recovered {} on branch {}",
+            workspace.worktree_path, workspace.branch
+        ));
+        store.insert(workspace);
+    }
+    store.save()?;
+
+    let notice = startup_notice(
+        WorkspaceStartupNoticeKind::RegistryRebuilt,
+        format!(
+            "The workspace registry was corrupt and no usable backup was found. \
+             {recovered_count} workspace(s) were recovered from worktrees on disk."
+        ),
+        details,
+    );
+    Ok((store, Some(notice)))
+}
+
+/// Builds a `Workspace` record for an existing linked worktree without
+/// touching it. Returns `None` when `worktree_path` is not a linked worktree
+/// (for example a plain clone or an unrelated directory).
+pub fn adopt_worktree(
+    worktree_path: &Path,
+    name: Option<String>,
+) -> Result<Option<Workspace>, WorkspaceError> {
+    let canonical_worktree = canonicalize_path(worktree_path)?;
+    let worktrees = list_worktrees(worktree_path)?;
+    let Some(main_worktree) = worktrees.first() else {
+        return Ok(None);
+    };
+    let repo_root_path = canonicalize_path(&main_worktree.path)?;
+    if repo_root_path == canonical_worktree {
+        return Ok(None);
+    }
+    let Some(entry) = worktrees.iter().find(|entry| {
+        canonicalize_path(&entry.path).ok().as_deref() == Some(canonical_worktree.as_str())
+    }) else {
+        return Ok(None);
+    };
+
+    let repo_root = Path::new(&repo_root_path);
+    let base_ref = guess_base_ref(repo_root, entry.branch.as_deref())?;
+    let (source_type, source) = match origin_remote_url(repo_root)
+        .as_deref()
+        .and_then(github_repo_url_from_remote)
+    {
+        Some(repo_url) => (WorkspaceSourceType::Github, repo_url),
+        None => (WorkspaceSourceType::Local, repo_root_path.clone()),
+    };
+    let name = name
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| {
+            worktree_path
+                .file_name()
+                .map(|value| value.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Workspace".to_string())
+        });
+
+    Ok(Some(build_workspace(
+        next_workspace_id(),
+        name,
+        source_type,
+        source,
+        PreparedWorkspace {
+            repo_root_path,
+            worktree_path: canonical_worktree,
+            branch: entry.branch.clone().unwrap_or_else(|| "HEAD".to_string()),
+            base_ref,
+            sparse_paths: read_sparse_paths(worktree_path),
+        },
+    )))
+}
+
+fn rebuild_from_worktrees(workspaces_root: &Path) -> Vec<Workspace> {
+    let Ok(entries) = fs::read_dir(workspaces_root) else {
+        return Vec::new();
+    };
+    let mut candidates = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    candidates.sort();
+
+    candidates
+        .iter()
+        .filter_map(|path| adopt_worktree(path, None).ok().flatten())
+        .collect()
+}
+
+pub fn startup_notice(
+    kind: WorkspaceStartupNoticeKind,
+    message: String,
+    details: Vec<String>,
+) -> WorkspaceStartupNotice {
+    WorkspaceStartupNotice {
+        kind,
+        message,
+        details,
+        created_at: now_iso8601(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::load_store_with_recovery;
+    use crate::workspaces::git_local::create_local_workspace;
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::{
+        now_iso8601, Workspace, WorkspaceBranch, WorkspaceSourceType, WorkspaceStartupNoticeKind,
+        WorkspaceStatus,
+    };
+    use crate::workspaces::WorkspaceStore;
+    use crate::workspaces::test_support::git;
+
+    fn sample_workspace(id: &str) -> Workspace {
+        Workspace {
+            id: id.to_string(),
+            name: "KAT-154".to_string(),
+            source_type: WorkspaceSourceType::Local,
+            source: "/tmp/repo".to_string(),
+            repo_root_path: "/tmp/repo".to_string(),
+            worktree_path: "/tmp/repo.worktrees/kat-154".to_string(),
+            branch: "workspace/kat-154-ws1".to_string(),
+            base_ref: Some("main".to_string()),
+            status: WorkspaceStatus::Ready,
+            created_at: now_iso8601(),
+            updated_at: now_iso8601(),
+            last_opened_at: None,
+            error_message: None,
+            pull_request: None,
+            source_pull_request: None,
+            parent_workspace_id: None,
+            sparse_paths: Vec::new(),
+            create_job: None,
+        }
+    }
+
+    fn corrupt_registry(store: &WorkspaceStore) {
+        fs::write(store.registry_path(), "{ \"workspaces\": [").unwrap();
+    }
+
+    fn quarantined_files(store: &WorkspaceStore) -> usize {
+        fs::read_dir(store.registry_path().parent().unwrap())
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("workspaces.json.corrupt-")
+            })
+            .count()
+    }
+
+    #[test]
+    fn loads_intact_registry_without_notice() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.insert(sample_workspace("ws_1"));
+        store.save().unwrap();
+
+        let (loaded, notice) = load_store_with_recovery(dir.path()).unwrap();
+        assert_eq!(loaded.list().len(), 1);
+        assert!(notice.is_none());
+    }
+
+    #[test]
+    fn restores_newest_readable_backup_when_registry_is_corrupt() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.insert(sample_workspace("ws_1"));
+        store.save().unwrap();
+        store.insert(sample_workspace("ws_2"));
+        store.save().unwrap();
+        store.save().unwrap();
+        fs::write(store.backup_path(1), "not json").unwrap();
+        corrupt_registry(&store);
+
+        let (recovered, notice) = load_store_with_recovery(dir.path()).unwrap();
+
+        let notice = notice.unwrap();
+        assert_eq!(
+            notice.kind,
+            WorkspaceStartupNoticeKind::RegistryRestoredFromBackup
+        );
+        assert!(notice.message.contains("#2"));
+        assert_eq!(recovered.list().len(), 1);
+        assert_eq!(quarantined_files(&recovered), 1);
+        assert_eq!(WorkspaceStore::load(dir.path()).unwrap().list().len(), 1);
+    }
+
+    #[test]
+    fn rebuilds_registry_from_worktrees_when_no_backup_is_usable() {
+        let dir = tempdir().unwrap();
+        let repo_path = dir.path().join("repo");
+        fs::create_dir_all(&repo_path).unwrap();
+        git(&repo_path, &["init"]);
+        git(&repo_path, &["checkout", "-B", "main"]);
+        fs::write(repo_path.join("README.md"), "# fixture\n").unwrap();
+        git(&repo_path, &["add", "."]);
+        git(&repo_path, &["commit", "-m", "initial"]);
+
+        let app_data_dir = dir.path().join("app-data");
+        let created = create_local_workspace(
+            &repo_path,
+            "KAT-154",
+            WorkspaceBranch::New(None),
+            None,
+            &[],
+            "ab12",
+            &app_data_dir.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+        let store = WorkspaceStore::new(&app_data_dir);
+        corrupt_registry(&store);
+
+        let (recovered, notice) = load_store_with_recovery(&app_data_dir).unwrap();
+
+        assert_eq!(
+            notice.unwrap().kind,
+            WorkspaceStartupNoticeKind::RegistryRebuilt
+        );
+        let workspaces = recovered.list();
+        assert_eq!(workspaces.len(), 1);
+        assert_eq!(workspaces[0].worktree_path, created.worktree_path);
+        assert_eq!(workspaces[0].branch, created.branch);
+        assert_eq!(workspaces[0].repo_root_path, created.repo_root_path);
+        assert_eq!(workspaces[0].source_type, WorkspaceSourceType::Local);
+        assert_eq!(quarantined_files(&recovered), 1);
+    }
+}
@@ -0,0 +1,335 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::git_local::{branch_exists, canonicalize_path, run_git};
+use super::model::{
+    derive_workspace_branch_name, slugify_name, workspace_suffix, PullRequestState, Workspace,
+};
+use super::WorkspaceError;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceRenameInput {
+    pub workspace_id: String,
+    pub name: String,
+    /// Rename the branch to `branch_name`, or to the name derived from the
+    /// new display name when unset.
+    #[serde(default)]
+    pub rename_branch: bool,
+    #[serde(default)]
+    pub branch_name: Option<String>,
+    /// Move the worktree folder to match the new display name.
+    #[serde(default)]
+    pub move_worktree: bool,
+    /// Rename the branch even though a pull request is open for it.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Git changes made by `rename_worktree`, kept so they can be undone if the
+/// registry cannot be updated afterwards.
+#[derive(Debug, Clone)]
+pub struct AppliedRename {
+    repo_root: PathBuf,
+    name: String,
+    branch: Option<(String, String)>,
+    worktree: Option<(String, String)>,
+}
+
+impl AppliedRename {
+    pub fn apply_to(&self, workspace: &mut Workspace) {
+        workspace.name = self.name.clone();
+        if let Some((_, branch)) = &self.branch {
+            workspace.branch = branch.clone();
+        }
+        if let Some((_, worktree_path)) = &self.worktree {
+            workspace.worktree_path = worktree_path.clone();
+        }
+    }
+
+    /// Moves the worktree and branch back, in reverse order.
+    pub fn rollback(&self) -> Result<(), WorkspaceError> {
+        if let Some((from, to)) = &self.worktree {
+            run_git(&self.repo_root, &["worktree", "move", to, from])?;
+        }
+        if let Some((from, to)) = &self.branch {
+            run_git(&self.repo_root, &["branch", "-m", to, from])?;
+        }
+        Ok(())
+    }
+}
+
+/// Renames the workspace branch and moves its worktree as requested by
+/// `input`. If a step fails, the steps already done are rolled back.
+pub fn rename_worktree(
+    workspace: &Workspace,
+    input: &WorkspaceRenameInput,
+) -> Result<AppliedRename, WorkspaceError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(WorkspaceError::InvalidInput(
+            "Workspace name must not be empty".to_string(),
+        ));
+    }
+    let repo_root = PathBuf::from(&workspace.repo_root_path);
+    let suffix = workspace_suffix(&workspace.id);
+
+    let new_branch = if input.rename_branch {
+        let branch = input
+            .branch_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| derive_workspace_branch_name(name, suffix));
+        Some(branch).filter(|branch| *branch != workspace.branch)
+    } else {
+        None
+    };
+    if let Some(branch) = &new_branch {
+        check_branch_rename(workspace, &repo_root, branch, input.force)?;
+    }
+
+    let new_worktree = if input.move_worktree {
+        let current = Path::new(&workspace.worktree_path);
+        let parent = current.parent().ok_or_else(|| {
+            WorkspaceError::InvalidInput(format!(
+                "Worktree has no parent folder: {}",
+                workspace.worktree_path
+            ))
+        })?;
+        Some(parent.join(format!("{}-{suffix}", slugify_name(name)))).filter(|path| path != current)
+    } else {
+        None
+    };
+    if let Some(path) = &new_worktree {
+        if Path::new(&workspace.worktree_path) == repo_root {
+            return Err(WorkspaceError::InvalidInput(
+                "The main worktree of a repository cannot be moved".to_string(),
+            ));
+        }
+        if path.exists() {
+            return Err(WorkspaceError::InvalidInput(format!(
+                "Worktree path already exists: {}",
+                path.display()
+            )));
+        }
+    }
+
+    let mut applied = AppliedRename {
+        repo_root,
+        name: name.to_string(),
+        branch: None,
+        worktree: None,
+    };
+    if let Some(branch) = new_branch {
+        run_git(
+            &applied.repo_root,
+            &["branch", "-m", &workspace.branch, &branch],
+        )?;
+        applied.branch = Some((workspace.branch.clone(), branch));
+    }
+    if let Some(path) = new_worktree {
+        let moved = run_git(
+            &applied.repo_root,
+            &[
+                "worktree",
+                "move",
+                &workspace.worktree_path,
+                path.to_string_lossy().as_ref(),
+            ],
+        )
+        .and_then(|_| canonicalize_path(&path));
+        match moved {
+            Ok(worktree_path) => {
+                applied.worktree = Some((workspace.worktree_path.clone(), worktree_path));
+            }
+            Err(err) => {
+                if let Err(rollback_err) = applied.rollback() {
+                    eprintln!("Warning: failed to roll back workspace rename: {rollback_err}");
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(applied)
+}
+
+fn check_branch_rename(
+    workspace: &Workspace,
+    repo_root: &Path,
+    branch: &str,
+    force: bool,
+) -> Result<(), WorkspaceError> {
+    if workspace.branch.is_empty() || !branch_exists(repo_root, &workspace.branch)? {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "Workspace branch not found: {}",
+            workspace.branch
+        )));
+    }
+    if branch == "main" || branch == "master" {
+        return Err(WorkspaceError::InvalidInput(
+            "Workspace branch cannot be main/master".to_string(),
+        ));
+    }
+    run_git(repo_root, &["check-ref-format", "--branch", branch])
+        .map_err(|_| WorkspaceError::InvalidInput(format!("Invalid branch name: {branch}")))?;
+    if branch_exists(repo_root, branch)? {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "Branch already exists: {branch}"
+        )));
+    }
+
+    let open_pull_request = workspace.pull_request.as_ref().filter(|pr| {
+        pr.is_head_branch(&workspace.branch)
+            && pr
+                .status
+                .as_ref()
+                .is_none_or(|status| status.state == PullRequestState::Open)
+    });
+    if let Some(pr) = open_pull_request.filter(|_| !force) {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "Pull request #{} is open for {}. Renaming the branch would detach it from the \
+             pull request; rename with force to do it anyway.",
+            pr.number, workspace.branch
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::{rename_worktree, WorkspaceRenameInput};
+    use crate::workspaces::git_local::create_local_workspace;
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::{
+        build_workspace, Workspace, WorkspaceBranch, WorkspacePullRequest, WorkspaceSourceType,
+    };
+    use crate::workspaces::test_support::{git, init_repo};
+
+    fn fixture(root: &Path) -> Workspace {
+        let repo = root.join("repo");
+        init_repo(&repo);
+        let prepared = create_local_workspace(
+            &repo,
+            "Old name",
+            WorkspaceBranch::New(None),
+            Some("main".to_string()),
+            &[],
+            "ab12",
+            &root.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+        build_workspace(
+            "ws_ab12cd".to_string(),
+            "Old name".to_string(),
+            WorkspaceSourceType::Local,
+            repo.to_string_lossy().to_string(),
+            prepared,
+        )
+    }
+
+    fn input(rename_branch: bool, move_worktree: bool) -> WorkspaceRenameInput {
+        WorkspaceRenameInput {
+            workspace_id: "ws_ab12cd".to_string(),
+            name: "New name".to_string(),
+            rename_branch,
+            branch_name: None,
+            move_worktree,
+            force: false,
+        }
+    }
+
+    #[test]
+    fn renames_branch_and_moves_worktree() {
+        let dir = tempdir().unwrap();
+        let mut workspace = fixture(dir.path());
+
+        let applied = rename_worktree(&workspace, &input(true, true)).unwrap();
+        applied.apply_to(&mut workspace);
+
+        assert_eq!(workspace.name, "New name");
+        assert_eq!(workspace.branch, "workspace/new-name-ab12");
+        assert!(workspace.worktree_path.ends_with("new-name-ab12"));
+        assert_eq!(
+            git(
+                Path::new(&workspace.worktree_path),
+                &["symbolic-ref", "--short", "HEAD"]
+            ),
+            "workspace/new-name-ab12"
+        );
+    }
+
+    #[test]
+    fn rolls_back_branch_rename_when_worktree_move_fails() {
+        let dir = tempdir().unwrap();
+        let workspace = fixture(dir.path());
+        let repo = Path::new(&workspace.repo_root_path);
+        // `git worktree move` refuses locked worktrees.
+        git(repo, &["worktree", "lock", &workspace.worktree_path]);
+
+        assert!(rename_worktree(&workspace, &input(true, true)).is_err());
+
+        assert_eq!(
+            git(
+                Path::new(&workspace.worktree_path),
+                &["symbolic-ref", "--short", "HEAD"]
+            ),
+            workspace.branch
+        );
+        assert_eq!(
+            git(repo, &["branch", "--list", "workspace/new-name-ab12"]),
+            ""
+        );
+    }
+
+    #[test]
+    fn blocks_branch_rename_while_pull_request_is_open_unless_forced() {
+        let dir = tempdir().unwrap();
+        let mut workspace = fixture(dir.path());
+        workspace.pull_request = Some(WorkspacePullRequest {
+            number: 3,
+            url: "https://github.com/acme/widgets/pull/3".to_string(),
+            head_branch: workspace.branch.clone(),
+            base_branch: "main".to_string(),
+            head_repository: Some("acme/widgets".to_string()),
+            is_cross_repository: false,
+            maintainer_can_modify: false,
+            push_remote: None,
+            status: None,
+        });
+
+        let err = rename_worktree(&workspace, &input(true, false)).unwrap_err();
+        assert!(err.to_string().contains("#3"));
+
+        let mut forced = input(true, false);
+        forced.force = true;
+        assert!(rename_worktree(&workspace, &forced).is_ok());
+    }
+
+    #[test]
+    fn allows_branch_rename_when_pull_request_is_for_another_branch() {
+        let dir = tempdir().unwrap();
+        let mut workspace = fixture(dir.path());
+        workspace.pull_request = Some(WorkspacePullRequest {
+            number: 3,
+            url: "https://github.com/acme/widgets/pull/3".to_string(),
+            head_branch: "feature/other".to_string(),
+            base_branch: "main".to_string(),
+            head_repository: Some("acme/widgets".to_string()),
+            is_cross_repository: false,
+            maintainer_can_modify: false,
+            push_remote: None,
+            status: None,
+        });
+
+        assert!(rename_worktree(&workspace, &input(true, false)).is_ok());
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use super::git_github::{github_repo_url_from_remote, repo_id_from_url};
+use super::git_local::{list_worktrees, origin_remote_url, run_git};
+use super::git_progress::GitMonitor;
+use super::model::{Workspace, WorkspaceSourceType};
+use super::repo_locks::RepoLocks;
+use super::WorkspaceError;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedRepo {
+    pub path: String,
+    /// `owner/repo` of the cache's github.com origin.
+    pub repo_id: Option<String>,
+    pub size_bytes: u64,
+    pub last_fetched_at: Option<String>,
+    /// Registered workspaces whose worktrees hang off this cache, archived
+    /// ones included.
+    pub workspace_ids: Vec<String>,
+    pub evictable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorktreeUsage {
+    pub workspace_id: String,
+    pub worktree_path: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheInventory {
+    pub caches: Vec<CachedRepo>,
+    pub worktrees: Vec<WorktreeUsage>,
+    pub cache_bytes: u64,
+    pub worktree_bytes: u64,
+}
+
+/// Lists every repo cache with its disk usage, along with the size of each
+/// workspace worktree. Walks the whole tree of each, so run it off the main
+/// thread.
+pub fn read_cache_inventory(app_data_dir: &Path, workspaces: &[Workspace]) -> CacheInventory {
+    let caches = cache_paths(app_data_dir, workspaces)
+        .into_iter()
+        .filter_map(|path| read_cached_repo(&path, workspaces))
+        .collect::<Vec<_>>();
+    let worktrees = workspaces
+        .iter()
+        .filter(|workspace| workspace.worktree_path != workspace.repo_root_path)
+        .filter(|workspace| Path::new(&workspace.worktree_path).exists())
+        .map(|workspace| WorktreeUsage {
+            workspace_id: workspace.id.clone(),
+            worktree_path: workspace.worktree_path.clone(),
+            size_bytes: dir_size(Path::new(&workspace.worktree_path)),
+        })
+        .collect::<Vec<_>>();
+
+    CacheInventory {
+        cache_bytes: caches.iter().map(|cache| cache.size_bytes).sum(),
+        worktree_bytes: worktrees.iter().map(|worktree| worktree.size_bytes).sum(),
+        caches,
+        worktrees,
+    }
+}
+
+/// The clones under `repo-cache/`, plus the repositories of GitHub
+/// workspaces cloned into a custom clone root.
+fn cache_paths(app_data_dir: &Path, workspaces: &[Workspace]) -> BTreeSet<PathBuf> {
+    let mut paths = BTreeSet::new();
+    if let Ok(kinds) = fs::read_dir(app_data_dir.join("repo-cache")) {
+        for kind in kinds.flatten() {
+            let Ok(entries) = fs::read_dir(kind.path()) else {
+                continue;
+            };
+            for entry in entries.flatten().filter(|entry| entry.path().is_dir()) {
+                paths.insert(fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path()));
+            }
+        }
+    }
+    paths.extend(
+        workspaces
+            .iter()
+            .filter(|workspace| workspace.source_type == WorkspaceSourceType::Github)
+            .map(|workspace| PathBuf::from(&workspace.repo_root_path))
+            .filter(|path| path.exists()),
+    );
+    paths
+}
+
+fn read_cached_repo(path: &Path, workspaces: &[Workspace]) -> Option<CachedRepo> {
+    let git_dir = run_git(path, &["rev-parse", "--absolute-git-dir"]).ok()?;
+    let path_string = path.to_string_lossy().to_string();
+    let workspace_ids = workspaces
+        .iter()
+        .filter(|workspace| workspace.repo_root_path == path_string)
+        .map(|workspace| workspace.id.clone())
+        .collect::<Vec<_>>();
+
+    Some(CachedRepo {
+        repo_id: origin_remote_url(path)
+            .and_then(|url| github_repo_url_from_remote(&url))
+            .and_then(|url| repo_id_from_url(&url).ok()),
+        size_bytes: dir_size(path),
+        last_fetched_at: last_fetched_at(Path::new(&git_dir)),
+        evictable: workspace_ids.is_empty(),
+        workspace_ids,
+        path: path_string,
+    })
+}
+
+/// `FETCH_HEAD` is rewritten by every fetch; a cache that was cloned but
+/// never fetched since only has the `packed-refs` written by the clone.
+fn last_fetched_at(git_dir: &Path) -> Option<String> {
+    ["FETCH_HEAD", "packed-refs"]
+        .iter()
+        .find_map(|name| fs::metadata(git_dir.join(name)).ok()?.modified().ok())
+        .map(|modified| {
+            DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Millis, true)
+        })
+}
+
+/// Apparent size of everything under `path`. Symlinks are not followed.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|entry| dir_size(&entry.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Prunes stale worktree entries and repacks the cache.
+pub fn maintain_cache(cache_path: &Path) -> Result<(), WorkspaceError> {
+    run_git(cache_path, &["worktree", "prune"])?;
+    run_git(cache_path, &["maintenance", "run", "--task=gc"]).map(|_| ())
+}
+
+/// Deletes a cache that no workspace uses. Worktrees added to the cache
+/// outside the app keep it alive as well.
+pub fn evict_cache(cache: &CachedRepo) -> Result<(), WorkspaceError> {
+    if !cache.evictable {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "{} is still used by {} workspace(s)",
+            cache.path,
+            cache.workspace_ids.len()
+        )));
+    }
+    let path = Path::new(&cache.path);
+    let has_other_worktrees = list_worktrees(path)?
+        .iter()
+        .skip(1)
+        .any(|entry| !entry.prunable);
+    if has_other_worktrees {
+        return Err(WorkspaceError::InvalidInput(format!(
+            "{} has worktrees that are not workspaces",
+            cache.path
+        )));
+    }
+    fs::remove_dir_all(path)?;
+    Ok(())
+}
+
+/// Evicts unused caches, least recently fetched first, until all caches fit
+/// in `max_bytes`. Returns the evicted caches.
+pub fn enforce_cache_budget(
+    inventory: &CacheInventory,
+    max_bytes: u64,
+    repo_locks: &RepoLocks,
+) -> Vec<CachedRepo> {
+    let mut candidates = inventory
+        .caches
+        .iter()
+        .filter(|cache| cache.evictable)
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| a.last_fetched_at.cmp(&b.last_fetched_at));
+
+    let mut total = inventory.cache_bytes;
+    let mut evicted = Vec::new();
+    for cache in candidates {
+        if total <= max_bytes {
+            break;
+        }
+        // Waits out a creation that is still adding its worktree, which
+        // `evict_cache` then sees.
+        let evicted_cache = repo_locks
+            .lock(Path::new(&cache.path), GitMonitor::default())
+            .and_then(|_repo_lock| evict_cache(cache));
+        match evicted_cache {
+            Ok(()) => {
+                total = total.saturating_sub(cache.size_bytes);
+                evicted.push(cache.clone());
+            }
+            Err(err) => eprintln!("Warning: failed to evict repo cache {}: {err}", cache.path),
+        }
+    }
+    evicted
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use super::{enforce_cache_budget, evict_cache, read_cache_inventory};
+    use crate::workspaces::git_local::create_local_workspace;
+    use crate::workspaces::git_progress::GitMonitor;
+    use crate::workspaces::model::{
+        build_workspace, Workspace, WorkspaceBranch, WorkspaceSourceType,
+    };
+    use crate::workspaces::repo_locks::RepoLocks;
+    use crate::workspaces::test_support::git;
+
+    /// Two caches under `repo-cache/github`, one of them with a workspace.
+    fn fixture(app_data_dir: &Path) -> Workspace {
+        let origin = app_data_dir.join("origin");
+        fs::create_dir_all(&origin).unwrap();
+        git(&origin, &["init", "-b", "main"]);
+        fs::write(origin.join("README.md"), "# fixture\n").unwrap();
+        git(&origin, &["add", "."]);
+        git(&origin, &["commit", "-m", "init"]);
+
+        let cache_root = app_data_dir.join("repo-cache").join("github");
+        fs::create_dir_all(&cache_root).unwrap();
+        for name in ["acme__widgets", "acme__old"] {
+            git(&cache_root, &["clone", origin.to_str().unwrap(), name]);
+        }
+        let prepared = create_local_workspace(
+            &cache_root.join("acme__widgets"),
+            "KAT-1",
+            WorkspaceBranch::New(None),
+            None,
+            &[],
+            "ab12",
+            &app_data_dir.join("workspaces"),
+            GitMonitor::default(),
+        )
+        .unwrap()
+        .prepared;
+        build_workspace(
+            "ws_ab12".to_string(),
+            "KAT-1".to_string(),
+            WorkspaceSourceType::Github,
+            "https://github.com/acme/widgets".to_string(),
+            prepared,
+        )
+    }
+
+    #[test]
+    fn lists_caches_with_linked_workspaces_and_worktree_sizes() {
+        let dir = tempdir().unwrap();
+        let workspace = fixture(dir.path());
+
+        let inventory = read_cache_inventory(dir.path(), std::slice::from_ref(&workspace));
+
+        assert_eq!(inventory.caches.len(), 2);
+        let used = inventory
+            .caches
+            .iter()
+            .find(|cache| cache.path == workspace.repo_root_path)
+            .unwrap();
+        assert_eq!(used.workspace_ids, vec!["ws_ab12".to_string()]);
+        assert!(!used.evictable && used.size_bytes > 0);
+        assert!(used.last_fetched_at.is_some());
+        assert_eq!(inventory.worktrees.len(), 1);
+        assert!(inventory.worktrees[0].size_bytes >= "# fixture\n".len() as u64);
+        assert!(evict_cache(used).is_err());
+    }
+
+    #[test]
+    fn budget_evicts_only_unused_caches() {
+        let dir = tempdir().unwrap();
+        let workspace = fixture(dir.path());
+        let inventory = read_cache_inventory(dir.path(), std::slice::from_ref(&workspace));
+
+        let evicted = enforce_cache_budget(&inventory, 0, &RepoLocks::default());
+
+        assert_eq!(evicted.len(), 1);
+        assert!(evicted[0].path.ends_with("acme__old"));
+        assert!(!Path::new(&evicted[0].path).exists());
+        assert!(Path::new(&workspace.repo_root_path).exists());
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+use super::git_progress::{GitMonitor, GitProgress, GitProgressPhase};
+use super::WorkspaceError;
+
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Serializes operations on the same repository, so that two of them never
+/// fetch into or add worktrees to it at once and trip over git's
+/// `index.lock` and `shallow.lock`. Different repositories do not wait for
+/// each other. Clones share the registry.
+#[derive(Debug, Clone, Default)]
+pub struct RepoLocks {
+    inner: Arc<LockTable>,
+}
+
+#[derive(Debug, Default)]
+struct LockTable {
+    held: Mutex<HashSet<PathBuf>>,
+    released: Condvar,
+}
+
+impl RepoLocks {
+    /// Waits until no other operation holds `repo_path`, then holds it until
+    /// the guard is dropped. Waiting shows up as a `WaitingForRepository`
+    /// progress update, and stops early if the monitor is cancelled.
+    pub fn lock(
+        &self,
+        repo_path: &Path,
+        monitor: GitMonitor<'_>,
+    ) -> Result<RepoLockGuard, WorkspaceError> {
+        let key = fs::canonicalize(repo_path).unwrap_or_else(|_| repo_path.to_path_buf());
+        // The set is only touched while the mutex is held, so it stays
+        // consistent even if a holder panicked.
+        let mut held = self
+            .inner
+            .held
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut waited = false;
+        while held.contains(&key) {
+            if !waited {
+                waited = true;
+                report_wait(monitor, false);
+            }
+            if monitor.cancel.is_some_and(|cancel| cancel.is_cancelled()) {
+                return Err(WorkspaceError::Cancelled {
+                    command: format!("Waiting for {}", repo_path.display()),
+                });
+            }
+            held = self
+                .inner
+                .released
+                .wait_timeout(held, CANCEL_POLL_INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        held.insert(key.clone());
+        drop(held);
+
+        if waited {
+            report_wait(monitor, true);
+        }
+        Ok(RepoLockGuard {
+            locks: self.clone(),
+            key,
+        })
+    }
+}
+
+fn report_wait(monitor: GitMonitor<'_>, done: bool) {
+    if let Some(on_progress) = monitor.on_progress {
+        on_progress(GitProgress {
+            phase: GitProgressPhase::WaitingForRepository,
+            title: "Waiting for another operation on the repository".to_string(),
+            percent: None,
+            current: 0,
+            total: None,
+            transferred_bytes: None,
+            bytes_per_second: None,
+            done,
+        });
+    }
+}
+
+/// Holds a repository locked by `RepoLocks::lock` until dropped.
+#[must_use = "the repository is unlocked as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct RepoLockGuard {
+    locks: RepoLocks,
+    key: PathBuf,
+}
+
+impl Drop for RepoLockGuard {
+    fn drop(&mut self) {
+        let table = &self.locks.inner;
+        table
+            .held
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.key);
+        table.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use tempfile::tempdir;
+
+    use super::RepoLocks;
+    use crate::workspaces::git_progress::{GitMonitor, GitProgressPhase};
+    use crate::workspaces::process::CancelToken;
+    use crate::workspaces::WorkspaceError;
+
+    #[test]
+    fn serializes_the_same_repository_and_reports_the_wait() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().to_path_buf();
+        let other = dir.path().join("other");
+        let locks = RepoLocks::default();
+
+        let guard = locks.lock(&repo, GitMonitor::default()).unwrap();
+        // A different repository is not held up.
+        drop(locks.lock(&other, GitMonitor::default()).unwrap());
+
+        let (sender, receiver) = mpsc::channel();
+        let waiter = {
+            let locks = locks.clone();
+            // The same directory under another spelling is the same key.
+            let repo = repo.join(".");
+            thread::spawn(move || {
+                let updates = RefCell::new(Vec::new());
+                let on_progress = |progress| updates.borrow_mut().push(progress);
+                let monitor = GitMonitor {
+                    on_progress: Some(&on_progress),
+                    cancel: None,
+                };
+                let _guard = locks.lock(&repo, monitor).unwrap();
+                sender.send(()).unwrap();
+                updates.into_inner()
+            })
+        };
+        assert!(receiver.recv_timeout(Duration::from_millis(300)).is_err());
+        drop(guard);
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        let updates = waiter.join().unwrap();
+        assert_eq!(updates.len(), 2);
+        assert!(updates
+            .iter()
+            .all(|progress| progress.phase == GitProgressPhase::WaitingForRepository));
+        assert!(!updates[0].done && updates[1].done);
+    }
+
+    #[test]
+    fn cancelling_stops_the_wait() {
+        let dir = tempdir().unwrap();
+        let locks = RepoLocks::default();
+        let _guard = locks.lock(dir.path(), GitMonitor::default()).unwrap();
+        let cancel = CancelToken::default();
+        let canceller = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            canceller.cancel();
+        });
+
+        let err = locks
+            .lock(
+                dir.path(),
+                GitMonitor {
+                    on_progress: None,
+                    cancel: Some(&cancel),
+                },
+            )
+            .unwrap_err();
+
+        assert!(matches!(err, WorkspaceError::Cancelled { .. }));
+    }
+}
@@ -1,16 +1,59 @@
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use super::model::{now_iso8601, KnownRepoOption, Workspace, WorkspaceSourceType, WorkspaceStatus};
+use super::model::{
+    now_iso8601, AutoArchivePolicy, CloneStrategy, KnownRepoOption, RegistryBackup, Workspace,
+    WorkspaceSourceType, WorkspaceStatus,
+};
 use super::WorkspaceError;
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Number of rotating copies kept next to the registry (`workspaces.json.1..N`).
+pub const REGISTRY_BACKUP_COUNT: usize = 5;
+
+/// Version written to `schemaVersion`. Bumping it requires appending the
+/// matching step to `REGISTRY_MIGRATIONS` and a fixture under `fixtures/`.
+pub const REGISTRY_SCHEMA_VERSION: u32 = 1;
+
+type RegistryMigration = fn(&mut Value) -> Result<(), WorkspaceError>;
+
+/// `REGISTRY_MIGRATIONS[n]` upgrades a raw registry from version `n` to `n + 1`.
+const REGISTRY_MIGRATIONS: [RegistryMigration; REGISTRY_SCHEMA_VERSION as usize] =
+    [migrate_v0_to_v1];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct WorkspaceRegistry {
+    schema_version: u32,
     workspaces: Vec<Workspace>,
     active_workspace_id: Option<String>,
+    #[serde(default)]
+    auto_archive_policy: AutoArchivePolicy,
+    /// Default clone strategy per lowercase `owner/repo`. Repositories not
+    /// listed use `CloneStrategy::Full`.
+    #[serde(default)]
+    clone_strategies: BTreeMap<String, CloneStrategy>,
+    /// Disk budget for repo caches. Unused caches are evicted to stay under it.
+    #[serde(default)]
+    cache_budget_bytes: Option<u64>,
+}
+
+impl Default for WorkspaceRegistry {
+    fn default() -> Self {
+        Self {
+            schema_version: REGISTRY_SCHEMA_VERSION,
+            workspaces: Vec::new(),
+            active_workspace_id: None,
+            auto_archive_policy: AutoArchivePolicy::default(),
+            clone_strategies: BTreeMap::new(),
+            cache_budget_bytes: None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -31,8 +74,16 @@ impl WorkspaceStore {
         let mut store = Self::new(app_data_dir);
         let registry_path = store.registry_path();
         if registry_path.exists() {
-            let content = fs::read_to_string(registry_path)?;
-            store.registry = serde_json::from_str(&content)?;
+            let content = fs::read_to_string(&registry_path)?;
+            let (registry, from_version) = parse_registry(&content)?;
+            store.registry = registry;
+            if from_version < REGISTRY_SCHEMA_VERSION {
+                write_atomically(
+                    &sibling_path(&registry_path, &format!("pre-migration-v{from_version}")),
+                    content.as_bytes(),
+                )?;
+                store.save()?;
+            }
         }
         Ok(store)
     }
@@ -43,10 +94,57 @@ impl WorkspaceStore {
             fs::create_dir_all(parent)?;
         }
         let serialized = serde_json::to_string_pretty(&self.registry)?;
-        fs::write(registry_path, serialized)?;
+        if registry_path.exists() {
+            self.rotate_backups()?;
+        }
+        write_atomically(&registry_path, serialized.as_bytes())?;
         Ok(())
     }
 
+    pub fn list_backups(&self) -> Result<Vec<RegistryBackup>, WorkspaceError> {
+        let mut backups = Vec::new();
+        for index in 1..=REGISTRY_BACKUP_COUNT {
+            let path = self.backup_path(index);
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            let workspace_count = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| parse_registry(&content).ok())
+                .map(|(registry, _)| registry.workspaces.len());
+            backups.push(RegistryBackup {
+                index,
+                path: path.to_string_lossy().to_string(),
+                modified_at: metadata.modified().ok().map(|modified| {
+                    DateTime::<Utc>::from(modified).to_rfc3339_opts(SecondsFormat::Millis, true)
+                }),
+                size_bytes: metadata.len(),
+                workspace_count,
+            });
+        }
+        Ok(backups)
+    }
+
+    /// Replaces the in-memory registry with backup `index` and saves it. The
+    /// registry being replaced is rotated into `.1`, so a restore can be undone.
+    pub fn restore_backup(&mut self, index: usize) -> Result<(), WorkspaceError> {
+        if index == 0 || index > REGISTRY_BACKUP_COUNT {
+            return Err(WorkspaceError::InvalidInput(format!(
+                "Backup index must be between 1 and {REGISTRY_BACKUP_COUNT}"
+            )));
+        }
+        let path = self.backup_path(index);
+        if !path.exists() {
+            return Err(WorkspaceError::NotFound(format!(
+                "Registry backup not found: {}",
+                path.display()
+            )));
+        }
+        let content = fs::read_to_string(&path)?;
+        self.registry = parse_registry(&content)?.0;
+        self.save()
+    }
+
     pub fn list(&self) -> Vec<Workspace> {
         self.registry.workspaces.clone()
     }
@@ -55,6 +153,56 @@ impl WorkspaceStore {
         self.registry.workspaces.push(workspace);
     }
 
+    pub fn get(&self, id: &str) -> Result<Workspace, WorkspaceError> {
+        self.registry
+            .workspaces
+            .iter()
+            .find(|workspace| workspace.id == id)
+            .cloned()
+            .ok_or_else(|| WorkspaceError::NotFound(format!("Workspace not found: {id}")))
+    }
+
+    /// Applies `change` to the workspace and bumps `updated_at`.
+    pub fn update(
+        &mut self,
+        id: &str,
+        change: impl FnOnce(&mut Workspace),
+    ) -> Result<Workspace, WorkspaceError> {
+        let workspace = self
+            .registry
+            .workspaces
+            .iter_mut()
+            .find(|workspace| workspace.id == id)
+            .ok_or_else(|| WorkspaceError::NotFound(format!("Workspace not found: {id}")))?;
+        change(workspace);
+        workspace.updated_at = now_iso8601();
+        Ok(workspace.clone())
+    }
+
+    pub fn mark_error(&mut self, id: &str, reason: String) -> Result<Workspace, WorkspaceError> {
+        self.update(id, |workspace| {
+            workspace.status = WorkspaceStatus::Error;
+            workspace.error_message = Some(reason);
+        })
+    }
+
+    /// Marks workspaces left in `Creating` by a previous run as failed, so
+    /// their creation can be retried. Returns how many were marked.
+    pub fn fail_interrupted_creates(&mut self) -> usize {
+        let now = now_iso8601();
+        let mut count = 0;
+        for workspace in &mut self.registry.workspaces {
+            if workspace.status == WorkspaceStatus::Creating {
+                workspace.status = WorkspaceStatus::Error;
+                workspace.error_message =
+                    Some("Creation was interrupted when the app closed".to_string());
+                workspace.updated_at = now.clone();
+                count += 1;
+            }
+        }
+        count
+    }
+
     pub fn set_active(&mut self, id: &str) -> Result<(), WorkspaceError> {
         let now = now_iso8601();
         let workspace = self
@@ -73,6 +221,39 @@ impl WorkspaceStore {
         self.registry.active_workspace_id.clone()
     }
 
+    pub fn auto_archive_policy(&self) -> AutoArchivePolicy {
+        self.registry.auto_archive_policy
+    }
+
+    pub fn set_auto_archive_policy(&mut self, policy: AutoArchivePolicy) {
+        self.registry.auto_archive_policy = policy;
+    }
+
+    pub fn cache_budget_bytes(&self) -> Option<u64> {
+        self.registry.cache_budget_bytes
+    }
+
+    pub fn set_cache_budget_bytes(&mut self, max_bytes: Option<u64>) {
+        self.registry.cache_budget_bytes = max_bytes;
+    }
+
+    pub fn clone_strategy(&self, repo_id: &str) -> CloneStrategy {
+        self.registry
+            .clone_strategies
+            .get(&repo_id.to_ascii_lowercase())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_clone_strategy(&mut self, repo_id: &str, strategy: CloneStrategy) {
+        let key = repo_id.to_ascii_lowercase();
+        if strategy == CloneStrategy::default() {
+            self.registry.clone_strategies.remove(&key);
+        } else {
+            self.registry.clone_strategies.insert(key, strategy);
+        }
+    }
+
     pub fn archive(&mut self, id: &str) -> Result<(), WorkspaceError> {
         let workspace = self
             .registry
@@ -108,6 +289,38 @@ impl WorkspaceStore {
             .join("workspaces.json")
     }
 
+    /// Moves the registry file aside as `workspaces.json.corrupt-<timestamp>`
+    /// and returns the new path.
+    pub fn quarantine_registry(&self) -> Result<PathBuf, WorkspaceError> {
+        let registry_path = self.registry_path();
+        let timestamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+        let quarantined = sibling_path(&registry_path, &format!("corrupt-{timestamp}"));
+        fs::rename(&registry_path, &quarantined)?;
+        Ok(quarantined)
+    }
+
+    pub fn backup_path(&self, index: usize) -> PathBuf {
+        sibling_path(&self.registry_path(), &index.to_string())
+    }
+
+    fn rotate_backups(&self) -> Result<(), WorkspaceError> {
+        let oldest = self.backup_path(REGISTRY_BACKUP_COUNT);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for index in (1..REGISTRY_BACKUP_COUNT).rev() {
+            let from = self.backup_path(index);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(index + 1))?;
+            }
+        }
+        // Copy rather than rename so a valid registry stays on disk until the
+        // replacement has been written.
+        let current = fs::read(self.registry_path())?;
+        write_atomically(&self.backup_path(1), &current)?;
+        Ok(())
+    }
+
     pub fn list_known_repos(&self, query: Option<&str>) -> Vec<KnownRepoOption> {
         let mut dedup = std::collections::HashMap::<String, KnownRepoOption>::new();
         for workspace in &self.registry.workspaces {
@@ -148,6 +361,76 @@ impl WorkspaceStore {
     }
 }
 
+/// Parses a registry of any supported schema version, applying forward
+/// migrations in order. Returns the registry and the version it was read at.
+fn parse_registry(content: &str) -> Result<(WorkspaceRegistry, u32), WorkspaceError> {
+    let mut value: Value = serde_json::from_str(content)?;
+    let from_version = match value.get("schemaVersion") {
+        Some(version) => serde_json::from_value::<u32>(version.clone())?,
+        None => 0,
+    };
+    if from_version > REGISTRY_SCHEMA_VERSION {
+        return Err(WorkspaceError::UnsupportedSchemaVersion {
+            found: from_version,
+            supported: REGISTRY_SCHEMA_VERSION,
+        });
+    }
+    for (version, migrate) in REGISTRY_MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip(from_version as usize)
+    {
+        migrate(&mut value)?;
+        let object = value.as_object_mut().ok_or_else(|| {
+            WorkspaceError::Serde(serde::de::Error::custom("registry must be a JSON object"))
+        })?;
+        object.insert("schemaVersion".to_string(), Value::from(version as u32 + 1));
+    }
+    Ok((serde_json::from_value(value)?, from_version))
+}
+
+/// v0 registries predate `schemaVersion`; their shape is otherwise unchanged.
+fn migrate_v0_to_v1(_registry: &mut Value) -> Result<(), WorkspaceError> {
+    Ok(())
+}
+
+/// Writes `contents` to a temp file next to `path`, fsyncs it and renames it
+/// over `path`, so readers only ever observe the old or the new file.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), WorkspaceError> {
+    let tmp_path = sibling_path(path, "tmp");
+    {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    if let Err(err) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err.into());
+    }
+    sync_parent_dir(path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<(), WorkspaceError> {
+    if let Some(parent) = path.parent() {
+        fs::File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<(), WorkspaceError> {
+    Ok(())
+}
+
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
 fn repo_id_from_source(source: &str) -> Option<String> {
     let parsed = url::Url::parse(source).ok()?;
     if parsed.host_str() != Some("github.com") {
@@ -189,6 +472,12 @@ mod tests {
             created_at: now_iso8601(),
             updated_at: now_iso8601(),
             last_opened_at: None,
+            error_message: None,
+            pull_request: None,
+            source_pull_request: None,
+            parent_workspace_id: None,
+            sparse_paths: Vec::new(),
+            create_job: None,
         }
     }
 
@@ -203,6 +492,141 @@ mod tests {
         assert_eq!(loaded.list().len(), 1);
     }
 
+    #[test]
+    fn save_replaces_registry_without_leaving_temp_file() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.insert(sample_workspace("ws_1"));
+        store.save().unwrap();
+        store.insert(sample_workspace("ws_2"));
+        store.save().unwrap();
+
+        let loaded = WorkspaceStore::load(dir.path()).unwrap();
+        assert_eq!(loaded.list().len(), 2);
+        assert!(!sibling_path(&store.registry_path(), "tmp").exists());
+    }
+
+    #[test]
+    fn save_rotates_previous_registry_into_numbered_backups() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.save().unwrap();
+        assert!(!store.backup_path(1).exists());
+
+        for index in 0..REGISTRY_BACKUP_COUNT + 2 {
+            store.insert(sample_workspace(&format!("ws_{index}")));
+            store.save().unwrap();
+        }
+
+        let backups = store.list_backups().unwrap();
+        assert_eq!(backups.len(), REGISTRY_BACKUP_COUNT);
+        assert_eq!(backups[0].index, 1);
+        assert_eq!(backups[0].workspace_count, Some(REGISTRY_BACKUP_COUNT + 1));
+        assert_eq!(backups[REGISTRY_BACKUP_COUNT - 1].workspace_count, Some(2));
+        assert!(!store.backup_path(REGISTRY_BACKUP_COUNT + 1).exists());
+    }
+
+    #[test]
+    fn restore_backup_replaces_registry_and_keeps_current_as_backup() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.insert(sample_workspace("ws_1"));
+        store.save().unwrap();
+        store.insert(sample_workspace("ws_2"));
+        store.save().unwrap();
+
+        store.restore_backup(1).unwrap();
+
+        assert_eq!(store.list().len(), 1);
+        let loaded = WorkspaceStore::load(dir.path()).unwrap();
+        assert_eq!(loaded.list().len(), 1);
+        assert_eq!(store.list_backups().unwrap()[0].workspace_count, Some(2));
+    }
+
+    #[test]
+    fn restore_backup_rejects_missing_or_out_of_range_index() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        assert!(store
+            .restore_backup(1)
+            .unwrap_err()
+            .to_string()
+            .contains("not found"));
+        assert!(store
+            .restore_backup(REGISTRY_BACKUP_COUNT + 1)
+            .unwrap_err()
+            .to_string()
+            .contains("between"));
+    }
+
+    /// One fixture per historical schema version, indexed by version.
+    const REGISTRY_FIXTURES: [&str; REGISTRY_SCHEMA_VERSION as usize + 1] = [
+        include_str!("fixtures/registry-v0.json"),
+        include_str!("fixtures/registry-v1.json"),
+    ];
+
+    #[test]
+    fn loads_and_migrates_every_historical_registry_fixture() {
+        for (version, fixture) in REGISTRY_FIXTURES.iter().enumerate() {
+            let dir = tempdir().unwrap();
+            let store = WorkspaceStore::new(dir.path());
+            fs::create_dir_all(store.registry_path().parent().unwrap()).unwrap();
+            fs::write(store.registry_path(), fixture).unwrap();
+
+            let loaded = WorkspaceStore::load(dir.path()).unwrap();
+
+            assert_eq!(loaded.list().len(), 2, "fixture v{version}");
+            assert_eq!(
+                loaded.active_workspace_id(),
+                Some("ws_fixture1".to_string())
+            );
+            let saved: Value =
+                serde_json::from_str(&fs::read_to_string(loaded.registry_path()).unwrap()).unwrap();
+            assert_eq!(saved["schemaVersion"], Value::from(REGISTRY_SCHEMA_VERSION));
+            let pre_migration = sibling_path(
+                &loaded.registry_path(),
+                &format!("pre-migration-v{version}"),
+            );
+            if version < REGISTRY_SCHEMA_VERSION as usize {
+                assert_eq!(fs::read_to_string(pre_migration).unwrap(), *fixture);
+            } else {
+                assert!(!pre_migration.exists());
+            }
+        }
+    }
+
+    #[test]
+    fn refuses_to_load_registry_from_newer_schema_version() {
+        let dir = tempdir().unwrap();
+        let store = WorkspaceStore::new(dir.path());
+        fs::create_dir_all(store.registry_path().parent().unwrap()).unwrap();
+        let newer = format!(
+            "{{\"schemaVersion\": {}, \"workspaces\": [], \"activeWorkspaceId\": null}}",
+            REGISTRY_SCHEMA_VERSION + 1
+        );
+        fs::write(store.registry_path(), &newer).unwrap();
+
+        let err = WorkspaceStore::load(dir.path()).unwrap_err();
+
+        assert!(matches!(
+            err,
+            WorkspaceError::UnsupportedSchemaVersion { .. }
+        ));
+        assert_eq!(fs::read_to_string(store.registry_path()).unwrap(), newer);
+    }
+
+    #[test]
+    fn restores_backup_written_by_older_schema_version() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.save().unwrap();
+        fs::write(store.backup_path(1), REGISTRY_FIXTURES[0]).unwrap();
+
+        store.restore_backup(1).unwrap();
+
+        assert_eq!(store.list().len(), 2);
+    }
+
     #[test]
     fn sets_active_workspace_id() {
         let dir = tempdir().unwrap();
@@ -220,6 +644,42 @@ mod tests {
         assert!(err.to_string().contains("not found"));
     }
 
+    #[test]
+    fn persists_auto_archive_policy() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        assert_eq!(store.auto_archive_policy(), AutoArchivePolicy::default());
+        let policy = AutoArchivePolicy {
+            archive_merged: true,
+            remove_worktree: true,
+            delete_branch: false,
+        };
+
+        store.set_auto_archive_policy(policy);
+        store.save().unwrap();
+
+        let loaded = WorkspaceStore::load(dir.path()).unwrap();
+        assert_eq!(loaded.auto_archive_policy(), policy);
+    }
+
+    #[test]
+    fn persists_clone_strategy_per_repository() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.set_clone_strategy("Acme/Widgets", CloneStrategy::Shallow { depth: 50 });
+        store.set_clone_strategy("acme/gadgets", CloneStrategy::Blobless);
+        store.set_clone_strategy("acme/gadgets", CloneStrategy::Full);
+        store.save().unwrap();
+
+        let loaded = WorkspaceStore::load(dir.path()).unwrap();
+        assert_eq!(
+            loaded.clone_strategy("acme/widgets"),
+            CloneStrategy::Shallow { depth: 50 }
+        );
+        assert_eq!(loaded.clone_strategy("acme/gadgets"), CloneStrategy::Full);
+        assert_eq!(loaded.registry.clone_strategies.len(), 1);
+    }
+
     #[test]
     fn archive_sets_status_and_clears_active_when_archiving_active_workspace() {
         let dir = tempdir().unwrap();
@@ -256,6 +716,41 @@ mod tests {
         assert!(err.to_string().contains("not found"));
     }
 
+    #[test]
+    fn fails_workspaces_left_creating() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        let mut creating = sample_workspace("ws_1");
+        creating.status = WorkspaceStatus::Creating;
+        store.insert(creating);
+        store.insert(sample_workspace("ws_2"));
+
+        assert_eq!(store.fail_interrupted_creates(), 1);
+
+        assert_eq!(store.get("ws_1").unwrap().status, WorkspaceStatus::Error);
+        assert_eq!(store.get("ws_2").unwrap().status, WorkspaceStatus::Ready);
+        assert_eq!(store.fail_interrupted_creates(), 0);
+    }
+
+    #[test]
+    fn mark_error_records_reason_and_status() {
+        let dir = tempdir().unwrap();
+        let mut store = WorkspaceStore::new(dir.path());
+        store.insert(sample_workspace("ws_1"));
+
+        store
+            .mark_error("ws_1", "Worktree folder is missing".to_string())
+            .unwrap();
+
+        let workspace = store.get("ws_1").unwrap();
+        assert_eq!(workspace.status, WorkspaceStatus::Error);
+        assert_eq!(
+            workspace.error_message.as_deref(),
+            Some("Worktree folder is missing")
+        );
+        assert!(store.get("ws_missing").is_err());
+    }
+
     #[test]
     fn remove_returns_workspace_and_clears_active() {
         let dir = tempdir().unwrap();
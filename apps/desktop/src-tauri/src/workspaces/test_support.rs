@@ -0,0 +1,40 @@
+//! Git helpers shared by the workspace tests.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(repo_path: &Path, args: &[&str]) -> Output {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["-c", "user.name=Kata", "-c", "user.email=kata@example.com"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Runs git in `repo_path` with a test identity and returns its trimmed
+/// stdout, failing the test if git fails.
+pub fn git(repo_path: &Path, args: &[&str]) -> String {
+    let output = run(repo_path, args);
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Runs git like `git`, for commands that are expected to fail.
+pub fn git_succeeds(repo_path: &Path, args: &[&str]) -> bool {
+    run(repo_path, args).status.success()
+}
+
+/// Creates a repository at `path` on `main` with one empty commit.
+pub fn init_repo(path: &Path) {
+    fs::create_dir_all(path).unwrap();
+    git(path, &["init", "-b", "main"]);
+    git(path, &["commit", "--allow-empty", "-m", "init"]);
+}